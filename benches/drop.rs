@@ -0,0 +1,40 @@
+//! Compares streaming a document with and without `--drop` of its large field, which is skipped
+//! without visiting it. Run with `cargo bench --bench drop`.
+
+use std::time::{Duration, Instant};
+
+use stream::{path::parse_path, stream, Json, Options};
+
+/// `{"big": [{"id": 0, "tags": ["a", "b"], "score": 0.5}, ...], "small": 1}`.
+fn document(elements: usize) -> Vec<u8> {
+    let big: Vec<_> = (0..elements)
+        .map(|id| serde_json::json!({"id": id, "tags": ["a", "b"], "score": id as f64 / 2.0}))
+        .collect();
+    serde_json::to_vec(&serde_json::json!({"big": big, "small": 1})).unwrap()
+}
+
+/// The fastest of a few runs, and the number of values streamed.
+fn time(input: &[u8], options: &Options) -> (Duration, usize) {
+    (0..5)
+        .map(|_| {
+            let start = Instant::now();
+            let input = std::io::Cursor::new(input.to_vec());
+            let values = stream::<Json, _>(input, options.clone()).count();
+            (start.elapsed(), values)
+        })
+        .min()
+        .unwrap()
+}
+
+fn main() {
+    let input = document(50_000);
+    let (all, all_values) = time(&input, &Options::default());
+    let dropping = Options::builder()
+        .drop(vec![parse_path(".big").unwrap()])
+        .build();
+    let (dropped, dropped_values) = time(&input, &dropping);
+    println!("{} bytes of input", input.len());
+    println!("without --drop: {all:?} for {all_values} values");
+    println!("with --drop .big: {dropped:?} for {dropped_values} values");
+    println!("speedup: {:.1}x", all.as_secs_f64() / dropped.as_secs_f64());
+}
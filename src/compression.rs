@@ -0,0 +1,43 @@
+//! Compressed input, told apart by the extension of its file.
+
+use std::{
+    io::{self, Read},
+    path::Path,
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Compression {
+    /// `.gz`, including files of several gzip members one after the other.
+    Gzip,
+    /// `.zst`.
+    Zstd,
+}
+
+impl std::fmt::Display for Compression {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Compression::Gzip => write!(f, "gzip"),
+            Compression::Zstd => write!(f, "zstd"),
+        }
+    }
+}
+
+impl Compression {
+    /// The compression of the file at `path` by its last extension, e.g. [`Compression::Zstd`]
+    /// for `data.yaml.zst`.
+    pub fn from_path(path: &Path) -> Option<Self> {
+        match path.extension()?.to_str()? {
+            "gz" => Some(Compression::Gzip),
+            "zst" => Some(Compression::Zstd),
+            _ => None,
+        }
+    }
+
+    /// Decompresses `read`.
+    pub fn decoder<R: Read + Send + 'static>(self, read: R) -> io::Result<Box<dyn Read + Send>> {
+        Ok(match self {
+            Compression::Gzip => Box::new(flate2::read::MultiGzDecoder::new(read)),
+            Compression::Zstd => Box::new(zstd::stream::read::Decoder::new(read)?),
+        })
+    }
+}
@@ -0,0 +1,132 @@
+//! Converting documents to another format by forwarding the events of the deserializer straight
+//! to a serializer, without [`PathValue`](crate::PathValue)s or documents in between.
+
+use std::{cell::Cell, ptr::NonNull};
+
+use serde::{
+    de::Error as _,
+    ser::{SerializeSeq, Serializer},
+};
+
+use crate::{DeserializeError, FromReader, MultiDocDeserializer, StreamError};
+
+#[derive(Debug)]
+pub enum ConvertError<E> {
+    /// The input couldn't be read, or a document couldn't be deserialized from it.
+    Deserialize(StreamError),
+    /// The serializer failed. Since the events of a document are forwarded as they come, this
+    /// also covers errors of the input in the middle of a document, which the deserializer reports
+    /// to the serializer.
+    Serialize(E),
+}
+
+impl<E: std::fmt::Display> std::fmt::Display for ConvertError<E> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConvertError::Deserialize(e) => write!(f, "{e}"),
+            ConvertError::Serialize(e) => write!(f, "Serialization error: {e}"),
+        }
+    }
+}
+
+impl<E: std::error::Error + 'static> std::error::Error for ConvertError<E> {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ConvertError::Deserialize(e) => Some(e),
+            ConvertError::Serialize(e) => Some(e),
+        }
+    }
+}
+
+/// The sequence being serialized, as seen while deserializing each of its elements.
+trait Elements {
+    /// Serializes the document `de` is at as the next element, returning whether that succeeded.
+    fn transcode<'de>(&mut self, de: &mut dyn erased_serde::Deserializer<'de>) -> bool;
+}
+
+struct Seq<S: SerializeSeq> {
+    seq: S,
+    error: Option<S::Error>,
+}
+
+impl<S: SerializeSeq> Elements for Seq<S> {
+    fn transcode<'de>(&mut self, de: &mut dyn erased_serde::Deserializer<'de>) -> bool {
+        match self
+            .seq
+            .serialize_element(&serde_transcode::Transcoder::new(de))
+        {
+            Ok(()) => true,
+            Err(e) => {
+                self.error = Some(e);
+                false
+            }
+        }
+    }
+}
+
+thread_local! {
+    /// The [`Seq`] of the innermost [`convert`] running in this thread.
+    static ELEMENTS: Cell<Option<NonNull<dyn Elements>>> = const { Cell::new(None) };
+}
+
+/// Restores [`ELEMENTS`] once [`convert`] is done with its [`Seq`], even if it panics.
+struct Restore(Option<NonNull<dyn Elements>>);
+
+impl Drop for Restore {
+    fn drop(&mut self) {
+        ELEMENTS.set(self.0);
+    }
+}
+
+struct Document;
+
+impl<'de> serde::Deserialize<'de> for Document {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let mut elements = ELEMENTS.get().expect("only deserialized by convert");
+        // SAFETY: `convert` keeps the `Seq` alive and otherwise untouched for as long as it's set,
+        // and only this thread sees it.
+        let elements = unsafe { elements.as_mut() };
+        let mut deserializer = <dyn erased_serde::Deserializer>::erase(deserializer);
+        if elements.transcode(&mut deserializer) {
+            Ok(Document)
+        } else {
+            Err(D::Error::custom("failed to serialize the document"))
+        }
+    }
+}
+
+/// Converts every document in `read`, parsed as `T`, to `ser` as the elements of a sequence, by
+/// forwarding the events of the deserializer as they come. Much like `jq -n [inputs]`, but without
+/// holding more than the deserializer and the serializer need to.
+///
+/// The output is a sequence even if there's only one document. Unlike [`crate::stream`], the
+/// documents are only ever asked for with `deserialize_any`, so [`MultiDocDeserializer::HINT`] is
+/// ignored, and parsing happens in the calling thread.
+pub fn convert<T, R, S>(read: R, ser: S) -> Result<S::Ok, ConvertError<S::Error>>
+where
+    T: FromReader,
+    R: std::io::Read,
+    S: Serializer,
+{
+    let mut seq = Seq {
+        seq: ser.serialize_seq(None).map_err(ConvertError::Serialize)?,
+        error: None,
+    };
+    let result = {
+        let elements: NonNull<dyn Elements + '_> = NonNull::from(&mut seq as &mut dyn Elements);
+        // SAFETY: Only erases the lifetime, which `Restore` ends before `seq` is used again.
+        let elements: NonNull<dyn Elements> = unsafe { std::mem::transmute(elements) };
+        let _restore = Restore(ELEMENTS.replace(Some(elements)));
+        T::from_reader(read)
+            .into_multidoc_iter::<Document>()
+            .try_for_each(|document| document.map(|_| ()))
+    };
+    if let Some(e) = seq.error {
+        return Err(ConvertError::Serialize(e));
+    }
+    result.map_err(|e| ConvertError::Deserialize(e.into_stream_error(T::FORMAT)))?;
+    seq.seq.end().map_err(ConvertError::Serialize)
+}
@@ -0,0 +1,145 @@
+//! Decoding input that has been wrapped in a text encoding before it's parsed.
+
+use std::io::{self, BufRead, BufReader, Read};
+
+use base64::{DecodeError, Engine};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum Encoding {
+    /// Base64 with the standard alphabet and padding, like `base64` writes.
+    Base64,
+    /// Hexadecimal, in upper or lower case.
+    Hex,
+}
+
+impl std::fmt::Display for Encoding {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Encoding::Base64 => write!(f, "base64"),
+            Encoding::Hex => write!(f, "hex"),
+        }
+    }
+}
+
+impl Encoding {
+    /// How many encoded characters decode to a whole number of bytes.
+    fn quantum(self) -> usize {
+        match self {
+            Encoding::Base64 => 4,
+            Encoding::Hex => 2,
+        }
+    }
+
+    fn decode(self, encoded: &[u8], out: &mut Vec<u8>) -> Result<(), String> {
+        match self {
+            Encoding::Base64 => base64::engine::general_purpose::STANDARD
+                .decode_vec(encoded, out)
+                .map_err(|e| match e {
+                    DecodeError::InvalidByte(offset, c)
+                    | DecodeError::InvalidLastSymbol {
+                        offset, symbol: c, ..
+                    } => format!(
+                        "invalid character `{}` at offset {offset}",
+                        c.escape_ascii()
+                    ),
+                    DecodeError::InvalidLength(_) => "truncated".to_string(),
+                    DecodeError::InvalidPadding => "invalid padding".to_string(),
+                }),
+            Encoding::Hex => {
+                if !encoded.len().is_multiple_of(2) {
+                    return Err("odd number of digits".to_string());
+                }
+                let digit = |c: u8| match c {
+                    b'0'..=b'9' => Ok(c - b'0'),
+                    b'a'..=b'f' => Ok(c - b'a' + 10),
+                    b'A'..=b'F' => Ok(c - b'A' + 10),
+                    _ => Err(format!("invalid digit `{}`", c.escape_ascii())),
+                };
+                for pair in encoded.chunks(2) {
+                    out.push(digit(pair[0])? << 4 | digit(pair[1])?);
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+/// A [`Read`] adapter that decodes the inner reader as `encoding`, line by line.
+///
+/// By default, each line is decoded on its own and followed by a newline, for inputs with one
+/// encoded document per line. Blank lines are skipped. With `whole`, the lines are decoded as if
+/// they were one, without newlines in between, for a single encoded input wrapped over many lines
+/// like `base64` does, or for binary formats.
+///
+/// Invalid input fails with [`io::ErrorKind::InvalidData`], mentioning its line.
+pub struct DecodeReader<R> {
+    inner: BufReader<R>,
+    encoding: Encoding,
+    whole: bool,
+    line: usize,
+    /// Encoded characters of the lines read so far that don't make up a whole quantum yet.
+    encoded: Vec<u8>,
+    decoded: Vec<u8>,
+    pos: usize,
+}
+
+impl<R: Read> DecodeReader<R> {
+    pub fn new(inner: R, encoding: Encoding, whole: bool) -> Self {
+        Self {
+            inner: BufReader::new(inner),
+            encoding,
+            whole,
+            line: 0,
+            encoded: vec![],
+            decoded: vec![],
+            pos: 0,
+        }
+    }
+
+    /// Decodes the next line into `decoded`, leaving it empty at the end of the input.
+    fn fill(&mut self) -> io::Result<()> {
+        self.decoded.clear();
+        self.pos = 0;
+        while self.decoded.is_empty() {
+            let mut line = vec![];
+            if self.inner.read_until(b'\n', &mut line)? == 0 {
+                let rest = std::mem::take(&mut self.encoded);
+                return self.decode(&rest);
+            }
+            self.line += 1;
+            self.encoded.extend(line.trim_ascii());
+            if !self.whole {
+                let encoded = std::mem::take(&mut self.encoded);
+                self.decode(&encoded)?;
+                if !self.decoded.is_empty() {
+                    self.decoded.push(b'\n');
+                }
+            } else {
+                let len = self.encoded.len() / self.encoding.quantum() * self.encoding.quantum();
+                let encoded: Vec<_> = self.encoded.drain(..len).collect();
+                self.decode(&encoded)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn decode(&mut self, encoded: &[u8]) -> io::Result<()> {
+        let (encoding, line) = (self.encoding, self.line.max(1));
+        encoding.decode(encoded, &mut self.decoded).map_err(|e| {
+            let message = format!("Invalid {encoding} on line {line}: {e}");
+            io::Error::new(io::ErrorKind::InvalidData, message)
+        })
+    }
+}
+
+impl<R: Read> Read for DecodeReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.pos == self.decoded.len() {
+            self.fill()?;
+        }
+        let n = buf.len().min(self.decoded.len() - self.pos);
+        buf[..n].copy_from_slice(&self.decoded[self.pos..self.pos + n]);
+        self.pos += n;
+        Ok(n)
+    }
+}
@@ -0,0 +1,172 @@
+//! Structural differences between two streams of [`PathValue`]s, compared leaf by leaf.
+
+use std::collections::{HashMap, VecDeque};
+
+use crate::{Path, PathValue, PrimitiveValue, StreamError};
+
+/// A difference between the leaves of two streams, as reported by [`diff`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Diff {
+    /// There's a leaf at `path` only in the second stream.
+    Added { path: Path, value: PrimitiveValue },
+    /// There's a leaf at `path` only in the first stream.
+    Removed { path: Path, value: PrimitiveValue },
+    /// There are leaves at `path` in both streams, but with different values.
+    Changed {
+        path: Path,
+        old: PrimitiveValue,
+        new: PrimitiveValue,
+    },
+}
+
+/// Leaves of one side that the other side hasn't had at the same path yet, with the order they
+/// came in, so that those never matched are reported in that order.
+#[derive(Default)]
+struct Pending {
+    leaves: HashMap<Path, (usize, PrimitiveValue)>,
+    count: usize,
+}
+
+impl Pending {
+    fn insert(&mut self, path: Path, value: PrimitiveValue) {
+        self.leaves.insert(path, (self.count, value));
+        self.count += 1;
+    }
+
+    fn into_sorted(self) -> Vec<(Path, PrimitiveValue)> {
+        let mut leaves: Vec<_> = self.leaves.into_iter().collect();
+        leaves.sort_by_key(|(_, (i, _))| *i);
+        leaves
+            .into_iter()
+            .map(|(path, (_, value))| (path, value))
+            .collect()
+    }
+}
+
+/// The iterator of [`diff`].
+pub struct Diffs<A, B> {
+    a: Option<A>,
+    b: Option<B>,
+    pending_a: Pending,
+    pending_b: Pending,
+    ready: VecDeque<Result<Diff, StreamError>>,
+}
+
+impl<A, B> Diffs<A, B>
+where
+    A: Iterator<Item = Result<PathValue, StreamError>>,
+    B: Iterator<Item = Result<PathValue, StreamError>>,
+{
+    /// Takes the next leaf of `side`, ending it if it's done. Close markers are skipped and errors
+    /// are queued as they are.
+    fn next_leaf<I>(
+        side: &mut Option<I>,
+        ready: &mut VecDeque<Result<Diff, StreamError>>,
+    ) -> Option<(Path, PrimitiveValue)>
+    where
+        I: Iterator<Item = Result<PathValue, StreamError>>,
+    {
+        loop {
+            match side.as_mut()?.next() {
+                Some(Ok(PathValue {
+                    path,
+                    value: Some(value),
+                    ..
+                })) => return Some((path, value)),
+                Some(Ok(_)) => continue,
+                Some(Err(e)) => {
+                    ready.push_back(Err(e));
+                    return None;
+                }
+                None => {
+                    *side = None;
+                    return None;
+                }
+            }
+        }
+    }
+
+    fn changed(path: Path, old: PrimitiveValue, new: PrimitiveValue) -> Option<Diff> {
+        (old != new).then_some(Diff::Changed { path, old, new })
+    }
+
+    /// Reads a leaf from each side, and queues whatever can be told about them.
+    fn step(&mut self) {
+        let a = Self::next_leaf(&mut self.a, &mut self.ready);
+        let b = Self::next_leaf(&mut self.b, &mut self.ready);
+        match (a, b) {
+            (Some((path_a, old)), Some((path_b, new))) if path_a == path_b => {
+                self.ready.extend(Self::changed(path_a, old, new).map(Ok));
+            }
+            (a, b) => {
+                if let Some((path, old)) = a {
+                    match self.pending_b.leaves.remove(&path) {
+                        Some((_, new)) => self.ready.extend(Self::changed(path, old, new).map(Ok)),
+                        None => self.pending_a.insert(path, old),
+                    }
+                }
+                if let Some((path, new)) = b {
+                    match self.pending_a.leaves.remove(&path) {
+                        Some((_, old)) => self.ready.extend(Self::changed(path, old, new).map(Ok)),
+                        None => self.pending_b.insert(path, new),
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl<A, B> Iterator for Diffs<A, B>
+where
+    A: Iterator<Item = Result<PathValue, StreamError>>,
+    B: Iterator<Item = Result<PathValue, StreamError>>,
+{
+    type Item = Result<Diff, StreamError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(diff) = self.ready.pop_front() {
+                return Some(diff);
+            }
+            if self.a.is_none() && self.b.is_none() {
+                let removed = std::mem::take(&mut self.pending_a).into_sorted();
+                let added = std::mem::take(&mut self.pending_b).into_sorted();
+                self.ready.extend(
+                    removed
+                        .into_iter()
+                        .map(|(path, value)| Ok(Diff::Removed { path, value })),
+                );
+                self.ready.extend(
+                    added
+                        .into_iter()
+                        .map(|(path, value)| Ok(Diff::Added { path, value })),
+                );
+                return self.ready.pop_front();
+            }
+            self.step();
+        }
+    }
+}
+
+/// Compares the leaves of `a` and `b` by path, much like a structural diff of the documents they
+/// were streamed from. Close markers are ignored, and errors of either stream are passed through.
+/// Each stream should hold a single document, since paths are only unique within one.
+///
+/// The streams are read side by side, so leaves at the same paths in the same order are compared
+/// as they come and hold nothing. Leaves that come in a different order, e.g. because the keys of
+/// an object were reordered, are held until the other side has a leaf at the same path. Changed
+/// values are reported as soon as both sides are found, and leaves that only one side has are
+/// reported at the end in the order they came in, removed ones first.
+pub fn diff<A, B>(a: A, b: B) -> Diffs<A::IntoIter, B::IntoIter>
+where
+    A: IntoIterator<Item = Result<PathValue, StreamError>>,
+    B: IntoIterator<Item = Result<PathValue, StreamError>>,
+{
+    Diffs {
+        a: Some(a.into_iter()),
+        b: Some(b.into_iter()),
+        pending_a: Pending::default(),
+        pending_b: Pending::default(),
+        ready: VecDeque::new(),
+    }
+}
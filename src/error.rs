@@ -0,0 +1,150 @@
+use std::io;
+
+use crate::{path::format_path, Format, Path};
+
+#[derive(Debug)]
+pub enum StreamError {
+    /// The input is not a valid document of the format. `line` and `column` are 1-based, or 0
+    /// if the underlying deserializer doesn't know the position.
+    Deserialize {
+        format: Format,
+        line: usize,
+        column: usize,
+        message: String,
+    },
+    Io(io::Error),
+    /// A container at `path` is nested deeper than `max_depth`.
+    DepthExceeded {
+        path: Path,
+        max_depth: usize,
+    },
+    /// The object at `path` has more than `max_keys` keys.
+    TooManyKeys {
+        path: Path,
+        max_keys: usize,
+    },
+    /// The array at `path` has more than `max_length` elements.
+    ArrayTooLong {
+        path: Path,
+        max_length: usize,
+    },
+    /// With [`crate::Options::require_container`], the `document`-th document, 0-based, is a
+    /// scalar.
+    NotAContainer {
+        document: usize,
+    },
+    /// [`crate::extract`] didn't find anything at `path`.
+    NotFound {
+        path: Path,
+    },
+    /// An error from the `index`-th input of [`crate::stream_merged`] or [`crate::stream_files`].
+    InSource {
+        index: usize,
+        error: Box<StreamError>,
+    },
+}
+
+impl std::fmt::Display for StreamError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            StreamError::Deserialize { message, .. } => {
+                write!(f, "Deserialization error: {message}")
+            }
+            StreamError::Io(e) => write!(f, "I/O error: {e}"),
+            StreamError::DepthExceeded { path, max_depth } => {
+                write!(
+                    f,
+                    "Exceeded the maximum depth {max_depth} at {}",
+                    format_path(path)
+                )
+            }
+            StreamError::TooManyKeys { path, max_keys } => {
+                write!(
+                    f,
+                    "Exceeded the maximum of {max_keys} keys in the object at {}",
+                    format_path(path)
+                )
+            }
+            StreamError::ArrayTooLong { path, max_length } => {
+                write!(
+                    f,
+                    "Exceeded the maximum length {max_length} of the array at {}",
+                    format_path(path)
+                )
+            }
+            StreamError::NotAContainer { document } => {
+                write!(f, "Document {document} is not an array or an object")
+            }
+            StreamError::NotFound { path } => write!(f, "Nothing found at {}", format_path(path)),
+            StreamError::InSource { index, error } => write!(f, "Input {index}: {error}"),
+        }
+    }
+}
+
+impl std::error::Error for StreamError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            StreamError::Io(e) => Some(e),
+            StreamError::InSource { error, .. } => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for StreamError {
+    fn from(e: io::Error) -> Self {
+        StreamError::Io(e)
+    }
+}
+
+/// Errors of the underlying deserializers, that know how to turn themselves into a
+/// [`StreamError`].
+pub trait DeserializeError: std::error::Error {
+    fn into_stream_error(self, format: Format) -> StreamError;
+}
+
+impl DeserializeError for serde_json::Error {
+    fn into_stream_error(self, format: Format) -> StreamError {
+        if self.is_io() {
+            return StreamError::Io(self.into());
+        }
+        StreamError::Deserialize {
+            format,
+            line: self.line(),
+            column: self.column(),
+            message: self.to_string(),
+        }
+    }
+}
+
+impl DeserializeError for serde_yaml::Error {
+    fn into_stream_error(self, format: Format) -> StreamError {
+        let (line, column) = self
+            .location()
+            .map_or((0, 0), |location| (location.line(), location.column()));
+        StreamError::Deserialize {
+            format,
+            line,
+            column,
+            message: self.to_string(),
+        }
+    }
+}
+
+impl DeserializeError for serde_smile::Error {
+    fn into_stream_error(self, format: Format) -> StreamError {
+        use std::error::Error;
+        if let Some(e) = self
+            .source()
+            .and_then(|e| e.downcast_ref::<std::io::Error>())
+        {
+            return StreamError::Io(std::io::Error::new(e.kind(), e.to_string()));
+        }
+        StreamError::Deserialize {
+            format,
+            line: 0,
+            column: 0,
+            message: self.to_string(),
+        }
+    }
+}
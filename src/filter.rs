@@ -0,0 +1,506 @@
+//! Iterator adapters that select which [`PathValue`]s of a stream to keep, or rewrite them.
+
+use std::collections::{HashSet, VecDeque};
+
+use crate::{Index, Path, PathValue, PrimitiveValue, StreamError};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum PickMode {
+    /// Keep leaves exactly at one of the paths.
+    Exact,
+    /// Keep everything under one of the paths, including close markers of containers under them.
+    #[default]
+    Subtree,
+}
+
+impl PickMode {
+    /// Whether [`Pick`] keeps `value` with these paths.
+    pub fn matches(self, paths: &[Path], value: &PathValue) -> bool {
+        paths.iter().any(|path| match self {
+            PickMode::Exact => {
+                value.value.is_some()
+                    && value.path.len() == path.len()
+                    && starts_with(&value.path, path)
+            }
+            PickMode::Subtree => is_under(value, path),
+        })
+    }
+}
+
+/// Keeps only the [`PathValue`]s at or under one of the given paths. Errors are passed through.
+pub struct Pick<I> {
+    inner: I,
+    paths: Vec<Path>,
+    mode: PickMode,
+}
+
+impl<I> Pick<I> {
+    pub fn new(inner: I, paths: Vec<Path>, mode: PickMode) -> Self {
+        Self { inner, paths, mode }
+    }
+
+    fn matches(&self, value: &PathValue) -> bool {
+        self.mode.matches(&self.paths, value)
+    }
+}
+
+impl<I: Iterator<Item = Result<PathValue, StreamError>>> Iterator for Pick<I> {
+    type Item = Result<PathValue, StreamError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            match self.inner.next()? {
+                Ok(value) if !self.matches(&value) => continue,
+                v => return Some(v),
+            }
+        }
+    }
+}
+
+/// Keeps only the [`PathValue`]s under `prefix` like [`Pick`], but ends as soon as one that isn't
+/// under it comes after one that was, for when the subtree is contiguous and the rest of the input
+/// doesn't need to be read. Errors are passed through.
+///
+/// The inner iterator is dropped when ending, which stops a [`crate::Stream`] from parsing.
+pub struct TakeWhilePrefix<I> {
+    inner: Option<I>,
+    prefix: Path,
+    started: bool,
+}
+
+impl<I> TakeWhilePrefix<I> {
+    pub fn new(inner: I, prefix: Path) -> Self {
+        Self {
+            inner: Some(inner),
+            prefix,
+            started: false,
+        }
+    }
+}
+
+impl<I: Iterator<Item = Result<PathValue, StreamError>>> Iterator for TakeWhilePrefix<I> {
+    type Item = Result<PathValue, StreamError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            match self.inner.as_mut()?.next()? {
+                Ok(value) if is_under(&value, &self.prefix) => {
+                    self.started = true;
+                    return Some(Ok(value));
+                }
+                Ok(_) if self.started => {
+                    self.inner = None;
+                    return None;
+                }
+                Ok(_) => continue,
+                Err(e) => return Some(Err(e)),
+            }
+        }
+    }
+}
+
+/// Drops the first `depth` indices of every path, so that e.g. with a depth of 3, the elements of
+/// `.data.results` are output as if each of them was a document. Errors are passed through.
+///
+/// Leaves with shorter paths than `depth` are dropped, as well as close markers with paths of at
+/// most `depth` indices, which close containers above the new roots.
+pub struct StripPrefix<I> {
+    inner: I,
+    depth: usize,
+}
+
+impl<I> StripPrefix<I> {
+    pub fn new(inner: I, depth: usize) -> Self {
+        Self { inner, depth }
+    }
+}
+
+impl<I: Iterator<Item = Result<PathValue, StreamError>>> Iterator for StripPrefix<I> {
+    type Item = Result<PathValue, StreamError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            match self.inner.next()? {
+                Ok(PathValue { path, value, count }) => {
+                    let min = if value.is_some() {
+                        self.depth
+                    } else {
+                        self.depth + 1
+                    };
+                    if path.len() < min {
+                        continue;
+                    }
+                    let path = path[self.depth..].to_vec();
+                    return Some(Ok(PathValue { path, value, count }));
+                }
+                Err(e) => return Some(Err(e)),
+            }
+        }
+    }
+}
+
+/// Whether `value` belongs to the subtree rooted at `prefix`.
+///
+/// A close marker at `[.., i]` closes the container at `[..]`, so it belongs to the subtree only if
+/// that container does.
+pub fn is_under(value: &PathValue, prefix: &Path) -> bool {
+    let path = &value.path;
+    match value.value {
+        Some(_) => starts_with(path, prefix),
+        None => path.len() > prefix.len() && starts_with(path, prefix),
+    }
+}
+
+/// Like `path.starts_with(prefix)`, but an [`Index::OrderedKey`] matches the [`Index::Map`] of the
+/// same key, whatever its position, so that paths written by hand match annotated ones.
+pub fn starts_with(path: &[Index], prefix: &[Index]) -> bool {
+    path.len() >= prefix.len()
+        && path.iter().zip(prefix).all(|(a, b)| match (a, b) {
+            (Index::Array(a), Index::Array(b)) => a == b,
+            (
+                Index::Map(a) | Index::OrderedKey { key: a, .. },
+                Index::Map(b) | Index::OrderedKey { key: b, .. },
+            ) => a == b,
+            _ => false,
+        })
+}
+
+/// A rule of [`Rename`]: renames map keys `from` to `to`, either at any depth or only at `depth`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RenameRule {
+    /// 0-based index in the path, i.e. 0 for the keys of a top-level map.
+    pub depth: Option<usize>,
+    pub from: String,
+    pub to: String,
+}
+
+/// Parses `OLD=NEW` to rename keys at any depth, or `DEPTH:OLD=NEW` to rename them only at
+/// `DEPTH`. `OLD` is everything up to the first `=`, so it can't contain `=` itself.
+impl std::str::FromStr for RenameRule {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (from, to) = s
+            .split_once('=')
+            .ok_or_else(|| "expected `OLD=NEW` or `DEPTH:OLD=NEW`".to_string())?;
+        let (depth, from) = match from.split_once(':') {
+            Some((depth, rest))
+                if !depth.is_empty() && depth.bytes().all(|c| c.is_ascii_digit()) =>
+            {
+                (
+                    Some(depth.parse().map_err(|e| format!("{depth:?}: {e}"))?),
+                    rest,
+                )
+            }
+            _ => (None, from),
+        };
+        Ok(Self {
+            depth,
+            from: from.into(),
+            to: to.into(),
+        })
+    }
+}
+
+/// Renames map keys in the paths according to the given rules. Errors are passed through.
+///
+/// Each key in a path is renamed by the first rule that matches it, in the order the rules are
+/// given. Rules match the original keys only, so a key renamed by one rule isn't renamed again by
+/// another. Renaming a key to one that already exists in the same map results in duplicate keys.
+pub struct Rename<I> {
+    inner: I,
+    rules: Vec<RenameRule>,
+}
+
+impl<I> Rename<I> {
+    pub fn new(inner: I, rules: Vec<RenameRule>) -> Self {
+        Self { inner, rules }
+    }
+}
+
+impl<I: Iterator<Item = Result<PathValue, StreamError>>> Iterator for Rename<I> {
+    type Item = Result<PathValue, StreamError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut value = match self.inner.next()? {
+            Ok(value) => value,
+            e => return Some(e),
+        };
+        for (depth, index) in value.path.iter_mut().enumerate() {
+            let (Index::Map(key) | Index::OrderedKey { key, .. }) = index else {
+                continue;
+            };
+            let rule = self
+                .rules
+                .iter()
+                .find(|rule| rule.depth.is_none_or(|d| d == depth) && &rule.from == key);
+            if let Some(rule) = rule {
+                key.clone_from(&rule.to);
+            }
+        }
+        Some(Ok(value))
+    }
+}
+
+/// A type of scalar leaf, for [`OnlyTypes`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum LeafType {
+    Null,
+    Bool,
+    Number,
+    /// Also matches [`PrimitiveValue::Bytes`], which are written as strings.
+    String,
+}
+
+impl LeafType {
+    pub fn matches(self, value: &PrimitiveValue) -> bool {
+        matches!(
+            (self, value),
+            (LeafType::Null, PrimitiveValue::Null)
+                | (LeafType::Bool, PrimitiveValue::Boolean(_))
+                | (
+                    LeafType::Number,
+                    PrimitiveValue::Number(_) | PrimitiveValue::Integer(_)
+                )
+                | (
+                    LeafType::String,
+                    PrimitiveValue::String(_) | PrimitiveValue::Bytes(_)
+                )
+        )
+    }
+}
+
+/// Keeps only the leaves of one of the given types, dropping close markers and empty containers.
+/// Errors are passed through.
+pub struct OnlyTypes<I> {
+    inner: I,
+    types: Vec<LeafType>,
+}
+
+impl<I> OnlyTypes<I> {
+    pub fn new(inner: I, types: Vec<LeafType>) -> Self {
+        Self { inner, types }
+    }
+}
+
+impl<I: Iterator<Item = Result<PathValue, StreamError>>> Iterator for OnlyTypes<I> {
+    type Item = Result<PathValue, StreamError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            match self.inner.next()? {
+                Ok(PathValue {
+                    value: Some(ref value),
+                    ..
+                }) if !self.types.iter().any(|t| t.matches(value)) => continue,
+                Ok(PathValue { value: None, .. }) => continue,
+                v => return Some(v),
+            }
+        }
+    }
+}
+
+/// Keeps the [`PathValue`]s that `matches`, along with up to `lines` of the ones before and after
+/// each of them, like `grep -C`. Errors are passed through, and aren't counted as lines.
+///
+/// Overlapping contexts are merged, so that each value is yielded once and in order, and a match
+/// in the context after another one extends it by another `lines`. Nothing tells apart groups
+/// that aren't contiguous. Only the `lines` values before the current one are buffered.
+pub struct WithContext<I, F> {
+    inner: I,
+    lines: usize,
+    matches: F,
+    /// The values since the last one yielded, up to `lines` of them.
+    before: VecDeque<PathValue>,
+    /// The values to yield before reading more, i.e. a match and its context before it.
+    ready: VecDeque<PathValue>,
+    /// How many more values are in the context after the last match.
+    after: usize,
+}
+
+impl<I, F> WithContext<I, F> {
+    pub fn new(inner: I, lines: usize, matches: F) -> Self {
+        Self {
+            inner,
+            lines,
+            matches,
+            before: VecDeque::with_capacity(lines),
+            ready: VecDeque::new(),
+            after: 0,
+        }
+    }
+}
+
+impl<I, F> Iterator for WithContext<I, F>
+where
+    I: Iterator<Item = Result<PathValue, StreamError>>,
+    F: FnMut(&PathValue) -> bool,
+{
+    type Item = Result<PathValue, StreamError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(value) = self.ready.pop_front() {
+                return Some(Ok(value));
+            }
+            match self.inner.next()? {
+                Ok(value) if (self.matches)(&value) => {
+                    self.after = self.lines;
+                    std::mem::swap(&mut self.ready, &mut self.before);
+                    self.ready.push_back(value);
+                }
+                Ok(value) if self.after > 0 => {
+                    self.after -= 1;
+                    return Some(Ok(value));
+                }
+                Ok(value) => {
+                    if self.before.len() == self.lines {
+                        self.before.pop_front();
+                    }
+                    if self.lines > 0 {
+                        self.before.push_back(value);
+                    }
+                }
+                Err(e) => return Some(Err(e)),
+            }
+        }
+    }
+}
+
+/// Keeps one out of every `n` leaves, starting with the first one, e.g. the leaves at positions 0,
+/// 10, 20 and so on for 10. Close markers don't count and are dropped, since the containers they
+/// close aren't output whole anyway. Errors are passed through and don't count either.
+pub struct EveryNth<I> {
+    inner: I,
+    n: usize,
+    /// Leaves seen so far, modulo `n`.
+    seen: usize,
+}
+
+impl<I> EveryNth<I> {
+    pub fn new(inner: I, n: usize) -> Self {
+        assert!(n > 0, "one out of 0 leaves");
+        Self { inner, n, seen: 0 }
+    }
+}
+
+impl<I: Iterator<Item = Result<PathValue, StreamError>>> Iterator for EveryNth<I> {
+    type Item = Result<PathValue, StreamError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            match self.inner.next()? {
+                Ok(PathValue { value: None, .. }) => continue,
+                Ok(v) => {
+                    let keep = self.seen == 0;
+                    self.seen = (self.seen + 1) % self.n;
+                    if keep {
+                        return Some(Ok(v));
+                    }
+                }
+                Err(e) => return Some(Err(e)),
+            }
+        }
+    }
+}
+
+/// Keeps a uniform random sample of `k` leaves, or all of them if there are fewer, with reservoir
+/// sampling. The sample is only known once the inner iterator ends, so it's yielded then, in the
+/// order the leaves came in, and the whole input is read. Close markers are dropped, and errors are
+/// passed through as they come.
+///
+/// The same `seed` gives the same sample of the same input. Without one, the sample is different
+/// each time.
+pub struct ReservoirSample<I> {
+    inner: Option<I>,
+    k: usize,
+    rng: fastrand::Rng,
+    /// Leaves seen so far.
+    seen: usize,
+    /// The sampled leaves, with their positions among the leaves.
+    reservoir: Vec<(usize, PathValue)>,
+    sample: std::vec::IntoIter<(usize, PathValue)>,
+}
+
+impl<I> ReservoirSample<I> {
+    pub fn new(inner: I, k: usize, seed: Option<u64>) -> Self {
+        Self {
+            inner: Some(inner),
+            k,
+            rng: seed.map_or_else(fastrand::Rng::new, fastrand::Rng::with_seed),
+            seen: 0,
+            reservoir: vec![],
+            sample: vec![].into_iter(),
+        }
+    }
+}
+
+impl<I: Iterator<Item = Result<PathValue, StreamError>>> Iterator for ReservoirSample<I> {
+    type Item = Result<PathValue, StreamError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some(inner) = &mut self.inner {
+            match inner.next() {
+                Some(Ok(PathValue { value: None, .. })) => continue,
+                Some(Ok(v)) => {
+                    if self.reservoir.len() < self.k {
+                        self.reservoir.push((self.seen, v));
+                    } else {
+                        let i = self.rng.usize(..=self.seen);
+                        if i < self.k {
+                            self.reservoir[i] = (self.seen, v);
+                        }
+                    }
+                    self.seen += 1;
+                }
+                Some(Err(e)) => return Some(Err(e)),
+                None => {
+                    self.inner = None;
+                    let mut reservoir = std::mem::take(&mut self.reservoir);
+                    reservoir.sort_by_key(|(i, _)| *i);
+                    self.sample = reservoir.into_iter();
+                }
+            }
+        }
+        self.sample.next().map(|(_, v)| Ok(v))
+    }
+}
+
+/// Yields each distinct leaf value once, at its first occurrence, without its path. Close markers
+/// are dropped, and errors are passed through.
+///
+/// Every distinct value seen so far is kept in memory. Values are distinct as by
+/// [`PrimitiveValue`]'s `==`, so e.g. `0` and `-0` are distinct, as well as a string and bytes
+/// that are written the same.
+pub struct DistinctValues<I> {
+    inner: I,
+    seen: HashSet<PrimitiveValue>,
+}
+
+impl<I> DistinctValues<I> {
+    pub fn new(inner: I) -> Self {
+        Self {
+            inner,
+            seen: HashSet::new(),
+        }
+    }
+}
+
+impl<I: Iterator<Item = Result<PathValue, StreamError>>> Iterator for DistinctValues<I> {
+    type Item = Result<PrimitiveValue, StreamError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            match self.inner.next()? {
+                Ok(PathValue {
+                    value: Some(value), ..
+                }) if !self.seen.contains(&value) => {
+                    self.seen.insert(value.clone());
+                    return Some(Ok(value));
+                }
+                Ok(_) => continue,
+                Err(e) => return Some(Err(e)),
+            }
+        }
+    }
+}
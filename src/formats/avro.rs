@@ -0,0 +1,90 @@
+use std::io::Read;
+
+use apache_avro::types::Value as AvroValue;
+use serde_json::Value;
+
+use super::Error;
+use crate::{Format, FromReader, MultiDocDeserializer};
+
+/// [Avro](https://avro.apache.org/docs/current/specification/) Object Container Files, whose
+/// header embeds the schema of the records. Each record is a document.
+///
+/// Unions are streamed as the value of their chosen branch. Bytes and fixed values are arrays of
+/// numbers, and enums are strings. The keys of Avro maps are sorted, since the file doesn't keep
+/// their order, while fields of records are in the order of the schema.
+pub struct Avro;
+
+impl FromReader for Avro {
+    const FORMAT: Format = Format::Avro;
+    type De<'de, R> = AvroDocuments<R> where R: 'de + Read;
+
+    fn from_reader<'de, R: 'de + Read>(read: R) -> Self::De<'de, R> {
+        AvroDocuments { read }
+    }
+}
+
+pub struct AvroDocuments<R> {
+    read: R,
+}
+
+impl<'de, R: Read> MultiDocDeserializer<'de> for AvroDocuments<R> {
+    type Error = Error;
+    type Iterator<T: serde::Deserialize<'de>> = AvroIter<R, T>;
+
+    fn into_multidoc_iter<T: serde::Deserialize<'de>>(self) -> Self::Iterator<T> {
+        AvroIter {
+            reader: apache_avro::Reader::new(self.read).map_err(|e| Some(Error::wrapping(e))),
+            _phantom: std::marker::PhantomData,
+        }
+    }
+}
+
+pub struct AvroIter<R, T> {
+    /// The error reading the header, until it's yielded.
+    reader: Result<apache_avro::Reader<'static, R>, Option<Error>>,
+    _phantom: std::marker::PhantomData<T>,
+}
+
+impl<'de, R: Read, T: serde::Deserialize<'de>> Iterator for AvroIter<R, T> {
+    type Item = Result<T, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let reader = match &mut self.reader {
+            Ok(reader) => reader,
+            Err(e) => return e.take().map(Err),
+        };
+        let record = reader.next()?;
+        Some(
+            record
+                .map_err(Error::wrapping)
+                .and_then(into_json)
+                .and_then(|value| T::deserialize(value).map_err(Error::new)),
+        )
+    }
+}
+
+fn into_json(value: AvroValue) -> Result<Value, Error> {
+    Ok(match value {
+        AvroValue::Union(_, value) => into_json(*value)?,
+        AvroValue::Array(v) => {
+            Value::Array(v.into_iter().map(into_json).collect::<Result<_, _>>()?)
+        }
+        AvroValue::Map(v) => {
+            let mut entries: Vec<_> = v.into_iter().collect();
+            entries.sort_by(|(a, _), (b, _)| a.cmp(b));
+            Value::Object(
+                entries
+                    .into_iter()
+                    .map(|(key, value)| Ok((key, into_json(value)?)))
+                    .collect::<Result<_, Error>>()?,
+            )
+        }
+        AvroValue::Record(v) => Value::Object(
+            v.into_iter()
+                .map(|(key, value)| Ok((key, into_json(value)?)))
+                .collect::<Result<_, Error>>()?,
+        ),
+        // Scalars, including the logical types, are converted as apache-avro does.
+        value => Value::try_from(value).map_err(Error::new)?,
+    })
+}
@@ -0,0 +1,118 @@
+use std::io::{self, BufRead, BufReader, Read};
+
+use ciborium::{de::Error as DecodeError, value::Error as ValueError, Value};
+
+use super::{next_document, Error};
+use crate::{Format, FromReader, MultiDocDeserializer};
+
+/// [RFC 8742](https://www.rfc-editor.org/rfc/rfc8742) CBOR sequences, i.e. CBOR items one after
+/// the other without any framing, like [`super::MsgpackStream`]. Each item is a document, up to
+/// the end of the input.
+///
+/// Each item is read into memory before it's streamed. Byte strings are
+/// [`crate::PrimitiveValue::Bytes`], and tags are dropped, leaving the values they tag, so e.g. a
+/// big integer is the bytes of its magnitude. As with MessagePack, the stream ends at the first
+/// error, and an item cut off by the end of the input is reported as truncated.
+pub struct CborSeq;
+
+impl FromReader for CborSeq {
+    const FORMAT: Format = Format::CborSeq;
+    type De<'de, R> = CborDocuments<R> where R: 'de + Read;
+
+    fn from_reader<'de, R: 'de + Read>(read: R) -> Self::De<'de, R> {
+        CborDocuments { read }
+    }
+}
+
+pub struct CborDocuments<R> {
+    read: R,
+}
+
+impl<'de, R: Read + 'de> MultiDocDeserializer<'de> for CborDocuments<R> {
+    type Error = Error;
+    type Iterator<T: serde::Deserialize<'de>> = CborIter<R, T>;
+
+    fn into_multidoc_iter<T: serde::Deserialize<'de>>(self) -> Self::Iterator<T> {
+        CborIter {
+            read: Some(Counted {
+                read: BufReader::new(self.read),
+                offset: 0,
+            }),
+            _phantom: std::marker::PhantomData,
+        }
+    }
+}
+
+/// Counts the bytes that have been read, for the offsets of errors, which ciborium only knows
+/// relative to the start of each item.
+struct Counted<R> {
+    read: BufReader<R>,
+    offset: usize,
+}
+
+impl<R: Read> Read for Counted<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.read.read(buf)?;
+        self.offset += n;
+        Ok(n)
+    }
+}
+
+impl<R: Read> BufRead for Counted<R> {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        self.read.fill_buf()
+    }
+
+    fn consume(&mut self, amt: usize) {
+        self.read.consume(amt);
+        self.offset += amt;
+    }
+}
+
+pub struct CborIter<R, T> {
+    /// `None` once the input has ended or failed.
+    read: Option<Counted<R>>,
+    _phantom: std::marker::PhantomData<T>,
+}
+
+impl<'de, R: Read, T: serde::Deserialize<'de>> Iterator for CborIter<R, T> {
+    type Item = Result<T, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        next_document(&mut self.read, |read| {
+            let start = read.offset;
+            ciborium::from_reader::<Value, _>(read)
+                .map_err(|e| match e {
+                    DecodeError::Io(e) if e.kind() == io::ErrorKind::UnexpectedEof => {
+                        Error::new("Truncated value at the end of the input")
+                    }
+                    DecodeError::Io(e) => e.into(),
+                    DecodeError::Syntax(offset) => {
+                        Error::new(format!("Invalid CBOR at byte {}", start + offset))
+                    }
+                    DecodeError::Semantic(_, message) => Error::new(message),
+                    DecodeError::RecursionLimitExceeded => Error::new("Nested too deeply"),
+                })
+                .and_then(|value| {
+                    untag(value)
+                        .deserialized()
+                        .map_err(|ValueError::Custom(message)| Error::new(message))
+                })
+        })
+    }
+}
+
+/// Drops the tags in `value`, leaving the values they tag.
+fn untag(value: Value) -> Value {
+    match value {
+        Value::Tag(_, value) => untag(*value),
+        Value::Array(values) => Value::Array(values.into_iter().map(untag).collect()),
+        Value::Map(entries) => Value::Map(
+            entries
+                .into_iter()
+                .map(|(k, v)| (untag(k), untag(v)))
+                .collect(),
+        ),
+        value => value,
+    }
+}
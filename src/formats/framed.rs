@@ -0,0 +1,155 @@
+use std::io::{self, BufReader, Read};
+
+use super::{
+    next_document, Avro, CborSeq, Error, Hocon, Jsonc, Kv, MsgpackStream, Properties, RawLines,
+    Ron, Smile, Toml,
+};
+use crate::{
+    DeserializeError, Format, FromReader, Json, Jsonl, MultiDocDeserializer, Options, StreamError,
+    Yaml,
+};
+
+/// Frames of documents in formats that may differ from one frame to the next, e.g. a log of RPCs
+/// with JSON and MessagePack payloads.
+///
+/// Each frame is a type tag of 1 byte, then the length of the payload as a 32-bit big-endian
+/// integer, then the payload. The payload is a document in the format given to its tag by
+/// [`Options::frame_tags`], so e.g. [`Options::with_doc_index`] numbers the frames. Only the first
+/// document of a payload is read, in formats that can have more than one.
+///
+/// Each payload is read into memory to be parsed. A payload that fails to parse, or that has a
+/// tag of no format, is an error of its own, after which the stream goes on with the next frame
+/// if [`Options::skip_errors`] is set. A frame cut off by the end of the input ends the stream.
+pub struct Framed;
+
+impl FromReader for Framed {
+    const FORMAT: Format = Format::Framed;
+    type De<'de, R> = Frames<R> where R: 'de + Read;
+
+    fn from_reader<'de, R: 'de + Read>(read: R) -> Self::De<'de, R> {
+        Self::from_reader_with(read, &Options::default())
+    }
+
+    fn from_reader_with<'de, R: 'de + Read>(read: R, options: &Options) -> Self::De<'de, R> {
+        Frames {
+            read,
+            tags: options.frame_tags.clone(),
+        }
+    }
+}
+
+pub struct Frames<R> {
+    read: R,
+    tags: Vec<(u8, Format)>,
+}
+
+impl<'de, R: Read> MultiDocDeserializer<'de> for Frames<R> {
+    type Error = Error;
+    type Iterator<T: serde::Deserialize<'de>> = FramesIter<R, T>;
+
+    fn into_multidoc_iter<T: serde::Deserialize<'de>>(self) -> Self::Iterator<T> {
+        FramesIter {
+            read: Some(BufReader::new(self.read)),
+            tags: self.tags,
+            frame: 0,
+            _phantom: std::marker::PhantomData,
+        }
+    }
+}
+
+pub struct FramesIter<R, T> {
+    /// `None` once the input has ended or failed.
+    read: Option<BufReader<R>>,
+    tags: Vec<(u8, Format)>,
+    /// The 0-based index of the next frame.
+    frame: usize,
+    _phantom: std::marker::PhantomData<T>,
+}
+
+impl<R: Read, T> FramesIter<R, T> {
+    /// Reads the tag and the payload of the next frame, `None` at the end of the input.
+    fn read_frame(&mut self) -> Option<Result<(u8, Vec<u8>), Error>> {
+        let frame = self.frame;
+        next_document(&mut self.read, |read| {
+            let mut header = [0; 5];
+            read.read_exact(&mut header)
+                .and_then(|()| {
+                    let len = u32::from_be_bytes(header[1..].try_into().unwrap());
+                    let mut payload = vec![];
+                    read.take(len.into()).read_to_end(&mut payload)?;
+                    if payload.len() < len as usize {
+                        return Err(io::ErrorKind::UnexpectedEof.into());
+                    }
+                    Ok((header[0], payload))
+                })
+                .map_err(|e| match e.kind() {
+                    io::ErrorKind::UnexpectedEof => Error::new(format!(
+                        "Frame {frame} is truncated at the end of the input"
+                    )),
+                    _ => e.into(),
+                })
+        })
+    }
+}
+
+impl<'de, R: Read, T: serde::Deserialize<'de>> Iterator for FramesIter<R, T> {
+    type Item = Result<T, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (tag, payload) = match self.read_frame()? {
+            Ok(frame) => frame,
+            Err(e) => return Some(Err(e)),
+        };
+        let frame = self.frame;
+        self.frame += 1;
+        let Some(&(_, format)) = self.tags.iter().find(|(t, _)| *t == tag) else {
+            let tag = [tag].escape_ascii().to_string();
+            return Some(Err(Error::new(format!(
+                "Frame {frame} has the tag `{tag}` of no format"
+            ))));
+        };
+        Some(
+            parse(format, payload)
+                .unwrap_or_else(|| Err(Error::new("no document")))
+                .map_err(|e| Error::new(format!("Frame {frame} ({format}): {e}"))),
+        )
+    }
+}
+
+/// The first document of `payload` as `format`, `None` if it has none.
+fn parse<'de, T: serde::Deserialize<'de>>(
+    format: Format,
+    payload: Vec<u8>,
+) -> Option<Result<T, Error>> {
+    fn parse<'de, F: FromReader, T: serde::Deserialize<'de>>(
+        payload: Vec<u8>,
+    ) -> Option<Result<T, Error>> {
+        let document = F::from_reader(io::Cursor::new(payload))
+            .into_multidoc_iter::<T>()
+            .next()?;
+        // The message has the position in the payload already, if it's known.
+        Some(document.map_err(|e| match e.into_stream_error(F::FORMAT) {
+            StreamError::Deserialize { message, .. } => Error::new(message),
+            e => Error::new(e),
+        }))
+    }
+    match format {
+        Format::Json => parse::<Json, T>(payload),
+        Format::Jsonl => parse::<Jsonl, T>(payload),
+        Format::Jsonc => parse::<Jsonc, T>(payload),
+        Format::Yaml => parse::<Yaml, T>(payload),
+        Format::Hocon => parse::<Hocon, T>(payload),
+        Format::Toml => parse::<Toml, T>(payload),
+        Format::Raw => parse::<RawLines, T>(payload),
+        Format::Properties => parse::<Properties, T>(payload),
+        Format::Avro => parse::<Avro, T>(payload),
+        Format::Ron => parse::<Ron, T>(payload),
+        Format::Smile => parse::<Smile, T>(payload),
+        Format::MsgpackStream => parse::<MsgpackStream, T>(payload),
+        Format::CborSeq => parse::<CborSeq, T>(payload),
+        Format::Kv => parse::<Kv, T>(payload),
+        Format::Framed | Format::Auto => Some(Err(Error::new(format!(
+            "{format} can't be the format of a frame"
+        )))),
+    }
+}
@@ -0,0 +1,52 @@
+use std::io::Read;
+
+use hocon::HoconLoader;
+
+use super::{Error, SingleValue};
+use crate::{Format, FromReader};
+
+/// [HOCON](https://github.com/lightbend/config/blob/main/HOCON.md), which is always a single
+/// document.
+///
+/// Substitutions are resolved. Includes are rejected with an error, since the document is not read
+/// from a file and there is nothing to resolve them against.
+pub struct Hocon;
+
+impl FromReader for Hocon {
+    const FORMAT: Format = Format::Hocon;
+    type De<'de, R> = SingleValue<R> where R: 'de + Read;
+
+    fn from_reader<'de, R: 'de + Read>(read: R) -> Self::De<'de, R> {
+        SingleValue::new(read, parse)
+    }
+}
+
+fn parse(s: &str) -> Result<serde_json::Value, Error> {
+    let hocon = HoconLoader::new()
+        .no_system()
+        .load_str(s)
+        .and_then(HoconLoader::hocon)
+        .map_err(Error::new)?;
+    into_json(hocon)
+}
+
+fn into_json(hocon: hocon::Hocon) -> Result<serde_json::Value, Error> {
+    use hocon::Hocon as H;
+    use serde_json::Value;
+    Ok(match hocon {
+        H::Real(v) => serde_json::Number::from_f64(v)
+            .map(Value::Number)
+            .ok_or_else(|| Error::new(format!("{v} is not a finite number")))?,
+        H::Integer(v) => v.into(),
+        H::String(v) => v.into(),
+        H::Boolean(v) => v.into(),
+        H::Array(v) => Value::Array(v.into_iter().map(into_json).collect::<Result<_, _>>()?),
+        H::Hash(v) => Value::Object(
+            v.into_iter()
+                .map(|(key, value)| Ok((key, into_json(value)?)))
+                .collect::<Result<_, Error>>()?,
+        ),
+        H::Null => Value::Null,
+        H::BadValue(e) => return Err(Error::new(e)),
+    })
+}
@@ -0,0 +1,192 @@
+use std::{
+    collections::VecDeque,
+    io::{self, BufRead, BufReader, Read},
+};
+
+use crate::{Format, FromReader, JsonDocuments};
+
+/// JSON with comments, as in VS Code's settings: `//` and `/* */` comments are allowed anywhere
+/// whitespace is, and so are trailing commas in arrays and objects.
+///
+/// The comments and trailing commas are replaced with spaces before parsing, so that errors are
+/// still reported at the line and column they are at in the input. Otherwise it's the same as
+/// [`crate::Json`], including multiple documents.
+pub struct Jsonc;
+
+impl FromReader for Jsonc {
+    const FORMAT: Format = Format::Jsonc;
+    type De<'de, R> = JsonDocuments<StripComments<R>> where R: 'de + Read;
+
+    fn from_reader<'de, R: 'de + Read>(read: R) -> Self::De<'de, R> {
+        JsonDocuments::new(StripComments {
+            read: BufReader::new(read),
+            state: State::Code,
+            out: VecDeque::new(),
+            pending: vec![],
+            last: b' ',
+        })
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum State {
+    Code,
+    String,
+    /// Right after a `\` in a string.
+    Escape,
+    /// Right after a `/` outside strings, which may start a comment.
+    Slash,
+    LineComment,
+    BlockComment,
+    /// Right after a `*` in a block comment, which may end it.
+    Star,
+}
+
+/// Reads JSONC as JSON, replacing the comments and trailing commas of `R` with spaces. Newlines in
+/// comments are kept.
+pub struct StripComments<R> {
+    read: BufReader<R>,
+    state: State,
+    /// Bytes that are ready to be read.
+    out: VecDeque<u8>,
+    /// A comma outside strings and the whitespace and comments after it so far. It's held back
+    /// until the next byte that isn't either tells whether it's a trailing comma.
+    pending: Vec<u8>,
+    /// The last byte written with [`StripComments::token`], since only a comma after a value can
+    /// be a trailing one.
+    last: u8,
+}
+
+impl<R> StripComments<R> {
+    /// Writes whitespace, or a comment replaced with it.
+    fn space(&mut self, b: u8) {
+        if self.pending.is_empty() {
+            self.out.push_back(b);
+        } else {
+            self.pending.push(b);
+        }
+    }
+
+    /// Writes a byte that is neither whitespace nor a comment, after the pending comma if any.
+    fn token(&mut self, b: u8) {
+        if let [comma, ..] = self.pending.as_mut_slice() {
+            if b == b']' || b == b'}' {
+                *comma = b' ';
+            }
+            self.out.extend(self.pending.drain(..));
+        }
+        if b == b',' && !matches!(self.last, b'[' | b'{' | b',') {
+            self.pending.push(b);
+        } else {
+            self.out.push_back(b);
+        }
+        self.last = b;
+    }
+
+    fn push(&mut self, b: u8) {
+        self.state = match (self.state, b) {
+            (State::Code, b'"') => {
+                self.token(b);
+                State::String
+            }
+            (State::Code, b'/') => State::Slash,
+            (State::Code, b' ' | b'\t' | b'\n' | b'\r') => {
+                self.space(b);
+                State::Code
+            }
+            (State::Code, _) => {
+                self.token(b);
+                State::Code
+            }
+            (State::String, b'\\') => {
+                self.out.push_back(b);
+                State::Escape
+            }
+            (State::String, b'"') => {
+                self.out.push_back(b);
+                State::Code
+            }
+            (State::String | State::Escape, _) => {
+                self.out.push_back(b);
+                State::String
+            }
+            (State::Slash, b'/' | b'*') => {
+                self.space(b' ');
+                self.space(b' ');
+                if b == b'/' {
+                    State::LineComment
+                } else {
+                    State::BlockComment
+                }
+            }
+            (State::Slash, _) => {
+                // Not a comment, leave it to serde_json to reject the `/`.
+                self.token(b'/');
+                self.state = State::Code;
+                return self.push(b);
+            }
+            (State::LineComment | State::BlockComment | State::Star, b'\n') => {
+                self.space(b);
+                if self.state == State::LineComment {
+                    State::Code
+                } else {
+                    State::BlockComment
+                }
+            }
+            (State::LineComment, _) => {
+                self.space(b' ');
+                State::LineComment
+            }
+            (State::BlockComment | State::Star, b'*') => {
+                self.space(b' ');
+                State::Star
+            }
+            (State::Star, b'/') => {
+                self.space(b' ');
+                State::Code
+            }
+            (State::BlockComment | State::Star, _) => {
+                self.space(b' ');
+                State::BlockComment
+            }
+        };
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        match self.state {
+            State::Slash => self.token(b'/'),
+            State::BlockComment | State::Star => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "unterminated block comment",
+                ))
+            }
+            _ => {}
+        }
+        self.state = State::Code;
+        self.out.extend(self.pending.drain(..));
+        Ok(())
+    }
+}
+
+impl<R: Read> Read for StripComments<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.out.is_empty() {
+            let chunk = self.read.fill_buf()?;
+            if chunk.is_empty() {
+                self.finish()?;
+                break;
+            }
+            let chunk = chunk.to_vec();
+            self.read.consume(chunk.len());
+            for b in chunk {
+                self.push(b);
+            }
+        }
+        let n = buf.len().min(self.out.len());
+        for (dst, src) in buf.iter_mut().zip(self.out.drain(..n)) {
+            *dst = src;
+        }
+        Ok(n)
+    }
+}
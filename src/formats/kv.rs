@@ -0,0 +1,127 @@
+use std::io::{BufRead, BufReader, Read};
+
+use serde_json::{Map, Value};
+
+use super::Error;
+use crate::{Format, FromReader, MultiDocDeserializer, Options};
+
+/// Records of `key: value` lines, as in some ad-hoc logs, each of which is a document.
+///
+/// Records are separated by blank lines, or by lines that are [`Options::record_separator`] once
+/// trimmed, in which case blank lines are ignored. Each line of a record is split at its first
+/// `:`, and both sides are trimmed, so `a: b: c` is `{"a":"b: c"}`. All the values are strings, see
+/// [`Options::infer_types`]. If a key appears more than once in a record, the last value wins but
+/// the key keeps the position it first appeared at.
+pub struct Kv;
+
+impl FromReader for Kv {
+    const FORMAT: Format = Format::Kv;
+    type De<'de, R> = Records<R> where R: 'de + Read;
+
+    fn from_reader<'de, R: 'de + Read>(read: R) -> Self::De<'de, R> {
+        Records {
+            read: BufReader::new(read),
+            separator: String::new(),
+        }
+    }
+
+    fn from_reader_with<'de, R: 'de + Read>(read: R, options: &Options) -> Self::De<'de, R> {
+        Records {
+            read: BufReader::new(read),
+            separator: options
+                .record_separator
+                .as_deref()
+                .unwrap_or_default()
+                .trim()
+                .to_string(),
+        }
+    }
+}
+
+pub struct Records<R> {
+    read: BufReader<R>,
+    /// Trimmed, so that the empty string means blank lines.
+    separator: String,
+}
+
+impl<'de, R: Read> MultiDocDeserializer<'de> for Records<R> {
+    type Error = Error;
+    type Iterator<T: serde::Deserialize<'de>> = RecordsIter<R, T>;
+
+    fn into_multidoc_iter<T: serde::Deserialize<'de>>(self) -> Self::Iterator<T> {
+        RecordsIter {
+            separator: self.separator,
+            read: self.read,
+            line: 0,
+            _phantom: std::marker::PhantomData,
+        }
+    }
+}
+
+pub struct RecordsIter<R, T> {
+    read: BufReader<R>,
+    separator: String,
+    line: usize,
+    _phantom: std::marker::PhantomData<T>,
+}
+
+impl<R: Read, T> RecordsIter<R, T> {
+    /// Reads the next record, skipping separators before it. A bad line doesn't stop the record
+    /// from being read to its end, so that the next record can still be read after the error.
+    fn record(&mut self) -> Option<Result<Map<String, Value>, Error>> {
+        let mut record = None;
+        let mut error = None;
+        let mut buf = String::new();
+        loop {
+            buf.clear();
+            self.line += 1;
+            match self.read.read_line(&mut buf) {
+                Ok(0) => break,
+                Ok(_) => {}
+                Err(e) => return Some(Err(e.into())),
+            }
+            let line = buf.trim();
+            if line == self.separator {
+                if record.is_some() || error.is_some() {
+                    break;
+                }
+                continue;
+            }
+            if line.is_empty() {
+                continue;
+            }
+            let record = record.get_or_insert_with(Map::new);
+            match line.split_once(':') {
+                Some((key, value)) => {
+                    record.insert(
+                        key.trim().to_string(),
+                        Value::String(value.trim().to_string()),
+                    );
+                }
+                None => {
+                    error.get_or_insert(Error {
+                        line: self.line,
+                        column: 1,
+                        message: "Expected `key: value`".to_string(),
+                        io: None,
+                    });
+                }
+            }
+        }
+        match error {
+            Some(e) => Some(Err(e)),
+            None => record.map(Ok),
+        }
+    }
+}
+
+impl<'de, R: Read, T: serde::Deserialize<'de>> Iterator for RecordsIter<R, T> {
+    type Item = Result<T, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        Some(
+            self.record()?
+                .and_then(|record| T::deserialize(Value::Object(record)).map_err(Error::new)),
+        )
+    }
+}
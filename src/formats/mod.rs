@@ -0,0 +1,173 @@
+//! Input formats other than JSON and YAML.
+//!
+//! Many of these formats can't be parsed incrementally, so they are read into a
+//! [`serde_json::Value`] first and then streamed from it.
+
+use std::io::{self, BufRead, Read};
+
+use crate::{DeserializeError, Format, MultiDocDeserializer, StreamError};
+
+mod avro;
+mod cbor;
+mod framed;
+mod hocon;
+mod jsonc;
+mod kv;
+mod msgpack;
+mod properties;
+mod raw;
+mod ron;
+mod smile;
+mod toml;
+
+pub use self::{
+    avro::Avro,
+    cbor::CborSeq,
+    framed::Framed,
+    hocon::Hocon,
+    jsonc::{Jsonc, StripComments},
+    kv::Kv,
+    msgpack::MsgpackStream,
+    properties::Properties,
+    raw::RawLines,
+    ron::Ron,
+    smile::Smile,
+    toml::Toml,
+};
+
+/// Error of the formats in this module.
+#[derive(Debug)]
+pub struct Error {
+    /// 1-based position of the error, or 0 if unknown.
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+    /// The error of the reader, if this is one, which is a [`StreamError::Io`] rather than a
+    /// problem with the input.
+    pub io: Option<io::Error>,
+}
+
+impl Error {
+    pub fn new(message: impl std::fmt::Display) -> Self {
+        Self {
+            line: 0,
+            column: 0,
+            message: message.to_string(),
+            io: None,
+        }
+    }
+
+    /// An error of the parser of another crate, that is the error of the reader if that's what
+    /// caused it, unless the input only ended in the middle of a document.
+    fn wrapping(e: impl std::error::Error + 'static) -> Self {
+        let io = std::iter::successors(e.source(), |e| e.source())
+            .find_map(|e| e.downcast_ref::<io::Error>())
+            .filter(|e| e.kind() != io::ErrorKind::UnexpectedEof);
+        match io {
+            Some(io) => io::Error::new(io.kind(), io.to_string()).into(),
+            None => Self::new(e),
+        }
+    }
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.line == 0 {
+            write!(f, "{}", self.message)
+        } else {
+            write!(
+                f,
+                "{} at line {} column {}",
+                self.message, self.line, self.column
+            )
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        self.io.as_ref().map(|e| e as _)
+    }
+}
+
+impl serde::de::Error for Error {
+    fn custom<T: std::fmt::Display>(msg: T) -> Self {
+        Self::new(msg)
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Self {
+        Self {
+            message: e.to_string(),
+            io: Some(e),
+            ..Self::new("")
+        }
+    }
+}
+
+impl DeserializeError for Error {
+    fn into_stream_error(self, format: Format) -> StreamError {
+        if let Some(e) = self.io {
+            return StreamError::Io(e);
+        }
+        StreamError::Deserialize {
+            format,
+            line: self.line,
+            column: self.column,
+            message: self.to_string(),
+        }
+    }
+}
+
+/// The next of the documents read one after the other from `input` by `parse`, `None` at the end
+/// of the input, for formats whose documents can only be told apart by parsing them.
+///
+/// `input` is set to `None` at the end, or after an error since it's unknown where the failed
+/// document ends, so the iterator calling this ends too.
+fn next_document<R: BufRead, T>(
+    input: &mut Option<R>,
+    parse: impl FnOnce(&mut R) -> Result<T, Error>,
+) -> Option<Result<T, Error>> {
+    let read = input.as_mut()?;
+    let result = match read.fill_buf() {
+        Ok([]) => None,
+        Ok(_) => Some(parse(read)),
+        Err(e) => Some(Err(e.into())),
+    };
+    if !matches!(result, Some(Ok(_))) {
+        *input = None;
+    }
+    result
+}
+
+/// A [`MultiDocDeserializer`] for formats whose input is a single document, that is parsed into
+/// memory all at once by `parse`.
+pub struct SingleValue<R> {
+    read: R,
+    parse: fn(&str) -> Result<serde_json::Value, Error>,
+}
+
+impl<R: Read> SingleValue<R> {
+    pub fn new(read: R, parse: fn(&str) -> Result<serde_json::Value, Error>) -> Self {
+        Self { read, parse }
+    }
+
+    fn parse(mut self) -> Result<serde_json::Value, Error> {
+        let mut buf = String::new();
+        self.read.read_to_string(&mut buf)?;
+        (self.parse)(&buf)
+    }
+}
+
+impl<'de, R: Read> MultiDocDeserializer<'de> for SingleValue<R> {
+    type Error = Error;
+    type Iterator<T: serde::Deserialize<'de>> = std::iter::Once<Result<T, Error>>;
+
+    fn into_multidoc_iter<T: serde::Deserialize<'de>>(self) -> Self::Iterator<T> {
+        std::iter::once(
+            self.parse()
+                .and_then(|value| T::deserialize(value).map_err(Error::new)),
+        )
+    }
+}
@@ -0,0 +1,63 @@
+use std::io::{self, BufReader, Read};
+
+use rmp_serde::decode::Error as DecodeError;
+
+use super::{next_document, Error};
+use crate::{Format, FromReader, MultiDocDeserializer};
+
+/// [MessagePack](https://msgpack.org/) values one after the other without any framing, like
+/// NDJSON but binary. Each value is a document, up to the end of the input.
+///
+/// Binary values are [`crate::PrimitiveValue::Bytes`]. The end of a value can only be found by
+/// parsing it, so the stream ends at the first error, and a value cut off by the end of the input
+/// is reported as truncated.
+pub struct MsgpackStream;
+
+impl FromReader for MsgpackStream {
+    const FORMAT: Format = Format::MsgpackStream;
+    type De<'de, R> = MsgpackDocuments<R> where R: 'de + Read;
+
+    fn from_reader<'de, R: 'de + Read>(read: R) -> Self::De<'de, R> {
+        MsgpackDocuments { read }
+    }
+}
+
+pub struct MsgpackDocuments<R> {
+    read: R,
+}
+
+impl<'de, R: Read + 'de> MultiDocDeserializer<'de> for MsgpackDocuments<R> {
+    type Error = Error;
+    type Iterator<T: serde::Deserialize<'de>> = MsgpackIter<R, T>;
+
+    fn into_multidoc_iter<T: serde::Deserialize<'de>>(self) -> Self::Iterator<T> {
+        MsgpackIter {
+            read: Some(BufReader::new(self.read)),
+            _phantom: std::marker::PhantomData,
+        }
+    }
+}
+
+pub struct MsgpackIter<R, T> {
+    /// `None` once the input has ended or failed.
+    read: Option<BufReader<R>>,
+    _phantom: std::marker::PhantomData<T>,
+}
+
+impl<'de, R: Read, T: serde::Deserialize<'de>> Iterator for MsgpackIter<R, T> {
+    type Item = Result<T, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        next_document(&mut self.read, |read| {
+            T::deserialize(&mut rmp_serde::Deserializer::new(read)).map_err(|e| match e {
+                DecodeError::InvalidMarkerRead(e) | DecodeError::InvalidDataRead(e)
+                    if e.kind() == io::ErrorKind::UnexpectedEof =>
+                {
+                    Error::new("Truncated value at the end of the input")
+                }
+                DecodeError::InvalidMarkerRead(e) | DecodeError::InvalidDataRead(e) => e.into(),
+                e => Error::new(e),
+            })
+        })
+    }
+}
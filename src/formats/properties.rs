@@ -0,0 +1,171 @@
+use std::io::Read;
+
+use serde_json::{Map, Value};
+
+use super::{Error, SingleValue};
+use crate::{Format, FromReader};
+
+/// Java [`.properties`](https://docs.oracle.com/javase/8/docs/api/java/util/Properties.html#load-java.io.Reader-)
+/// files, which are always a single document.
+///
+/// Keys are split at `.` into nested maps, e.g. `a.b.c=1` is `{"a":{"b":{"c":"1"}}}`, and all
+/// the values are strings. A key that is both a value and a prefix of another key, like `a=1` with
+/// `a.b=2`, is an error. If a key appears more than once, the last value wins but the key keeps
+/// the position it first appeared at.
+pub struct Properties;
+
+impl FromReader for Properties {
+    const FORMAT: Format = Format::Properties;
+    type De<'de, R> = SingleValue<R> where R: 'de + Read;
+
+    fn from_reader<'de, R: 'de + Read>(read: R) -> Self::De<'de, R> {
+        SingleValue::new(read, parse)
+    }
+}
+
+fn parse(s: &str) -> Result<Value, Error> {
+    let mut root = Map::new();
+    for (line, key, value) in logical_lines(s) {
+        let at_line = |message: String| Error {
+            line,
+            column: 1,
+            message,
+            io: None,
+        };
+        let key = unescape(&key).map_err(at_line)?;
+        let value = unescape(&value).map_err(at_line)?;
+        let mut keys: Vec<&str> = key.split('.').collect();
+        let last = keys.pop().unwrap();
+        let mut map = &mut root;
+        for (i, k) in keys.iter().enumerate() {
+            let child = map.entry(*k).or_insert_with(|| Value::Object(Map::new()));
+            map = child.as_object_mut().ok_or_else(|| {
+                at_line(format!(
+                    "Key {key:?} conflicts with the value of {:?}",
+                    keys[..=i].join(".")
+                ))
+            })?;
+        }
+        match map.get_mut(last) {
+            Some(Value::Object(_)) => {
+                return Err(at_line(format!(
+                    "Key {key:?} conflicts with the keys under it"
+                )))
+            }
+            Some(v) => *v = Value::String(value),
+            None => {
+                map.insert(last.to_string(), Value::String(value));
+            }
+        }
+    }
+    Ok(Value::Object(root))
+}
+
+/// Splits `s` into the 1-based line number where each entry starts, and its still escaped key and
+/// value, skipping comments and blank lines and joining continuation lines.
+fn logical_lines(s: &str) -> Vec<(usize, String, String)> {
+    let mut entries = vec![];
+    let mut lines = s.lines().enumerate();
+    while let Some((i, line)) = lines.next() {
+        let line = line.trim_start_matches(is_whitespace);
+        if line.is_empty() || line.starts_with(['#', '!']) {
+            continue;
+        }
+        let mut logical = String::new();
+        let mut line = line;
+        loop {
+            match line.strip_suffix('\\') {
+                // An odd number of trailing backslashes means the line continues.
+                Some(rest) if !ends_with_escape(rest) => {
+                    logical.push_str(rest);
+                    match lines.next() {
+                        Some((_, next)) => line = next.trim_start_matches(is_whitespace),
+                        None => break,
+                    }
+                }
+                _ => {
+                    logical.push_str(line);
+                    break;
+                }
+            }
+        }
+        let (key, value) = split_entry(&logical);
+        entries.push((i + 1, key.to_string(), value.to_string()));
+    }
+    entries
+}
+
+/// Whether `s` ends with an unpaired backslash.
+fn ends_with_escape(s: &str) -> bool {
+    s.bytes().rev().take_while(|&c| c == b'\\').count() % 2 == 1
+}
+
+/// Splits at the first unescaped `=`, `:` or whitespace, also skipping whitespace around the
+/// separator.
+fn split_entry(line: &str) -> (&str, &str) {
+    let mut escaped = false;
+    for (i, c) in line.char_indices() {
+        if escaped {
+            escaped = false;
+        } else if c == '\\' {
+            escaped = true;
+        } else if c == '=' || c == ':' || is_whitespace(c) {
+            let key = &line[..i];
+            let mut rest = line[i..].trim_start_matches(is_whitespace);
+            if is_whitespace(c) {
+                if let Some(r) = rest.strip_prefix(['=', ':']) {
+                    rest = r;
+                }
+            } else {
+                rest = &rest[1..];
+            }
+            return (key, rest.trim_start_matches(is_whitespace));
+        }
+    }
+    (line, "")
+}
+
+fn is_whitespace(c: char) -> bool {
+    matches!(c, ' ' | '\t' | '\x0c')
+}
+
+fn unescape(s: &str) -> Result<String, String> {
+    let mut ret = String::with_capacity(s.len());
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            ret.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('t') => ret.push('\t'),
+            Some('n') => ret.push('\n'),
+            Some('r') => ret.push('\r'),
+            Some('f') => ret.push('\x0c'),
+            Some('u') => {
+                let hex: String = chars.by_ref().take(4).collect();
+                let c = u32::from_str_radix(&hex, 16)
+                    .ok()
+                    .filter(|_| hex.len() == 4)
+                    .ok_or_else(|| format!("Malformed \\uxxxx escape: \\u{hex}"))?;
+                // Surrogate pairs are escaped as two `\u`s, which have to be combined.
+                let c = if (0xd800..0xdc00).contains(&c) {
+                    let low: String = chars.by_ref().take(6).collect();
+                    low.strip_prefix("\\u")
+                        .and_then(|hex| u32::from_str_radix(hex, 16).ok())
+                        .filter(|low| (0xdc00..0xe000).contains(low))
+                        .map(|low| 0x10000 + ((c - 0xd800) << 10) + (low - 0xdc00))
+                        .ok_or_else(|| format!("Unpaired surrogate \\u{hex}"))?
+                } else {
+                    c
+                };
+                let c = char::from_u32(c).ok_or_else(|| format!("Unpaired surrogate \\u{hex}"))?;
+                ret.push(c);
+            }
+            // Any other escaped character, including a trailing backslash, stands for itself.
+            Some(c) => ret.push(c),
+            None => {}
+        }
+    }
+    Ok(ret)
+}
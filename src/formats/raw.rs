@@ -0,0 +1,64 @@
+use std::io::{BufRead, BufReader, Read};
+
+use serde::de::value::StringDeserializer;
+
+use super::Error;
+use crate::{Format, FromReader, MultiDocDeserializer};
+
+/// Plain text, where each line is a document consisting of a single string, like `jq -R`.
+///
+/// The trailing `\n` of each line is not part of the string.
+pub struct RawLines;
+
+impl FromReader for RawLines {
+    const FORMAT: Format = Format::Raw;
+    type De<'de, R> = Lines<R> where R: 'de + Read;
+
+    fn from_reader<'de, R: 'de + Read>(read: R) -> Self::De<'de, R> {
+        Lines {
+            read: BufReader::new(read),
+        }
+    }
+}
+
+pub struct Lines<R> {
+    read: BufReader<R>,
+}
+
+impl<'de, R: Read> MultiDocDeserializer<'de> for Lines<R> {
+    type Error = Error;
+    type Iterator<T: serde::Deserialize<'de>> = LinesIter<R, T>;
+
+    fn into_multidoc_iter<T: serde::Deserialize<'de>>(self) -> Self::Iterator<T> {
+        LinesIter {
+            read: self.read,
+            line: 0,
+            _phantom: std::marker::PhantomData,
+        }
+    }
+}
+
+pub struct LinesIter<R, T> {
+    read: BufReader<R>,
+    line: usize,
+    _phantom: std::marker::PhantomData<T>,
+}
+
+impl<'de, R: Read, T: serde::Deserialize<'de>> Iterator for LinesIter<R, T> {
+    type Item = Result<T, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut buf = String::new();
+        self.line += 1;
+        match self.read.read_line(&mut buf) {
+            Ok(0) => None,
+            Ok(_) => {
+                if buf.ends_with('\n') {
+                    buf.pop();
+                }
+                Some(T::deserialize(StringDeserializer::<Error>::new(buf)))
+            }
+            Err(e) => Some(Err(e.into())),
+        }
+    }
+}
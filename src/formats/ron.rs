@@ -0,0 +1,30 @@
+use std::io::Read;
+
+use super::{Error, SingleValue};
+use crate::{Format, FromReader};
+
+/// [RON](https://github.com/ron-rs/ron), which is always a single document.
+///
+/// Tuples like `(1, "a")`, and the fields of tuple structs and enum variants like `Point(1, 2)`,
+/// are arrays. Named structs like `Point(x: 1, y: 2)` are maps of their fields, and unit values
+/// `()` are `null`. The names of structs and variants are dropped. Keys of maps have to be
+/// strings.
+pub struct Ron;
+
+impl FromReader for Ron {
+    const FORMAT: Format = Format::Ron;
+    type De<'de, R> = SingleValue<R> where R: 'de + Read;
+
+    fn from_reader<'de, R: 'de + Read>(read: R) -> Self::De<'de, R> {
+        SingleValue::new(read, parse)
+    }
+}
+
+fn parse(s: &str) -> Result<serde_json::Value, Error> {
+    ron::from_str(s).map_err(|e| Error {
+        line: e.span.start.line,
+        column: e.span.start.col,
+        message: e.code.to_string(),
+        io: None,
+    })
+}
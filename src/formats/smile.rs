@@ -0,0 +1,35 @@
+use std::io::{BufReader, Read};
+
+use serde_smile::de::{IoRead, StreamDeserializer};
+
+use crate::{Format, FromReader, MultiDocDeserializer};
+
+/// [Smile](https://github.com/FasterXML/smile-format-specification), Jackson's binary JSON. Each
+/// top-level value is a document, up to the end of the input or the end-of-stream marker.
+///
+/// Binary values are [`crate::PrimitiveValue::Bytes`]. Big integers are numbers if they fit in 64
+/// bits. Larger ones and big decimals are maps with the private keys serde-smile represents them
+/// with, since they can't be numbers without losing precision.
+pub struct Smile;
+
+impl FromReader for Smile {
+    const FORMAT: Format = Format::Smile;
+    type De<'de, R> = SmileDocuments<R> where R: 'de + Read;
+
+    fn from_reader<'de, R: 'de + Read>(read: R) -> Self::De<'de, R> {
+        SmileDocuments { read }
+    }
+}
+
+pub struct SmileDocuments<R> {
+    read: R,
+}
+
+impl<'de, R: Read + 'de> MultiDocDeserializer<'de> for SmileDocuments<R> {
+    type Error = serde_smile::Error;
+    type Iterator<T: serde::Deserialize<'de>> = StreamDeserializer<'de, IoRead<BufReader<R>>, T>;
+
+    fn into_multidoc_iter<T: serde::Deserialize<'de>>(self) -> Self::Iterator<T> {
+        serde_smile::Deserializer::from_reader(BufReader::new(self.read)).into_iter()
+    }
+}
@@ -0,0 +1,60 @@
+use std::io::Read;
+
+use serde_json::Value;
+
+use super::{Error, SingleValue};
+use crate::{Format, FromReader};
+
+/// [TOML](https://toml.io), which is always a single document, a table.
+///
+/// Keys are in the order they're written in. Dates and times are strings as they're written, e.g.
+/// `1979-05-27T07:32:00Z`, and `inf` and `nan` are errors, since they can't be numbers.
+pub struct Toml;
+
+impl FromReader for Toml {
+    const FORMAT: Format = Format::Toml;
+    type De<'de, R> = SingleValue<R> where R: 'de + Read;
+
+    fn from_reader<'de, R: 'de + Read>(read: R) -> Self::De<'de, R> {
+        SingleValue::new(read, parse)
+    }
+}
+
+fn parse(s: &str) -> Result<Value, Error> {
+    let table = s.parse::<toml::Table>().map_err(|e| {
+        let (line, column) = e.span().map_or((0, 0), |span| {
+            let before = &s[..span.start];
+            let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+            (
+                before.matches('\n').count() + 1,
+                before[line_start..].chars().count() + 1,
+            )
+        });
+        Error {
+            line,
+            column,
+            message: e.message().to_string(),
+            io: None,
+        }
+    })?;
+    into_json(toml::Value::Table(table))
+}
+
+fn into_json(value: toml::Value) -> Result<Value, Error> {
+    use toml::Value as T;
+    Ok(match value {
+        T::String(v) => v.into(),
+        T::Integer(v) => v.into(),
+        T::Float(v) => serde_json::Number::from_f64(v)
+            .map(Value::Number)
+            .ok_or_else(|| Error::new(format!("{v} is not a finite number")))?,
+        T::Boolean(v) => v.into(),
+        T::Datetime(v) => v.to_string().into(),
+        T::Array(v) => Value::Array(v.into_iter().map(into_json).collect::<Result<_, _>>()?),
+        T::Table(v) => Value::Object(
+            v.into_iter()
+                .map(|(key, value)| Ok((key, into_json(value)?)))
+                .collect::<Result<_, Error>>()?,
+        ),
+    })
+}
@@ -0,0 +1,271 @@
+//! JSON multi-document readers that can resume after a bad document.
+
+use std::{
+    cell::RefCell,
+    io::{self, BufRead, BufReader, Read},
+    marker::PhantomData,
+    rc::Rc,
+};
+
+use serde_json::de::{IoRead, StreamDeserializer};
+
+use crate::{DeserializeError, Format, MultiDocDeserializer, StreamError};
+
+/// Concatenated JSON documents read from `R`, e.g. NDJSON.
+///
+/// Unlike [`serde_json::StreamDeserializer`], the iterator doesn't end after an error. The next
+/// call resynchronizes to the start of the line after the one where the error was reported, and
+/// resumes parsing from there. This recovers from a bad line of NDJSON, but a document spanning
+/// multiple lines that fails in the middle may yield a few spurious values or errors from its
+/// remaining lines. serde_json may also have read past the end of the bad line, looking for the
+/// end of an enclosing container: in that case parsing resumes at the first non-whitespace byte it
+/// read, so that the next line isn't lost.
+pub struct JsonDocuments<R> {
+    read: R,
+}
+
+impl<R> JsonDocuments<R> {
+    pub fn new(read: R) -> Self {
+        Self { read }
+    }
+}
+
+impl<'de, R: Read + 'de> MultiDocDeserializer<'de> for JsonDocuments<R> {
+    type Error = JsonError;
+    type Iterator<T: serde::Deserialize<'de>> = JsonMultiDocIter<'de, R, T>;
+
+    fn into_multidoc_iter<T: serde::Deserialize<'de>>(self) -> Self::Iterator<T> {
+        let read = SharedRead(Rc::new(RefCell::new(Shared {
+            read: BufReader::new(self.read),
+            lines: 0,
+            last: None,
+            replay: None,
+        })));
+        JsonMultiDocIter {
+            inner: serde_json::Deserializer::from_reader(read.clone()).into_iter(),
+            read,
+            line_offset: 0,
+            failed_at_line: None,
+        }
+    }
+}
+
+pub struct JsonMultiDocIter<'de, R: Read, T> {
+    read: SharedRead<R>,
+    inner: StreamDeserializer<'de, IoRead<SharedRead<R>>, T>,
+    /// Number of lines read before `inner` started.
+    line_offset: usize,
+    /// The line where the last error was reported if `inner` has failed, or 0 if unknown.
+    failed_at_line: Option<usize>,
+}
+
+impl<'de, R: Read, T: serde::Deserialize<'de>> Iterator for JsonMultiDocIter<'de, R, T> {
+    type Item = Result<T, JsonError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(line) = self.failed_at_line.take() {
+            if let Err(e) = self.read.resync(line) {
+                return Some(Err(JsonError {
+                    inner: serde_json::Error::io(e),
+                    line_offset: 0,
+                }));
+            }
+            self.line_offset = self.read.0.borrow().lines;
+            self.inner = serde_json::Deserializer::from_reader(self.read.clone()).into_iter();
+        }
+        match self.inner.next()? {
+            Ok(v) => Some(Ok(v)),
+            Err(inner) => {
+                self.failed_at_line = Some(match (inner.line(), inner.column()) {
+                    (0, _) => 0,
+                    // Reported right after the newline that ends the bad line, e.g. once a number
+                    // at its end has been parsed, so nothing of this line has been read yet.
+                    (line, 0) if line > 1 => line - 1 + self.line_offset,
+                    (line, _) => line + self.line_offset,
+                });
+                Some(Err(JsonError {
+                    inner,
+                    line_offset: self.line_offset,
+                }))
+            }
+        }
+    }
+}
+
+/// [JSON Lines](https://jsonlines.org/) read from `R`, i.e. exactly one JSON document per line.
+///
+/// Each line is parsed on its own, so unlike [`JsonDocuments`], a document spanning multiple lines,
+/// several documents on one line, or a blank line is an error reported at that line, and parsing
+/// always resumes at the next line. A `\r` before the `\n` is ignored, and so is a final `\n`.
+pub struct JsonLines<R> {
+    read: R,
+}
+
+impl<R> JsonLines<R> {
+    pub fn new(read: R) -> Self {
+        Self { read }
+    }
+}
+
+impl<'de, R: Read + 'de> MultiDocDeserializer<'de> for JsonLines<R> {
+    type Error = JsonError;
+    type Iterator<T: serde::Deserialize<'de>> = JsonLinesIter<R, T>;
+
+    fn into_multidoc_iter<T: serde::Deserialize<'de>>(self) -> Self::Iterator<T> {
+        JsonLinesIter {
+            read: BufReader::new(self.read),
+            lines: 0,
+            _phantom: PhantomData,
+        }
+    }
+}
+
+pub struct JsonLinesIter<R, T> {
+    read: BufReader<R>,
+    /// Number of lines read so far.
+    lines: usize,
+    _phantom: PhantomData<T>,
+}
+
+impl<'de, R: Read, T: serde::Deserialize<'de>> Iterator for JsonLinesIter<R, T> {
+    type Item = Result<T, JsonError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut line = vec![];
+        match self.read.read_until(b'\n', &mut line) {
+            Ok(0) => return None,
+            Ok(_) => {}
+            Err(e) => {
+                return Some(Err(JsonError {
+                    inner: serde_json::Error::io(e),
+                    line_offset: 0,
+                }))
+            }
+        }
+        self.lines += 1;
+        if line.ends_with(b"\n") {
+            line.pop();
+            if line.ends_with(b"\r") {
+                line.pop();
+            }
+        }
+        let mut de = serde_json::Deserializer::from_reader(io::Cursor::new(line));
+        let result = T::deserialize(&mut de).and_then(|value| de.end().map(|()| value));
+        Some(result.map_err(|inner| JsonError {
+            inner,
+            line_offset: self.lines - 1,
+        }))
+    }
+}
+
+/// A [`serde_json::Error`] with its line number adjusted for the lines skipped by resyncs.
+#[derive(Debug)]
+pub struct JsonError {
+    inner: serde_json::Error,
+    line_offset: usize,
+}
+
+impl std::fmt::Display for JsonError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.line_offset == 0 || self.inner.line() == 0 {
+            return self.inner.fmt(f);
+        }
+        // serde_json's message ends with its own position, which we have to rewrite.
+        let message = self.inner.to_string();
+        let suffix = format!(
+            " at line {} column {}",
+            self.inner.line(),
+            self.inner.column()
+        );
+        let message = message.strip_suffix(&suffix).unwrap_or(&message);
+        write!(
+            f,
+            "{message} at line {} column {}",
+            self.inner.line() + self.line_offset,
+            self.inner.column()
+        )
+    }
+}
+
+impl std::error::Error for JsonError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.inner)
+    }
+}
+
+impl DeserializeError for JsonError {
+    fn into_stream_error(self, format: Format) -> StreamError {
+        let message = self.to_string();
+        match self.inner.into_stream_error(format) {
+            StreamError::Deserialize {
+                format,
+                line,
+                column,
+                ..
+            } => StreamError::Deserialize {
+                format,
+                line: if line == 0 {
+                    0
+                } else {
+                    line + self.line_offset
+                },
+                column,
+                message,
+            },
+            error => error,
+        }
+    }
+}
+
+/// A handle to the input shared by all the deserializers created by the resyncs.
+struct SharedRead<R>(Rc<RefCell<Shared<R>>>);
+
+struct Shared<R> {
+    read: BufReader<R>,
+    /// Number of line breaks read so far.
+    lines: usize,
+    /// The last byte read.
+    last: Option<u8>,
+    /// A byte to be read again before the rest of `read`.
+    replay: Option<u8>,
+}
+
+impl<R> Clone for SharedRead<R> {
+    fn clone(&self) -> Self {
+        Self(self.0.clone())
+    }
+}
+
+impl<R: Read> SharedRead<R> {
+    /// Moves to the start of the line after the 1-based `line`, or to the next line if `line` is 0.
+    fn resync(&self, line: usize) -> io::Result<()> {
+        let mut shared = self.0.borrow_mut();
+        if line == 0 || shared.lines < line {
+            shared.read.skip_until(b'\n')?;
+            // If it reached the end instead, nothing more is read so the count doesn't matter.
+            shared.lines += 1;
+        } else if let Some(c) = shared.last.filter(|c| !c.is_ascii_whitespace()) {
+            // The failed deserializer peeked this byte past the line, and it's not been parsed.
+            shared.replay = Some(c);
+        }
+        Ok(())
+    }
+}
+
+impl<R: Read> Read for SharedRead<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let mut shared = self.0.borrow_mut();
+        if let Some(c) = shared.replay.take() {
+            if let Some(first) = buf.first_mut() {
+                *first = c;
+                return Ok(1);
+            }
+        }
+        let n = shared.read.read(buf)?;
+        shared.lines += buf[..n].iter().filter(|&&c| c == b'\n').count();
+        if n > 0 {
+            shared.last = Some(buf[n - 1]);
+        }
+        Ok(n)
+    }
+}
@@ -0,0 +1,2041 @@
+use std::{
+    cell::{Cell, RefCell},
+    collections::VecDeque,
+    marker::PhantomData,
+    ops::Range,
+    rc::Rc,
+    sync::{
+        mpsc::{sync_channel, Receiver, SyncSender},
+        Arc, Mutex,
+    },
+    thread::JoinHandle,
+};
+
+use unicode_normalization::UnicodeNormalization;
+
+pub mod compression;
+mod convert;
+pub mod decode;
+mod diff;
+mod error;
+pub mod filter;
+pub mod formats;
+mod json;
+mod number;
+pub mod path;
+pub mod peek;
+pub mod progress;
+pub mod render;
+pub mod schema;
+pub mod shape;
+mod spans;
+pub mod summary;
+pub mod table;
+pub mod timeout;
+pub mod values;
+
+pub use convert::{convert, ConvertError};
+pub use diff::{diff, Diff, Diffs};
+pub use error::{DeserializeError, StreamError};
+pub use formats::{
+    Avro, CborSeq, Framed, Hocon, Jsonc, Kv, MsgpackStream, Properties, RawLines, Ron, Smile, Toml,
+};
+pub use json::{JsonDocuments, JsonError, JsonLines, JsonLinesIter, JsonMultiDocIter};
+pub use spans::SpannedStream;
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Index {
+    Array(usize),
+    /// A key of a map. Keys that aren't strings, which formats like YAML and MessagePack allow,
+    /// are the JSON text of the key, e.g. `1`, `true` or `[1,"a"]` for the tuple `(1, "a")`, and
+    /// binary keys are base64 like [`PrimitiveValue::Bytes`]. That doesn't round-trip: a string
+    /// key `"1"` and an integer key `1` get the same path, and reassembling the documents gives
+    /// string keys. Maps inside a composite key still need string keys.
+    Map(String),
+    /// A key of a map with its 0-based position among the keys of the map, with
+    /// [`Options::index_object_keys`]. It's written as `{"key":"a","index":0}`, and matches the
+    /// [`Index::Map`] of the same key in paths to pick or drop.
+    OrderedKey {
+        key: String,
+        index: usize,
+    },
+}
+pub type Path = Vec<Index>;
+
+/// Numbers compare and hash by their bit patterns, so that `PartialEq` can be a proper equivalence
+/// relation: `NaN` equals a `NaN` with the same bits, and `0.0` doesn't equal `-0.0`.
+#[derive(Clone, Debug)]
+pub enum PrimitiveValue {
+    Null,
+    Boolean(bool),
+    Number(f64),
+    /// An integer of more than 2^53 in magnitude, which `f64` can't represent exactly, so that it
+    /// can still be written out exactly. Smaller integers are [`PrimitiveValue::Number`]s.
+    Integer(i128),
+    String(String),
+    /// Binary data of the formats that have it, like Smile. It's written out as a base64 string,
+    /// and counts as a string wherever value types matter.
+    Bytes(Vec<u8>),
+    EmptyArray,
+    EmptyObject,
+    /// A whole array of scalars at the path of the array, with [`Options::collapse_arrays`]. It's
+    /// never empty, and its elements are never [`PrimitiveValue::EmptyArray`],
+    /// [`PrimitiveValue::EmptyObject`] or [`PrimitiveValue::Scalars`] themselves.
+    Scalars(Vec<PrimitiveValue>),
+}
+
+impl PartialEq for PrimitiveValue {
+    fn eq(&self, other: &Self) -> bool {
+        use PrimitiveValue::*;
+        match (self, other) {
+            (Null, Null) | (EmptyArray, EmptyArray) | (EmptyObject, EmptyObject) => true,
+            (Boolean(a), Boolean(b)) => a == b,
+            (Number(a), Number(b)) => a.to_bits() == b.to_bits(),
+            (Integer(a), Integer(b)) => a == b,
+            (String(a), String(b)) => a == b,
+            (Bytes(a), Bytes(b)) => a == b,
+            (Scalars(a), Scalars(b)) => a == b,
+            _ => false,
+        }
+    }
+}
+
+impl PrimitiveValue {
+    /// [`PrimitiveValue::Number`] if `v` is exactly representable as one, and
+    /// [`PrimitiveValue::Integer`] otherwise.
+    pub fn integer(v: impl Into<i128>) -> Self {
+        let v = v.into();
+        if v.unsigned_abs() <= 1 << 53 {
+            PrimitiveValue::Number(v as f64)
+        } else {
+            PrimitiveValue::Integer(v)
+        }
+    }
+
+    /// The base64 string [`PrimitiveValue::Bytes`] are written as, with the standard alphabet and
+    /// padding.
+    pub fn base64(bytes: &[u8]) -> String {
+        use base64::Engine;
+        base64::engine::general_purpose::STANDARD.encode(bytes)
+    }
+
+    /// The jq type name of the value, e.g. `number`, or `string` for [`PrimitiveValue::Bytes`],
+    /// which are written as strings.
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            PrimitiveValue::Null => "null",
+            PrimitiveValue::Boolean(_) => "boolean",
+            PrimitiveValue::Number(_) | PrimitiveValue::Integer(_) => "number",
+            PrimitiveValue::String(_) | PrimitiveValue::Bytes(_) => "string",
+            PrimitiveValue::EmptyArray | PrimitiveValue::Scalars(_) => "array",
+            PrimitiveValue::EmptyObject => "object",
+        }
+    }
+
+    /// Like `==`, except that numbers also match if they're at most `eps` apart, e.g. `0.1 + 0.2`
+    /// matches `0.3` with an `eps` of `1e-9`. The difference is absolute, not relative to the
+    /// magnitude of the numbers. Infinities only match themselves, and `NaN` only matches a `NaN`
+    /// with the same bits, as with `==`.
+    pub fn approx_eq(&self, other: &PrimitiveValue, eps: f64) -> bool {
+        let as_f64 = |value: &PrimitiveValue| match value {
+            PrimitiveValue::Number(v) => Some(*v),
+            PrimitiveValue::Integer(v) => Some(*v as f64),
+            _ => None,
+        };
+        match (as_f64(self), as_f64(other)) {
+            _ if self == other => true,
+            (Some(a), Some(b)) => a.to_bits() == b.to_bits() || (a - b).abs() <= eps,
+            _ => false,
+        }
+    }
+}
+
+impl Eq for PrimitiveValue {}
+
+impl std::hash::Hash for PrimitiveValue {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        std::mem::discriminant(self).hash(state);
+        match self {
+            PrimitiveValue::Boolean(b) => b.hash(state),
+            PrimitiveValue::Number(v) => v.to_bits().hash(state),
+            PrimitiveValue::Integer(v) => v.hash(state),
+            PrimitiveValue::String(s) => s.hash(state),
+            PrimitiveValue::Bytes(b) => b.hash(state),
+            PrimitiveValue::Scalars(values) => values.hash(state),
+            PrimitiveValue::Null | PrimitiveValue::EmptyArray | PrimitiveValue::EmptyObject => {}
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct PathValue {
+    pub path: Path,
+    pub value: Option<PrimitiveValue>,
+    /// The number of elements or entries of the container that a close marker closes, with
+    /// [`Options::close_with_count`]. Always `None` for leaves.
+    pub count: Option<usize>,
+}
+/// How to stream the input. Built with [`Options::builder`], or from [`Options::default`] by
+/// setting its fields, since more are added over time.
+#[derive(Clone, Debug, Default)]
+#[non_exhaustive]
+pub struct Options {
+    /// Expand YAML merge keys (`<<`) before streaming a mapping.
+    pub merge_keys: bool,
+    /// Maximum nesting depth of arrays and maps. A top-level container has depth 1.
+    pub max_depth: Option<usize>,
+    /// Maximum number of keys of a map, to bound what a single map of untrusted input can make
+    /// the stream go through.
+    pub max_keys_per_object: Option<usize>,
+    /// Maximum number of elements of an array, like [`Options::max_keys_per_object`] for maps.
+    pub max_array_length: Option<usize>,
+    /// Don't emit anything under these paths, including close markers of containers under them.
+    pub drop: Vec<Path>,
+    /// Treat the documents as elements of one top-level array, i.e. prefix paths of the `n`-th
+    /// document with `n`, and close the array after the last document.
+    pub slurp: bool,
+    /// Report an error in a document and continue with the next one, instead of stopping at the
+    /// first error. Values emitted from the bad document before the error are not retracted. See
+    /// [`JsonDocuments`] for how JSON input resynchronizes. A syntax error in YAML still ends the
+    /// stream, since the YAML parser can't resynchronize after one. I/O errors always end the
+    /// stream.
+    pub skip_errors: bool,
+    /// Stop after emitting this many documents, without reading the rest of the input. Documents
+    /// that failed with [`Options::skip_errors`] count too.
+    pub max_documents: Option<usize>,
+    /// Only emit the documents with these 0-based indices in the input, and stop reading after the
+    /// range. Documents before the range are still parsed, to find where the next one starts.
+    pub documents: Option<Range<usize>>,
+    /// Prefix paths with the index of the document in the input, after the index added by
+    /// [`Options::slurp`] if any. Unlike `slurp`, there's no close marker for the documents.
+    pub with_doc_index: bool,
+    /// Normalize string values to Unicode NFC. Map keys are left as they are.
+    pub normalize_nfc: bool,
+    /// Trim leading and trailing whitespace of string values. Map keys are left as they are.
+    pub trim: bool,
+    /// Emit string values that look like JSON numbers, `true`, `false` or `null` as such, e.g. for
+    /// [`Properties`] whose values are all strings.
+    pub infer_types: bool,
+    /// Don't emit `null` leaves. Close markers are still emitted as usual, so the close marker of
+    /// a container whose last value is `null` still has the path of that value.
+    pub null_as_missing: bool,
+    /// Which close markers to emit.
+    pub close_markers: CloseMarkers,
+    /// Give each close marker the number of elements or entries of its container, as
+    /// [`PathValue::count`].
+    pub close_with_count: bool,
+    /// How many values the background thread may parse ahead of the iterator, for each reader of
+    /// [`stream_merged`]. `None` for the default of 1. A larger buffer can help when the values
+    /// are consumed in bursts, at the cost of holding more of them in memory.
+    pub buffer: Option<usize>,
+    /// What to do with YAML tags such as `!Secret`.
+    pub yaml_tags: YamlTags,
+    /// Which untagged YAML scalars are numbers, booleans or `null` rather than strings.
+    pub yaml_scalars: YamlScalars,
+    /// Fail on documents that are a scalar rather than an array or an object, with
+    /// [`StreamError::NotAContainer`].
+    pub require_container: bool,
+    /// The line that separates the records of [`Kv`], `None` for blank lines.
+    pub record_separator: Option<String>,
+    /// The format of the payload of each type tag of [`Framed`].
+    pub frame_tags: Vec<(u8, Format)>,
+    /// Emit each non-empty array whose elements are all scalars as one
+    /// [`PrimitiveValue::Scalars`] leaf at the path of the array, e.g. `[["tags"],["a","b"]]`,
+    /// instead of a leaf per element. An array with an array or an object in it is streamed as
+    /// usual, and the arrays in it may be collapsed on their own. Elements are held in memory
+    /// until the end of the array or its first container.
+    pub collapse_arrays: bool,
+    /// Give map keys as [`Index::OrderedKey`]s, with the position of each key in its map, so that
+    /// the order of the keys survives consumers that sort them.
+    pub index_object_keys: bool,
+}
+
+impl Options {
+    /// A builder starting from the default options, e.g.
+    /// `Options::builder().max_depth(64).skip_errors(true).build()`.
+    pub fn builder() -> OptionsBuilder {
+        OptionsBuilder::default()
+    }
+
+    fn channel<T>(&self, readers: usize) -> (SyncSender<T>, Receiver<T>) {
+        sync_channel(self.buffer.unwrap_or(1) * readers)
+    }
+}
+
+/// Builds [`Options`], with a method setting each of its fields.
+#[derive(Clone, Debug, Default)]
+pub struct OptionsBuilder(Options);
+
+macro_rules! setters {
+    ($($field:ident: $type:ty,)*) => {
+        impl OptionsBuilder {
+            $(
+                #[doc = concat!("Sets [`Options::", stringify!($field), "`].")]
+                pub fn $field(mut self, $field: impl Into<$type>) -> Self {
+                    self.0.$field = $field.into();
+                    self
+                }
+            )*
+
+            /// The options as set.
+            pub fn build(self) -> Options {
+                self.0
+            }
+        }
+    };
+}
+
+setters! {
+    merge_keys: bool,
+    max_depth: Option<usize>,
+    max_keys_per_object: Option<usize>,
+    max_array_length: Option<usize>,
+    drop: Vec<Path>,
+    slurp: bool,
+    skip_errors: bool,
+    max_documents: Option<usize>,
+    documents: Option<Range<usize>>,
+    with_doc_index: bool,
+    normalize_nfc: bool,
+    trim: bool,
+    infer_types: bool,
+    null_as_missing: bool,
+    close_markers: CloseMarkers,
+    close_with_count: bool,
+    buffer: Option<usize>,
+    yaml_tags: YamlTags,
+    yaml_scalars: YamlScalars,
+    require_container: bool,
+    record_separator: Option<String>,
+    frame_tags: Vec<(u8, Format)>,
+    collapse_arrays: bool,
+    index_object_keys: bool,
+}
+
+/// Which kinds of containers get a close marker.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum CloseMarkers {
+    Arrays,
+    Objects,
+    /// Like jq.
+    #[default]
+    Both,
+    None,
+}
+
+impl CloseMarkers {
+    fn close(self, array: bool) -> bool {
+        match self {
+            CloseMarkers::Arrays => array,
+            CloseMarkers::Objects => !array,
+            CloseMarkers::Both => true,
+            CloseMarkers::None => false,
+        }
+    }
+}
+
+/// How values with a local YAML tag, e.g. `!Secret value`, are streamed. Standard tags such as
+/// `!!str` only decide how the value is parsed and are never output.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum YamlTags {
+    /// Stream the value as the only entry of an object, keyed by the tag with its leading `!`, so
+    /// that `!Secret value` is streamed like `{"!Secret": "value"}`.
+    Preserve,
+    /// Stream the value as if it wasn't tagged.
+    #[default]
+    Strip,
+}
+
+/// Which plain, i.e. unquoted and untagged, YAML scalars are numbers, booleans or `null`.
+///
+/// `serde_yaml` resolves them by the YAML 1.2 core schema, not by YAML 1.1 as e.g. PyYAML does, so
+/// `yes`, `no`, `on`, `off` and `NO` are strings either way, as are `1_000` and sexagesimals like
+/// `1:30`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum YamlScalars {
+    /// As `serde_yaml` resolves them: `true`, `True` and `TRUE` are booleans, `null`, `Null`, `~`
+    /// and nothing at all are `null`, and numbers may be written e.g. as `0x1F`, `0o17`, `+12`,
+    /// `1e3`, `1.10` or `.inf`.
+    #[default]
+    Loose,
+    /// Only `true`, `false`, `null`, and numbers written as Rust writes them back, like `42`, `-3`
+    /// or `0.5`, are resolved as in `loose`. Other scalars that would be resolved to something else
+    /// than a string are strings as written, e.g. `"1.10"`, `"1.0"`, `"0x1F"`, `"~"` or `""`, so
+    /// that version strings keep their digits. Values with a standard tag like `!!float 1.0` are
+    /// strings as written too.
+    ///
+    /// Each document is parsed twice, and the whole input is held in memory twice to do so.
+    Strict,
+}
+
+fn infer_type(s: String) -> PrimitiveValue {
+    match s.as_str() {
+        "null" => PrimitiveValue::Null,
+        "true" => PrimitiveValue::Boolean(true),
+        "false" => PrimitiveValue::Boolean(false),
+        _ if s.trim() == s => match serde_json::from_str::<serde_json::Number>(&s) {
+            Ok(n) => json_number(&n),
+            Err(_) => PrimitiveValue::String(s),
+        },
+        _ => PrimitiveValue::String(s),
+    }
+}
+
+/// The [`PrimitiveValue`] of a number parsed by `serde_json`, which is an integer if it was written
+/// as one and fits in 64 bits.
+fn json_number(n: &serde_json::Number) -> PrimitiveValue {
+    match (n.as_i64(), n.as_u64()) {
+        (Some(v), _) => PrimitiveValue::integer(v),
+        (None, Some(v)) => PrimitiveValue::integer(v),
+        (None, None) => {
+            PrimitiveValue::Number(n.as_f64().expect("serde_json numbers convert to f64"))
+        }
+    }
+}
+
+/// Why the visitor bailed out of deserialization, if it did so on its own.
+enum Abort {
+    /// The receiver has been dropped, so nobody is interested in the rest of the input.
+    Disconnected,
+    /// An error that should be reported as-is rather than as a deserialization error.
+    Error(StreamError),
+}
+
+/// Where a [`StreamState`] sends the values it streams, e.g. a closure.
+pub trait Sink {
+    /// Takes the next value, returning `false` to stop the deserialization, e.g. because nobody is
+    /// interested in the rest of the input.
+    fn send(&mut self, value: PathValue) -> bool;
+}
+
+impl<F: FnMut(PathValue) -> bool> Sink for F {
+    fn send(&mut self, value: PathValue) -> bool {
+        self(value)
+    }
+}
+
+/// The visitor behind [`stream`], that streams whatever it deserializes as [`PathValue`]s to a
+/// [`Sink`], for driving deserializers that the functions of this crate don't cover.
+///
+/// As a [`serde::de::DeserializeSeed`], it streams a single value. [`Options`] that are about
+/// documents, like [`Options::slurp`] or [`Options::documents`], don't apply, since there's only
+/// one document.
+///
+/// Each change to the path and each value or close marker emitted is logged with the [`log`] crate
+/// at the trace level, e.g. to find where a deserializer leaves the path in a bad state. Unless
+/// a logger is set up to take them, that costs a check of the level each time, and a release
+/// build can drop them altogether with the `release_max_level_debug` feature of `log`.
+///
+/// ```
+/// use serde::de::DeserializeSeed;
+/// use stream::{Options, PathValue, StreamState};
+///
+/// let mut values = vec![];
+/// let mut path = vec![];
+/// let sink = |value: PathValue| {
+///     values.push(value);
+///     true
+/// };
+/// let mut state = StreamState::new(sink, &mut path, Options::default());
+/// let document = serde_json::json!({"a": [1, 2]});
+/// state.deserialize(document).unwrap();
+/// // Two leaves, and the close markers of the array and of the object.
+/// assert_eq!(values.len(), 4);
+/// ```
+pub struct StreamState<'a, S> {
+    sink: S,
+    path: &'a mut Path,
+    options: Rc<Options>,
+    abort: Rc<RefCell<Option<Abort>>>,
+    /// How to ask for the next value, which is reset to [`Hint::Any`] once used.
+    hint: Hint,
+    /// The length of the path of the top-level value, i.e. of the prefix added to all paths.
+    root_len: usize,
+    /// The 0-based index of the document in its input.
+    document: usize,
+    /// With [`Options::collapse_arrays`], the elements of the array being visited and their
+    /// indices, held back while they are all scalars.
+    collapsed: Option<Vec<(usize, PrimitiveValue)>>,
+}
+
+impl<'a, S: Sink> StreamState<'a, S> {
+    /// A visitor that sends what it streams to `sink`. `path` is where the paths are built, and
+    /// what they are prefixed with, e.g. `[0]` to stream a value like the first element of an
+    /// array. It's as it was given once a value has been streamed, or has failed to.
+    pub fn new(sink: S, path: &'a mut Path, options: Options) -> Self {
+        let root_len = path.len();
+        Self {
+            sink,
+            path,
+            options: Rc::new(options),
+            abort: Rc::new(RefCell::new(None)),
+            hint: Hint::Any,
+            root_len,
+            document: 0,
+            collapsed: None,
+        }
+    }
+
+    /// Why the visitor failed the deserialization itself, if it did: an error like
+    /// [`StreamError::DepthExceeded`], which the deserializer only got as the message of a custom
+    /// error. `None` if it failed for another reason, including the [`Sink`] stopping it.
+    pub fn take_error(&mut self) -> Option<StreamError> {
+        match self.abort.borrow_mut().take()? {
+            Abort::Disconnected => None,
+            Abort::Error(error) => Some(error),
+        }
+    }
+
+    fn push(&mut self, index: Index) {
+        self.path.push(index);
+        log::trace!("push {:?}", self.path);
+    }
+
+    fn pop(&mut self) -> Option<Index> {
+        let index = self.path.pop();
+        log::trace!("pop {index:?}, leaving {:?}", self.path);
+        index
+    }
+
+    fn send<E: serde::de::Error>(&mut self, value: PathValue) -> Result<(), E> {
+        if self
+            .options
+            .drop
+            .iter()
+            .any(|prefix| filter::is_under(&value, prefix))
+        {
+            return Ok(());
+        }
+        spans::record(&value);
+        if !self.sink.send(value) {
+            self.abort.borrow_mut().replace(Abort::Disconnected);
+            return Err(E::custom("receiver has been dropped"));
+        }
+        Ok(())
+    }
+
+    fn emit_value<E: serde::de::Error>(&mut self, value: PrimitiveValue) -> Result<(), E> {
+        log::trace!("emit_value {value:?} at {:?}", self.path);
+        let container = matches!(
+            value,
+            PrimitiveValue::EmptyArray | PrimitiveValue::EmptyObject | PrimitiveValue::Scalars(_)
+        );
+        if self.options.require_container && !container && self.path.len() == self.root_len {
+            return Err(self.fail(StreamError::NotAContainer {
+                document: self.document,
+            }));
+        }
+        if self.options.null_as_missing && matches!(value, PrimitiveValue::Null) {
+            return Ok(());
+        }
+        let value = match value {
+            PrimitiveValue::String(s) if self.options.infer_types => infer_type(self.normalize(s)),
+            PrimitiveValue::String(s) => PrimitiveValue::String(self.normalize(s)),
+            value => value,
+        };
+        if let (Some(collapsed), Some(&Index::Array(i))) = (&mut self.collapsed, self.path.last()) {
+            collapsed.push((i, value));
+            return Ok(());
+        }
+        self.send(PathValue {
+            path: self.path.clone(),
+            value: Some(value),
+            count: None,
+        })
+    }
+
+    /// Emits the elements held back by [`Options::collapse_arrays`] as they are, since the array
+    /// turned out to have a container at the current path.
+    fn flush_collapsed<E: serde::de::Error>(&mut self) -> Result<(), E> {
+        let Some(collapsed) = self.collapsed.take() else {
+            return Ok(());
+        };
+        let current = self.pop();
+        for (i, value) in collapsed {
+            self.push(Index::Array(i));
+            self.send(PathValue {
+                path: self.path.clone(),
+                value: Some(value),
+                count: None,
+            })?;
+            self.pop();
+        }
+        if let Some(index) = current {
+            self.push(index);
+        }
+        Ok(())
+    }
+
+    fn normalize(&self, s: String) -> String {
+        let s = if self.options.trim && s.trim() != s {
+            s.trim().to_string()
+        } else {
+            s
+        };
+        if self.options.normalize_nfc && !unicode_normalization::is_nfc(&s) {
+            s.nfc().collect()
+        } else {
+            s
+        }
+    }
+
+    /// Emits the close marker of an array or an object of `count` elements or entries, unless that
+    /// kind is left out by [`Options::close_markers`].
+    fn emit_close<E: serde::de::Error>(&mut self, array: bool, count: usize) -> Result<(), E> {
+        log::trace!("emit_close of {count} at {:?}", self.path);
+        if !self.options.close_markers.close(array) {
+            return Ok(());
+        }
+        self.send(PathValue {
+            path: self.path.clone(),
+            value: None,
+            count: self.options.close_with_count.then_some(count),
+        })
+    }
+
+    /// Whether the value at the current path is under one of [`Options::drop`], in which case it
+    /// can be skipped with [`serde::de::IgnoredAny`], which is much faster than visiting it only
+    /// for [`StreamState::send`] to throw away everything. Its close marker is still sent by its
+    /// container if it's the last child.
+    fn is_dropped(&self) -> bool {
+        self.options
+            .drop
+            .iter()
+            .any(|prefix| filter::starts_with(self.path, prefix))
+    }
+
+    fn fail<E: serde::de::Error>(&self, error: StreamError) -> E {
+        let message = error.to_string();
+        self.abort.borrow_mut().replace(Abort::Error(error));
+        E::custom(message)
+    }
+
+    fn check_depth<E: serde::de::Error>(&self) -> Result<(), E> {
+        match self.options.max_depth {
+            Some(max_depth) if self.path.len() >= max_depth => {
+                Err(self.fail(StreamError::DepthExceeded {
+                    path: self.path.clone(),
+                    max_depth,
+                }))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+impl<'de, 'a, S: Sink> serde::de::Visitor<'de> for &mut StreamState<'a, S> {
+    type Value = ();
+
+    fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            formatter,
+            "null, boolean, number, string, array, or map keyed with string"
+        )
+    }
+
+    fn visit_bool<E>(self, v: bool) -> Result<Self::Value, E>
+    where
+        E: serde::de::Error,
+    {
+        self.emit_value(PrimitiveValue::Boolean(v))
+    }
+
+    fn visit_i64<E>(self, v: i64) -> Result<Self::Value, E>
+    where
+        E: serde::de::Error,
+    {
+        self.emit_value(PrimitiveValue::integer(v))
+    }
+
+    fn visit_i128<E>(self, v: i128) -> Result<Self::Value, E>
+    where
+        E: serde::de::Error,
+    {
+        self.emit_value(PrimitiveValue::integer(v))
+    }
+
+    fn visit_u64<E>(self, v: u64) -> Result<Self::Value, E>
+    where
+        E: serde::de::Error,
+    {
+        self.emit_value(PrimitiveValue::integer(v))
+    }
+
+    fn visit_u128<E>(self, v: u128) -> Result<Self::Value, E>
+    where
+        E: serde::de::Error,
+    {
+        match i128::try_from(v) {
+            Ok(v) => self.emit_value(PrimitiveValue::integer(v)),
+            Err(_) => self.emit_value(PrimitiveValue::Number(v as f64)),
+        }
+    }
+
+    fn visit_f64<E>(self, v: f64) -> Result<Self::Value, E>
+    where
+        E: serde::de::Error,
+    {
+        self.emit_value(PrimitiveValue::Number(v))
+    }
+
+    fn visit_str<E>(self, v: &str) -> Result<Self::Value, E>
+    where
+        E: serde::de::Error,
+    {
+        self.visit_string(v.into())
+    }
+
+    fn visit_string<E>(self, v: String) -> Result<Self::Value, E>
+    where
+        E: serde::de::Error,
+    {
+        self.emit_value(PrimitiveValue::String(v))
+    }
+
+    fn visit_bytes<E>(self, v: &[u8]) -> Result<Self::Value, E>
+    where
+        E: serde::de::Error,
+    {
+        self.visit_byte_buf(v.into())
+    }
+
+    fn visit_byte_buf<E>(self, v: Vec<u8>) -> Result<Self::Value, E>
+    where
+        E: serde::de::Error,
+    {
+        self.emit_value(PrimitiveValue::Bytes(v))
+    }
+
+    fn visit_none<E>(self) -> Result<Self::Value, E>
+    where
+        E: serde::de::Error,
+    {
+        self.emit_value(PrimitiveValue::Null)
+    }
+
+    fn visit_unit<E>(self) -> Result<Self::Value, E>
+    where
+        E: serde::de::Error,
+    {
+        self.visit_none()
+    }
+
+    /// `Some(v)` is streamed like `v`, so that it can't be told apart from a value that isn't an
+    /// `Option`, like `null` and a missing value can't in JSON.
+    fn visit_some<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        serde::de::DeserializeSeed::deserialize(self, deserializer)
+    }
+
+    fn visit_seq<A>(self, mut seq: A) -> Result<Self::Value, A::Error>
+    where
+        A: serde::de::SeqAccess<'de>,
+    {
+        self.check_depth()?;
+        self.flush_collapsed()?;
+        if self.options.collapse_arrays {
+            self.collapsed = Some(vec![]);
+        }
+        let mut i = 0;
+        self.push(Index::Array(i));
+        loop {
+            match self.options.max_array_length {
+                Some(max_length) if i == max_length => {
+                    if seq.next_element::<serde::de::IgnoredAny>()?.is_none() {
+                        break;
+                    }
+                    self.pop();
+                    return Err(self.fail(StreamError::ArrayTooLong {
+                        path: self.path.clone(),
+                        max_length,
+                    }));
+                }
+                _ => {}
+            }
+            let found = if self.is_dropped() {
+                seq.next_element::<serde::de::IgnoredAny>()?.is_some()
+            } else {
+                seq.next_element_seed(&mut *self)?.is_some()
+            };
+            if !found {
+                break;
+            }
+            self.pop();
+            i += 1;
+            self.push(Index::Array(i));
+        }
+        self.pop();
+        let collapsed = self.collapsed.take();
+        if i == 0 {
+            self.emit_value(PrimitiveValue::EmptyArray)?;
+        } else if let Some(collapsed) = collapsed.filter(|collapsed| !collapsed.is_empty()) {
+            let values = collapsed.into_iter().map(|(_, value)| value).collect();
+            self.send(PathValue {
+                path: self.path.clone(),
+                value: Some(PrimitiveValue::Scalars(values)),
+                count: None,
+            })?;
+        } else {
+            self.push(Index::Array(i - 1));
+            self.emit_close(true, i)?;
+            self.pop();
+        }
+        Ok(())
+    }
+
+    /// serde_yaml visits tagged values as enums, with the tag without its leading `!` as the
+    /// variant.
+    fn visit_enum<A>(self, data: A) -> Result<Self::Value, A::Error>
+    where
+        A: serde::de::EnumAccess<'de>,
+    {
+        use serde::de::VariantAccess;
+        let (tag, value) = data.variant::<String>()?;
+        if self.options.yaml_tags == YamlTags::Strip {
+            return value.newtype_variant_seed(self);
+        }
+        self.check_depth()?;
+        self.flush_collapsed()?;
+        self.push(Index::Map(format!("!{tag}")));
+        if self.is_dropped() {
+            value.newtype_variant::<serde::de::IgnoredAny>()?;
+        } else {
+            value.newtype_variant_seed(&mut *self)?;
+        }
+        self.emit_close(false, 1)?;
+        self.pop();
+        Ok(())
+    }
+
+    fn visit_map<A>(self, map: A) -> Result<Self::Value, A::Error>
+    where
+        A: serde::de::MapAccess<'de>,
+    {
+        self.check_depth()?;
+        if self.options.merge_keys {
+            use serde::de::Error;
+            let merged = buffer_and_merge(map)?;
+            self.walk_map(MergedMapAccess {
+                entries: merged.into_iter(),
+                value: None,
+            })
+            .map_err(A::Error::custom)
+        } else {
+            self.walk_map(map)
+        }
+    }
+}
+
+struct Str;
+impl<'de> serde::de::DeserializeSeed<'de> for Str {
+    type Value = String;
+
+    fn deserialize<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        struct V;
+        impl<'de> serde::de::Visitor<'de> for V {
+            type Value = String;
+
+            fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+                write!(formatter, "key of a map")
+            }
+
+            fn visit_bool<E>(self, v: bool) -> Result<Self::Value, E>
+            where
+                E: serde::de::Error,
+            {
+                Ok(v.to_string())
+            }
+
+            fn visit_i64<E>(self, v: i64) -> Result<Self::Value, E>
+            where
+                E: serde::de::Error,
+            {
+                Ok(v.to_string())
+            }
+
+            fn visit_u64<E>(self, v: u64) -> Result<Self::Value, E>
+            where
+                E: serde::de::Error,
+            {
+                Ok(v.to_string())
+            }
+
+            fn visit_i128<E>(self, v: i128) -> Result<Self::Value, E>
+            where
+                E: serde::de::Error,
+            {
+                Ok(v.to_string())
+            }
+
+            fn visit_u128<E>(self, v: u128) -> Result<Self::Value, E>
+            where
+                E: serde::de::Error,
+            {
+                Ok(v.to_string())
+            }
+
+            fn visit_f64<E>(self, v: f64) -> Result<Self::Value, E>
+            where
+                E: serde::de::Error,
+            {
+                Ok(serde_json::Value::from(v).to_string())
+            }
+
+            fn visit_unit<E>(self) -> Result<Self::Value, E>
+            where
+                E: serde::de::Error,
+            {
+                Ok("null".into())
+            }
+
+            fn visit_none<E>(self) -> Result<Self::Value, E>
+            where
+                E: serde::de::Error,
+            {
+                self.visit_unit()
+            }
+
+            fn visit_some<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+            where
+                D: serde::Deserializer<'de>,
+            {
+                deserializer.deserialize_any(self)
+            }
+
+            fn visit_bytes<E>(self, v: &[u8]) -> Result<Self::Value, E>
+            where
+                E: serde::de::Error,
+            {
+                Ok(PrimitiveValue::base64(v))
+            }
+
+            fn visit_seq<A>(self, seq: A) -> Result<Self::Value, A::Error>
+            where
+                A: serde::de::SeqAccess<'de>,
+            {
+                let deserializer = serde::de::value::SeqAccessDeserializer::new(seq);
+                let key: serde_json::Value = serde::Deserialize::deserialize(deserializer)?;
+                Ok(key.to_string())
+            }
+
+            fn visit_map<A>(self, map: A) -> Result<Self::Value, A::Error>
+            where
+                A: serde::de::MapAccess<'de>,
+            {
+                let deserializer = serde::de::value::MapAccessDeserializer::new(map);
+                let key: serde_json::Value = serde::Deserialize::deserialize(deserializer)?;
+                Ok(key.to_string())
+            }
+
+            fn visit_str<E>(self, v: &str) -> Result<Self::Value, E>
+            where
+                E: serde::de::Error,
+            {
+                Ok(v.into())
+            }
+
+            fn visit_string<E>(self, v: String) -> Result<Self::Value, E>
+            where
+                E: serde::de::Error,
+            {
+                Ok(v)
+            }
+        }
+        deserializer.deserialize_any(V)
+    }
+}
+
+impl<'a, S: Sink> StreamState<'a, S> {
+    fn walk_map<'de, A>(&mut self, mut map: A) -> Result<(), A::Error>
+    where
+        A: serde::de::MapAccess<'de>,
+    {
+        self.flush_collapsed()?;
+        let mut empty = true;
+        // Placeholder that gets replaced by each key. Whether the map is empty is tracked by
+        // `empty` rather than by this key, so that a genuine `""` key is fine.
+        self.push(Index::Map("".into()));
+        let mut keys = 0;
+        while let Some(key) = map.next_key_seed(Str)? {
+            empty = false;
+            keys += 1;
+            match self.options.max_keys_per_object {
+                Some(max_keys) if keys > max_keys => {
+                    self.pop();
+                    return Err(self.fail(StreamError::TooManyKeys {
+                        path: self.path.clone(),
+                        max_keys,
+                    }));
+                }
+                _ => {}
+            }
+            self.pop();
+            self.push(if self.options.index_object_keys {
+                Index::OrderedKey {
+                    key,
+                    index: keys - 1,
+                }
+            } else {
+                Index::Map(key)
+            });
+            if self.is_dropped() {
+                map.next_value::<serde::de::IgnoredAny>()?;
+            } else {
+                map.next_value_seed(&mut *self)?;
+            }
+        }
+        if empty {
+            self.pop();
+            self.emit_value(PrimitiveValue::EmptyObject)?;
+        } else {
+            self.emit_close(false, keys)?;
+            self.pop();
+        }
+        Ok(())
+    }
+}
+
+type YamlEntries = Vec<(serde_yaml::Value, serde_yaml::Value)>;
+
+/// Reads the whole mapping into memory and expands its merge keys (`<<`).
+///
+/// The value of a merge key is either a mapping or a sequence of mappings. Keys written
+/// explicitly in the mapping always win over merged ones, and for a sequence, mappings that
+/// come earlier win over later ones. Merged entries take the position of the `<<` key itself.
+fn buffer_and_merge<'de, A>(mut map: A) -> Result<YamlEntries, A::Error>
+where
+    A: serde::de::MapAccess<'de>,
+{
+    let mut entries = vec![];
+    while let Some(entry) = map.next_entry()? {
+        entries.push(entry);
+    }
+    merge_entries(entries).map_err(serde::de::Error::custom)
+}
+
+fn merge_entries(entries: YamlEntries) -> Result<YamlEntries, String> {
+    use serde_yaml::Value;
+    let merge_key = Value::String("<<".into());
+    let explicit: std::collections::HashSet<Value> = entries
+        .iter()
+        .map(|(k, _)| k.clone())
+        .filter(|k| k != &merge_key)
+        .collect();
+    let mut seen = std::collections::HashSet::new();
+    let mut ret = vec![];
+    for (key, value) in entries {
+        if key != merge_key {
+            seen.insert(key.clone());
+            ret.push((key, value));
+            continue;
+        }
+        let sources = match value {
+            Value::Mapping(m) => vec![m],
+            Value::Sequence(s) => s
+                .into_iter()
+                .map(|v| match v {
+                    Value::Mapping(m) => Ok(m),
+                    _ => Err("merge key expects a sequence of mappings".to_string()),
+                })
+                .collect::<Result<_, _>>()?,
+            _ => return Err("merge key expects a mapping or a sequence of mappings".into()),
+        };
+        for source in sources {
+            for (k, v) in merge_entries(source.into_iter().collect())? {
+                if !explicit.contains(&k) && seen.insert(k.clone()) {
+                    ret.push((k, v));
+                }
+            }
+        }
+    }
+    Ok(ret)
+}
+
+struct MergedMapAccess {
+    entries: std::vec::IntoIter<(serde_yaml::Value, serde_yaml::Value)>,
+    value: Option<serde_yaml::Value>,
+}
+impl<'de> serde::de::MapAccess<'de> for MergedMapAccess {
+    type Error = serde_yaml::Error;
+
+    fn next_key_seed<K>(&mut self, seed: K) -> Result<Option<K::Value>, Self::Error>
+    where
+        K: serde::de::DeserializeSeed<'de>,
+    {
+        match self.entries.next() {
+            Some((key, value)) => {
+                self.value = Some(value);
+                seed.deserialize(key).map(Some)
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn next_value_seed<V>(&mut self, seed: V) -> Result<V::Value, Self::Error>
+    where
+        V: serde::de::DeserializeSeed<'de>,
+    {
+        let value = self
+            .value
+            .take()
+            .expect("next_value_seed called before next_key_seed");
+        seed.deserialize(value)
+    }
+}
+
+impl<'de, 'a, S: Sink> serde::de::DeserializeSeed<'de> for &mut StreamState<'a, S> {
+    type Value = ();
+
+    fn deserialize<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let result = match std::mem::take(&mut self.hint) {
+            Hint::Any => deserializer.deserialize_any(&mut *self),
+            Hint::Seq => deserializer.deserialize_seq(&mut *self),
+            Hint::Map => deserializer.deserialize_map(&mut *self),
+            Hint::Struct { name, fields } => {
+                deserializer.deserialize_struct(name, fields, &mut *self)
+            }
+        };
+        // Leave the path as it was given even if the value couldn't be streamed whole.
+        result.inspect_err(|_| {
+            if self.path.len() > self.root_len {
+                log::trace!("truncate {:?} to the root after an error", self.path);
+                self.path.truncate(self.root_len);
+            }
+        })
+    }
+}
+
+/// What the top-level value of each document is asked for as, for formats that aren't
+/// self-describing and so can't be driven by `deserialize_any` alone.
+///
+/// Only the top-level value is asked for this way. Values inside it are still asked for with
+/// `deserialize_any`, so a format that needs hints for those too can't be streamed.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Hint {
+    /// `deserialize_any`, for self-describing formats.
+    #[default]
+    Any,
+    /// `deserialize_seq`.
+    Seq,
+    /// `deserialize_map`.
+    Map,
+    /// `deserialize_struct` with these field names.
+    Struct {
+        name: &'static str,
+        fields: &'static [&'static str],
+    },
+}
+
+pub trait MultiDocDeserializer<'de> {
+    /// How to ask for the top-level value of each document.
+    const HINT: Hint = Hint::Any;
+    type Error: DeserializeError;
+    type Iterator<T: serde::Deserialize<'de>>: Iterator<Item = Result<T, Self::Error>>;
+    fn into_multidoc_iter<T: serde::Deserialize<'de>>(self) -> Self::Iterator<T>;
+}
+
+impl<'de, R: serde_json::de::Read<'de>> MultiDocDeserializer<'de>
+    for serde_json::de::Deserializer<R>
+{
+    type Error = serde_json::Error;
+    type Iterator<T: serde::Deserialize<'de>> = serde_json::de::StreamDeserializer<'de, R, T>;
+
+    fn into_multidoc_iter<T: serde::Deserialize<'de>>(self) -> Self::Iterator<T> {
+        self.into_iter()
+    }
+}
+
+/// Iterates the documents of a [`serde_yaml::Deserializer`], ending after a syntax error.
+pub struct SerdeYamlMultiDocIter<'de, T> {
+    inner: serde_yaml::Deserializer<'de>,
+    last_error: Option<String>,
+    _phantom: PhantomData<T>,
+}
+impl<'de, T: serde::Deserialize<'de>> Iterator for SerdeYamlMultiDocIter<'de, T> {
+    type Item = Result<T, serde_yaml::Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let result = T::deserialize(self.inner.next()?);
+        // After a syntax error, every following document fails with the same error.
+        let error = result.as_ref().err().map(ToString::to_string);
+        if error.is_some() && error == std::mem::replace(&mut self.last_error, error.clone()) {
+            return None;
+        }
+        Some(result)
+    }
+}
+impl<'de> MultiDocDeserializer<'de> for serde_yaml::Deserializer<'de> {
+    type Error = serde_yaml::Error;
+    type Iterator<T: serde::Deserialize<'de>> = SerdeYamlMultiDocIter<'de, T>;
+
+    fn into_multidoc_iter<T: serde::Deserialize<'de>>(self) -> Self::Iterator<T> {
+        SerdeYamlMultiDocIter {
+            inner: self,
+            last_error: None,
+            _phantom: PhantomData,
+        }
+    }
+}
+
+/// The documents of [`Yaml`], with scalars resolved as [`Options::yaml_scalars`] says.
+pub enum YamlDocuments<'de, R> {
+    Loose(serde_yaml::Deserializer<'de>),
+    Strict(R),
+}
+
+impl<'de, R: std::io::Read + 'de> MultiDocDeserializer<'de> for YamlDocuments<'de, R> {
+    type Error = serde_yaml::Error;
+    type Iterator<T: serde::Deserialize<'de>> = YamlIter<'de, T>;
+
+    fn into_multidoc_iter<T: serde::Deserialize<'de>>(self) -> Self::Iterator<T> {
+        let mut read = match self {
+            YamlDocuments::Loose(de) => return YamlIter::Loose(de.into_multidoc_iter()),
+            YamlDocuments::Strict(read) => read,
+        };
+        let mut input = vec![];
+        if let Err(e) = read.read_to_end(&mut input) {
+            return YamlIter::Failed(Some(serde::de::Error::custom(e)));
+        }
+        YamlIter::Strict {
+            resolved: serde_yaml::Deserializer::from_reader(std::io::Cursor::new(input.clone()))
+                .into_multidoc_iter(),
+            written: serde_yaml::Deserializer::from_reader(std::io::Cursor::new(input)),
+            _phantom: PhantomData,
+        }
+    }
+}
+
+pub enum YamlIter<'de, T> {
+    Loose(SerdeYamlMultiDocIter<'de, T>),
+    /// Each document is parsed once as `resolved` and again as `written`, to find the scalars that
+    /// aren't written as they would be written back, and what they are written as.
+    Strict {
+        resolved: SerdeYamlMultiDocIter<'de, serde_yaml::Value>,
+        written: serde_yaml::Deserializer<'de>,
+        _phantom: PhantomData<T>,
+    },
+    /// The input couldn't be read.
+    Failed(Option<serde_yaml::Error>),
+}
+
+impl<'de, T: serde::Deserialize<'de>> Iterator for YamlIter<'de, T> {
+    type Item = Result<T, serde_yaml::Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self {
+            YamlIter::Loose(docs) => docs.next(),
+            YamlIter::Strict {
+                resolved, written, ..
+            } => {
+                let resolved = resolved.next()?;
+                let written = written.next()?;
+                Some(resolved.and_then(|resolved| {
+                    let value =
+                        serde::de::DeserializeSeed::deserialize(StrictScalars(&resolved), written)?;
+                    T::deserialize(value)
+                }))
+            }
+            YamlIter::Failed(e) => e.take().map(Err),
+        }
+    }
+}
+
+/// Deserializes a YAML value that has been deserialized as the resolved value before, as the same
+/// value except for the scalars that [`YamlScalars::Strict`] makes strings as written.
+struct StrictScalars<'a>(&'a serde_yaml::Value);
+
+impl<'de> serde::de::DeserializeSeed<'de> for StrictScalars<'_> {
+    type Value = serde_yaml::Value;
+
+    fn deserialize<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        use serde::Deserialize;
+        use serde_yaml::Value;
+        let written_back = match self.0 {
+            Value::Null => "null".to_string(),
+            Value::Bool(v) => v.to_string(),
+            Value::Number(n) => match (n.as_i64(), n.as_u64(), n.as_f64()) {
+                (Some(v), _, _) => v.to_string(),
+                (None, Some(v), _) => v.to_string(),
+                (None, None, v) => v.expect("a number is one of those").to_string(),
+            },
+            Value::Sequence(_) => return deserializer.deserialize_seq(self),
+            Value::Mapping(_) => return deserializer.deserialize_map(self),
+            // serde_yaml visits tagged values as enums, like for `StreamState::visit_enum`.
+            Value::Tagged(_) => return deserializer.deserialize_any(self),
+            Value::String(_) => {
+                serde::de::IgnoredAny::deserialize(deserializer)?;
+                return Ok(self.0.clone());
+            }
+        };
+        let written = String::deserialize(deserializer)?;
+        Ok(if written == written_back {
+            self.0.clone()
+        } else {
+            Value::String(written)
+        })
+    }
+}
+
+impl<'de> serde::de::Visitor<'de> for StrictScalars<'_> {
+    type Value = serde_yaml::Value;
+
+    fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(formatter, "the same value as before")
+    }
+
+    fn visit_seq<A>(self, mut seq: A) -> Result<Self::Value, A::Error>
+    where
+        A: serde::de::SeqAccess<'de>,
+    {
+        let resolved = self.0.as_sequence().expect("visited as a sequence");
+        let mut values = Vec::with_capacity(resolved.len());
+        for value in resolved {
+            values.extend(seq.next_element_seed(StrictScalars(value))?);
+        }
+        Ok(serde_yaml::Value::Sequence(values))
+    }
+
+    fn visit_map<A>(self, mut map: A) -> Result<Self::Value, A::Error>
+    where
+        A: serde::de::MapAccess<'de>,
+    {
+        let resolved = self.0.as_mapping().expect("visited as a mapping");
+        let mut entries = serde_yaml::Mapping::with_capacity(resolved.len());
+        for (key, value) in resolved {
+            if let Some(key) = map.next_key_seed(StrictScalars(key))? {
+                entries.insert(key, map.next_value_seed(StrictScalars(value))?);
+            }
+        }
+        Ok(serde_yaml::Value::Mapping(entries))
+    }
+
+    fn visit_enum<A>(self, data: A) -> Result<Self::Value, A::Error>
+    where
+        A: serde::de::EnumAccess<'de>,
+    {
+        use serde::de::VariantAccess;
+        let serde_yaml::Value::Tagged(resolved) = self.0 else {
+            unreachable!("visited as a tagged value");
+        };
+        let (_, value) = data.variant::<serde::de::IgnoredAny>()?;
+        let value = value.newtype_variant_seed(StrictScalars(&resolved.value))?;
+        Ok(serde_yaml::Value::Tagged(Box::new(
+            serde_yaml::value::TaggedValue {
+                tag: resolved.tag.clone(),
+                value,
+            },
+        )))
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum Format {
+    Json,
+    /// JSON Lines, i.e. exactly one JSON document per line. See [`JsonLines`].
+    Jsonl,
+    /// JSON with comments and trailing commas. See [`Jsonc`].
+    Jsonc,
+    Yaml,
+    Hocon,
+    Toml,
+    /// Plain text, each line of which is a string.
+    Raw,
+    /// Java `.properties`.
+    Properties,
+    /// Avro Object Container File.
+    Avro,
+    Ron,
+    /// Jackson's binary JSON.
+    Smile,
+    /// MessagePack values one after the other. See [`MsgpackStream`].
+    MsgpackStream,
+    /// CBOR items one after the other. See [`CborSeq`].
+    CborSeq,
+    /// Frames of a type tag, a length and a payload in the format of the tag. See [`Framed`].
+    Framed,
+    /// Blank-line-separated records of `key: value` lines. See [`Kv`].
+    Kv,
+    /// JSON Lines or JSON if the whole input is valid JSON, YAML or TOML otherwise. See
+    /// [`try_detect_format`].
+    Auto,
+}
+impl std::fmt::Display for Format {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Format::Json => write!(f, "JSON"),
+            Format::Jsonl => write!(f, "JSON Lines"),
+            Format::Jsonc => write!(f, "JSONC"),
+            Format::Yaml => write!(f, "YAML"),
+            Format::Hocon => write!(f, "HOCON"),
+            Format::Toml => write!(f, "TOML"),
+            Format::Raw => write!(f, "raw text"),
+            Format::Properties => write!(f, "Java properties"),
+            Format::Avro => write!(f, "Avro"),
+            Format::Ron => write!(f, "RON"),
+            Format::Smile => write!(f, "Smile"),
+            Format::MsgpackStream => write!(f, "MessagePack"),
+            Format::CborSeq => write!(f, "CBOR"),
+            Format::Framed => write!(f, "framed documents"),
+            Format::Kv => write!(f, "key-value records"),
+            Format::Auto => write!(f, "JSON, JSON Lines, YAML or TOML"),
+        }
+    }
+}
+
+/// Guesses whether `input` is JSON, JSON Lines or YAML.
+///
+/// Since JSON is almost a subset of YAML, YAML would accept most JSON documents but treat some of
+/// them differently, e.g. `1e400` is a string rather than an out-of-range number. So JSON is tried
+/// first: `input` is JSON if it consists of syntactically valid JSON documents, whatever their
+/// values are, and [`Format::Yaml`] otherwise.
+///
+/// JSON is then [`Format::Jsonl`] if it has at least two lines that aren't blank, and each of them
+/// is exactly one document, as in NDJSON exports. Otherwise it's [`Format::Json`], e.g. for a
+/// single array pretty-printed over many lines, or for documents separated by spaces. Only the
+/// shape of the input is looked at, so NDJSON of a single line is JSON, which streams the same,
+/// but so is NDJSON with a document split over two lines, which JSON Lines would reject.
+pub fn detect_format(input: &[u8]) -> Format {
+    let mut documents =
+        serde_json::Deserializer::from_slice(input).into_iter::<serde::de::IgnoredAny>();
+    if !documents.all(|document| document.is_ok()) {
+        return Format::Yaml;
+    }
+    let lines: Vec<_> = input
+        .split(|&c| c == b'\n')
+        .filter(|line| !line.iter().all(u8::is_ascii_whitespace))
+        .collect();
+    let one_document = |line: &&[u8]| serde_json::from_slice::<serde::de::IgnoredAny>(line).is_ok();
+    if lines.len() >= 2 && lines.iter().all(one_document) {
+        Format::Jsonl
+    } else {
+        Format::Json
+    }
+}
+
+/// The format of the file at `path` by its extension, e.g. [`Format::Yaml`] for `data.yml`, after
+/// dropping the extension of its [`Compression`](compression::Compression) if any, e.g. for
+/// `data.yaml.zst`. `None` for extensions of no format, and for formats that are never
+/// written to files, like [`Format::Raw`].
+pub fn detect_format_from_path(path: &std::path::Path) -> Option<Format> {
+    let path = match compression::Compression::from_path(path) {
+        Some(_) => std::path::Path::new(path.file_stem()?),
+        None => path,
+    };
+    match path.extension()?.to_str()? {
+        "json" => Some(Format::Json),
+        "jsonl" | "ndjson" => Some(Format::Jsonl),
+        "jsonc" => Some(Format::Jsonc),
+        "yaml" | "yml" => Some(Format::Yaml),
+        "conf" | "hocon" => Some(Format::Hocon),
+        "toml" => Some(Format::Toml),
+        "properties" => Some(Format::Properties),
+        "avro" => Some(Format::Avro),
+        "ron" => Some(Format::Ron),
+        "sml" | "smile" => Some(Format::Smile),
+        "msgpack" | "mpk" => Some(Format::MsgpackStream),
+        "cbor" | "cbors" => Some(Format::CborSeq),
+        _ => None,
+    }
+}
+
+/// The binary format that `input` starts with the magic bytes of, if any:
+///
+/// | Format              | Leading bytes                                 |
+/// |---------------------|-----------------------------------------------|
+/// | [`Format::Avro`]    | `Obj` and `0x01`                              |
+/// | [`Format::Smile`]   | `:)` and a newline, i.e. `0x3A 0x29 0x0A`     |
+/// | [`Format::CborSeq`] | `0xD9 0xD9 0xF7`, the self-described CBOR tag |
+///
+/// CBOR only has magic bytes if the first item has the optional self-described tag, and
+/// MessagePack has none at all, so they mostly aren't detected here; see [`try_detect_format`].
+/// BSON does start with its length, but it's not supported as input.
+pub fn detect_binary_format(input: &[u8]) -> Option<Format> {
+    const MAGIC: &[(&[u8], Format)] = &[
+        (b"Obj\x01", Format::Avro),
+        (b":)\n", Format::Smile),
+        (b"\xd9\xd9\xf7", Format::CborSeq),
+    ];
+    MAGIC
+        .iter()
+        .find(|(magic, _)| input.starts_with(magic))
+        .map(|&(_, format)| format)
+}
+
+/// Like [`detect_format`], but trying each format in turn and taking the first one the whole input
+/// parses as: a binary format by [`detect_binary_format`], then JSON, then YAML, then TOML, then
+/// [`Format::MsgpackStream`] as a last resort. `None` if it's none of them, e.g. for an unclosed
+/// `[`, unlike [`detect_format`] which would guess YAML.
+///
+/// YAML takes most input that isn't JSON, so it's only rejected for syntax errors, and TOML is
+/// mostly detected by its `[table]` headers, which YAML rejects. The exception is TOML of only
+/// `key = value` lines, which is also YAML, of a single string spanning all of them: YAML of a
+/// single string is TOML instead if it's valid TOML. MessagePack has no magic bytes, and every
+/// ASCII byte is a valid MessagePack integer, so it's only tried for input that isn't UTF-8 text,
+/// and only taken if all of it parses.
+pub fn try_detect_format(input: &[u8]) -> Option<Format> {
+    use serde::Deserialize;
+    if let Some(format) = detect_binary_format(input) {
+        return Some(format);
+    }
+    let toml = || std::str::from_utf8(input).is_ok_and(|s| s.parse::<toml::Table>().is_ok());
+    match detect_format(input) {
+        Format::Yaml => {
+            let yaml: Option<Vec<_>> = serde_yaml::Deserializer::from_slice(input)
+                .map(|document| serde_yaml::Value::deserialize(document).ok())
+                .collect();
+            match yaml.as_deref() {
+                Some([serde_yaml::Value::String(_)]) if toml() => Some(Format::Toml),
+                Some(_) => Some(Format::Yaml),
+                None if toml() => Some(Format::Toml),
+                None => {
+                    let msgpack = std::str::from_utf8(input).is_err()
+                        && MsgpackStream::from_reader(input)
+                            .into_multidoc_iter::<serde::de::IgnoredAny>()
+                            .all(|document| document.is_ok());
+                    msgpack.then_some(Format::MsgpackStream)
+                }
+            }
+        }
+        format => Some(format),
+    }
+}
+
+pub trait FromReader {
+    const FORMAT: Format;
+    type De<'de, R>: MultiDocDeserializer<'de>
+    where
+        R: 'de + std::io::Read;
+    fn from_reader<'de, R: 'de + std::io::Read>(read: R) -> Self::De<'de, R>;
+    /// Like [`FromReader::from_reader`], for formats that take some of the [`Options`] into
+    /// account while parsing.
+    fn from_reader_with<'de, R: 'de + std::io::Read>(
+        read: R,
+        _options: &Options,
+    ) -> Self::De<'de, R> {
+        Self::from_reader(read)
+    }
+}
+pub struct Yaml;
+impl FromReader for Yaml {
+    const FORMAT: Format = Format::Yaml;
+    type De<'de, R> = YamlDocuments<'de, R> where R: 'de + std::io::Read;
+
+    fn from_reader<'de, R: 'de + std::io::Read>(read: R) -> Self::De<'de, R> {
+        YamlDocuments::Loose(serde_yaml::Deserializer::from_reader(read))
+    }
+
+    fn from_reader_with<'de, R: 'de + std::io::Read>(
+        read: R,
+        options: &Options,
+    ) -> Self::De<'de, R> {
+        match options.yaml_scalars {
+            YamlScalars::Loose => Self::from_reader(read),
+            YamlScalars::Strict => YamlDocuments::Strict(read),
+        }
+    }
+}
+pub struct Json;
+impl FromReader for Json {
+    const FORMAT: Format = Format::Json;
+    type De<'de, R> = JsonDocuments<R> where R: 'de + std::io::Read;
+
+    fn from_reader<'de, R: 'de + std::io::Read>(read: R) -> Self::De<'de, R> {
+        JsonDocuments::new(read)
+    }
+}
+pub struct Jsonl;
+impl FromReader for Jsonl {
+    const FORMAT: Format = Format::Jsonl;
+    type De<'de, R> = JsonLines<R> where R: 'de + std::io::Read;
+
+    fn from_reader<'de, R: 'de + std::io::Read>(read: R) -> Self::De<'de, R> {
+        JsonLines::new(read)
+    }
+}
+
+/// The `(path, value)` pairs parsed by the background threads of [`stream`] and its siblings.
+///
+/// Dropping it stops the threads and waits for them to finish, so that none of them outlives the
+/// iterator. A thread only notices that when it has parsed its next value, so if it's blocked
+/// reading the input, dropping blocks too, until more input or the end of it comes, which may be
+/// never for e.g. `tail -f`. See [`Stream::detached`] to not wait.
+pub struct Stream {
+    /// Read one after the other, each until all of its senders are gone.
+    receivers: VecDeque<Receiver<Result<PathValue, StreamError>>>,
+    threads: Vec<JoinHandle<()>>,
+    /// Whether dropping waits for the threads.
+    join_on_drop: bool,
+}
+
+impl Stream {
+    fn new(
+        receiver: Receiver<Result<PathValue, StreamError>>,
+        threads: Vec<JoinHandle<()>>,
+    ) -> Self {
+        Self::chained(vec![receiver], threads)
+    }
+
+    fn chained(
+        receivers: Vec<Receiver<Result<PathValue, StreamError>>>,
+        threads: Vec<JoinHandle<()>>,
+    ) -> Self {
+        Self {
+            receivers: receivers.into(),
+            threads,
+            join_on_drop: true,
+        }
+    }
+
+    /// Makes dropping the stream return right away, without waiting for the threads. A thread
+    /// that's still parsing is left to end when it next fails to send a value, and one that's
+    /// blocked reading the input is left blocked, until the process exits if nothing else comes.
+    ///
+    /// [`Stream::join`] still waits.
+    pub fn detached(mut self) -> Self {
+        self.join_on_drop = false;
+        self
+    }
+
+    /// Stops the threads like dropping does, and returns the panic payload of the first thread
+    /// that panicked, if any.
+    ///
+    /// Once the iterator has ended, the threads have nothing left to do, so this observes whether
+    /// parsing completed normally.
+    pub fn join(mut self) -> std::thread::Result<()> {
+        self.stop()
+    }
+
+    fn stop(&mut self) -> std::thread::Result<()> {
+        // Makes pending and later sends fail, which ends the threads.
+        self.receivers.clear();
+        let mut result = Ok(());
+        for thread in self.threads.drain(..) {
+            if let Err(payload) = thread.join() {
+                result = result.and(Err(payload));
+            }
+        }
+        result
+    }
+}
+
+impl Iterator for Stream {
+    type Item = Result<PathValue, StreamError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some(receiver) = self.receivers.front() {
+            match receiver.recv() {
+                Ok(value) => return Some(value),
+                Err(_) => self.receivers.pop_front(),
+            };
+        }
+        None
+    }
+}
+
+impl Drop for Stream {
+    fn drop(&mut self) {
+        if self.join_on_drop {
+            let _ = self.stop();
+        }
+    }
+}
+
+/// Streams `(path, value)` pairs of every document in `read`, parsed as `T`.
+///
+/// Parsing happens in a background thread, and the iterator stops right after yielding the first
+/// error unless [`Options::skip_errors`] is set.
+pub fn stream<T: FromReader, R: std::io::Read + Send + 'static>(
+    read: R,
+    options: Options,
+) -> Stream {
+    let (sender, receiver) = options.channel(1);
+    let thread = std::thread::spawn(|| parse::<T, R>(read, options, None, sender));
+    Stream::new(receiver, vec![thread])
+}
+
+/// Streams all `readers` concurrently, each parsed as `T` in its own thread, merging their outputs
+/// into one iterator.
+///
+/// Paths from the `i`-th reader are prefixed with `i` as if each reader was an element of an array,
+/// without close markers for that array. Outputs of different readers are interleaved in the order
+/// they are parsed, and an error only ends the stream of the reader that caused it, wrapped in
+/// [`StreamError::InSource`].
+pub fn stream_merged<T: FromReader>(
+    readers: Vec<Box<dyn std::io::Read + Send>>,
+    options: Options,
+) -> Stream {
+    let (sender, receiver) = options.channel(readers.len());
+    let threads = readers
+        .into_iter()
+        .enumerate()
+        .map(|(i, read)| {
+            let sender = sender.clone();
+            let options = options.clone();
+            std::thread::spawn(move || parse::<T, _>(read, options, Some(i), sender))
+        })
+        .collect();
+    Stream::new(receiver, threads)
+}
+
+/// In which order [`stream_files`] outputs the values of different files.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum MergeOrder {
+    /// All the values of a file, then all of the next file, as if the files were parsed one after
+    /// the other.
+    #[default]
+    ByFile,
+    /// In the order they are parsed, so that values of different files are mixed.
+    Interleaved,
+}
+
+/// Streams the files at `paths`, each parsed as `T`, with up to `threads` of them parsed
+/// concurrently in their own threads. Paths and errors are prefixed and wrapped with the index of
+/// the file like [`stream_merged`] does, and an error only ends the stream of its file.
+///
+/// Files are taken up in order as threads become free. With [`MergeOrder::ByFile`], a file that's
+/// parsed ahead of the one being output stops once [`Options::buffer`] values are waiting, so
+/// the memory stays bounded, but the speedup depends on how large that buffer is.
+pub fn stream_files<T: FromReader>(
+    paths: Vec<std::path::PathBuf>,
+    threads: usize,
+    order: MergeOrder,
+    options: Options,
+) -> Stream {
+    let (senders, receivers): (Vec<_>, Vec<_>) = match order {
+        MergeOrder::ByFile => paths.iter().map(|_| options.channel(1)).unzip(),
+        MergeOrder::Interleaved => {
+            let (sender, receiver) = options.channel(threads);
+            (vec![sender; paths.len()], vec![receiver])
+        }
+    };
+    let jobs = Arc::new(Mutex::new(paths.into_iter().zip(senders).enumerate()));
+    let threads = (0..threads.clamp(1, jobs.lock().unwrap().len().max(1)))
+        .map(|_| {
+            let jobs = Arc::clone(&jobs);
+            let options = options.clone();
+            std::thread::spawn(move || loop {
+                let Some((i, (path, sender))) = jobs.lock().unwrap().next() else {
+                    break;
+                };
+                match std::fs::File::open(path) {
+                    Ok(file) => parse::<T, _>(file, options.clone(), Some(i), sender),
+                    Err(e) => {
+                        let error = Box::new(StreamError::Io(e));
+                        let _ = sender.send(Err(StreamError::InSource { index: i, error }));
+                    }
+                }
+            })
+        })
+        .collect();
+    Stream::chained(receivers, threads)
+}
+
+/// Streams the `(path, value)` pairs of `value`, as if it was a JSON document with no other
+/// documents around it.
+///
+/// `value` is cloned, so that it can be walked in a background thread like [`stream`] does.
+pub fn stream_value(value: &serde_json::Value, options: Options) -> Stream {
+    let (sender, receiver) = options.channel(1);
+    let value = value.clone();
+    let thread = std::thread::spawn(|| {
+        parse_documents(ValueDocument(value), Format::Json, options, None, sender)
+    });
+    Stream::new(receiver, vec![thread])
+}
+
+/// Like [`stream`] on the file at `path`, but JSON is parsed straight from a memory map of the
+/// file instead of through a reader, which saves copying it into buffers. Strings are still copied
+/// into the [`PrimitiveValue`]s. Other formats, and JSON with [`Options::skip_errors`] since
+/// resyncing after an error needs a reader, fall back to [`stream`] on the opened file.
+///
+/// # Safety
+///
+/// The file must not be modified while it's being parsed, not even by another process: that's
+/// undefined behavior with a memory map, and in practice the values may be garbled, or the process
+/// may be killed with `SIGBUS` if the file gets truncated. Parsing may still go on for a moment
+/// after a [`Stream::detached`] is dropped.
+pub unsafe fn stream_mmap<T: FromReader>(
+    path: impl AsRef<std::path::Path>,
+    options: Options,
+) -> std::io::Result<Stream> {
+    let file = std::fs::File::open(path)?;
+    if T::FORMAT != Format::Json || options.skip_errors {
+        return Ok(stream::<T, _>(file, options));
+    }
+    // SAFETY: It's up to the caller to not modify the file, see above.
+    let map = unsafe { memmap2::Mmap::map(&file)? };
+    let (sender, receiver) = options.channel(1);
+    let thread = std::thread::spawn(move || {
+        let de = serde_json::Deserializer::from_slice(&map);
+        parse_documents(de, Format::Json, options, None, sender)
+    });
+    Ok(Stream::new(receiver, vec![thread]))
+}
+
+/// Like [`stream`] of [`Json`], but also yields the byte span of each leaf in `read`, e.g. to map
+/// paths back to where they are in the source. The span of a string, number, `true`, `false` or
+/// `null` is from its first byte to right after its last one, and the span of an empty array or
+/// object is from its `[` or `{` to right after its `]` or `}`.
+///
+/// Spans are approximate at container boundaries: a non-empty array or object isn't a leaf, so its
+/// close markers have no span, and covering it from its first leaf to its last one leaves out its
+/// brackets and anything else around its leaves. An error ends the stream even with
+/// [`Options::skip_errors`], since there's no resynchronizing.
+pub fn stream_with_spans<R: std::io::Read + Send + 'static>(
+    read: R,
+    options: Options,
+) -> SpannedStream {
+    let (sender, receiver) = options.channel(1);
+    let (span_sender, spans) = std::sync::mpsc::channel();
+    let thread = std::thread::spawn(move || {
+        let read = spans::track(std::io::BufReader::new(read), span_sender);
+        let de = serde_json::Deserializer::from_reader(read);
+        parse_documents(de, Format::Json, options, None, sender)
+    });
+    SpannedStream {
+        stream: Stream::new(receiver, vec![thread]),
+        spans,
+    }
+}
+
+/// Deserializes the subtree at `at` of the first document in `read` that has it, parsed as `T`,
+/// into a `U`. Only that subtree is buffered, and reading stops right after it, so e.g.
+/// `.config.limits` can be extracted as a struct without loading the rest of the file.
+///
+/// Fails with [`StreamError::NotFound`] if no document has anything at `at`. `options` apply as in
+/// [`stream`], except that all close markers are needed to tell where the subtree ends.
+pub fn extract<T, U, R>(read: R, at: &Path, options: Options) -> Result<U, StreamError>
+where
+    T: FromReader,
+    U: serde::de::DeserializeOwned,
+    R: std::io::Read + Send + 'static,
+{
+    let options = Options {
+        close_markers: CloseMarkers::Both,
+        ..options
+    };
+    // Not waiting for the rest of `read`, which may never end, once the subtree is complete.
+    let stream = stream::<T, R>(read, options).detached();
+    let subtree = filter::TakeWhilePrefix::new(stream, at.clone());
+    let value = values::into_values(filter::StripPrefix::new(subtree, at.len()))
+        .next()
+        .ok_or_else(|| StreamError::NotFound { path: at.clone() })??;
+    serde_json::from_value(value).map_err(|e| e.into_stream_error(T::FORMAT))
+}
+
+/// Returns the `(path, value)` pairs of `value` in the same order as [`stream_value`] with the
+/// default [`Options`], including the close markers, but walking `value` in the calling thread.
+pub fn walk(value: &serde_json::Value) -> Vec<PathValue> {
+    fn walk_children<'a>(
+        children: impl Iterator<Item = (Index, &'a serde_json::Value)>,
+        path: &mut Path,
+        out: &mut Vec<PathValue>,
+    ) {
+        let mut children = children.peekable();
+        while let Some((index, value)) = children.next() {
+            path.push(index);
+            walk_into(value, path, out);
+            if children.peek().is_none() {
+                out.push(PathValue {
+                    path: path.clone(),
+                    value: None,
+                    count: None,
+                });
+            }
+            path.pop();
+        }
+    }
+    fn walk_into(value: &serde_json::Value, path: &mut Path, out: &mut Vec<PathValue>) {
+        use serde_json::Value;
+        let leaf = match value {
+            Value::Array(array) if !array.is_empty() => {
+                let children = array.iter().enumerate().map(|(i, v)| (Index::Array(i), v));
+                return walk_children(children, path, out);
+            }
+            Value::Object(map) if !map.is_empty() => {
+                let children = map.iter().map(|(k, v)| (Index::Map(k.clone()), v));
+                return walk_children(children, path, out);
+            }
+            Value::Array(_) => PrimitiveValue::EmptyArray,
+            Value::Object(_) => PrimitiveValue::EmptyObject,
+            Value::Null => PrimitiveValue::Null,
+            Value::Bool(v) => PrimitiveValue::Boolean(*v),
+            Value::Number(v) => json_number(v),
+            Value::String(v) => PrimitiveValue::String(v.clone()),
+        };
+        out.push(PathValue {
+            path: path.clone(),
+            value: Some(leaf),
+            count: None,
+        });
+    }
+    let mut out = vec![];
+    walk_into(value, &mut vec![], &mut out);
+    out
+}
+
+/// A [`serde_json::Value`] as a single document.
+struct ValueDocument(serde_json::Value);
+
+impl<'de> MultiDocDeserializer<'de> for ValueDocument {
+    type Error = serde_json::Error;
+    type Iterator<T: serde::Deserialize<'de>> = std::iter::Once<Result<T, serde_json::Error>>;
+
+    fn into_multidoc_iter<T: serde::Deserialize<'de>>(self) -> Self::Iterator<T> {
+        std::iter::once(T::deserialize(self.0))
+    }
+}
+
+/// Parses `read` and sends its `(path, value)` pairs to `sender`. If `source` is given, paths are
+/// prefixed with it and errors are wrapped in [`StreamError::InSource`].
+fn parse<T: FromReader, R: std::io::Read>(
+    read: R,
+    options: Options,
+    source: Option<usize>,
+    sender: SyncSender<Result<PathValue, StreamError>>,
+) {
+    parse_documents(
+        T::from_reader_with(read, &options),
+        T::FORMAT,
+        options,
+        source,
+        sender,
+    )
+}
+
+fn parse_documents<'de, D: MultiDocDeserializer<'de>>(
+    de: D,
+    format: Format,
+    options: Options,
+    source: Option<usize>,
+    sender: SyncSender<Result<PathValue, StreamError>>,
+) {
+    thread_local! {
+        static SENDER: RefCell<Option<SyncSender<Result<PathValue, StreamError>>>> = const { RefCell::new(None) };
+        static OPTIONS: RefCell<Option<Rc<Options>>> = const { RefCell::new(None) };
+        static ABORT: Rc<RefCell<Option<Abort>>> = Rc::new(RefCell::new(None));
+        static ROOT: RefCell<Path> = const { RefCell::new(vec![]) };
+        static SKIP: Cell<bool> = const { Cell::new(false) };
+        static HINT: Cell<Hint> = const { Cell::new(Hint::Any) };
+        static DOCUMENT: Cell<usize> = const { Cell::new(0) };
+    }
+    let slurp = options.slurp;
+    let skip_errors = options.skip_errors;
+    let max_documents = options.max_documents;
+    let range = options.documents.clone().unwrap_or(0..usize::MAX);
+    let with_doc_index = options.with_doc_index;
+    let root: Path = source.into_iter().map(Index::Array).collect();
+    SENDER.with(|snd| snd.borrow_mut().replace(sender));
+    OPTIONS.with(|opt| opt.borrow_mut().replace(Rc::new(options)));
+    ROOT.with(|r| *r.borrow_mut() = root.clone());
+    HINT.set(D::HINT);
+    struct Stream;
+    impl<'de> serde::Deserialize<'de> for Stream {
+        fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+        where
+            D: serde::Deserializer<'de>,
+        {
+            if SKIP.get() {
+                deserializer.deserialize_ignored_any(serde::de::IgnoredAny)?;
+                return Ok(Self);
+            }
+            let mut path = ROOT.with(|root| root.borrow().clone());
+            let root_len = path.len();
+            let sender = SENDER.with(|snd| snd.borrow().as_ref().unwrap().clone());
+            let options = OPTIONS.with(|opt| opt.borrow().as_ref().unwrap().clone());
+            let abort = ABORT.with(Rc::clone);
+            let mut visitor = StreamState {
+                sink: move |value| sender.send(Ok(value)).is_ok(),
+                path: &mut path,
+                options,
+                abort,
+                hint: HINT.get(),
+                root_len,
+                document: DOCUMENT.get(),
+                collapsed: None,
+            };
+            serde::de::DeserializeSeed::deserialize(&mut visitor, deserializer)?;
+            Ok(Self)
+        }
+    }
+    let mut docs = de.into_multidoc_iter::<Stream>();
+    // Documents read so far, and the ones of them that have been emitted.
+    let mut documents = 0;
+    let mut emitted = 0;
+    loop {
+        if documents >= range.end || max_documents.is_some_and(|max| emitted >= max) {
+            break;
+        }
+        let skip = documents < range.start;
+        SKIP.set(skip);
+        DOCUMENT.set(documents);
+        ROOT.with(|r| {
+            let mut r = r.borrow_mut();
+            r.clone_from(&root);
+            if slurp {
+                r.push(Index::Array(emitted));
+            }
+            if with_doc_index {
+                r.push(Index::Array(documents));
+            }
+        });
+        let Some(v) = docs.next() else {
+            break;
+        };
+        documents += 1;
+        if !skip {
+            emitted += 1;
+        }
+        if let Err(e) = v {
+            let mut error = match ABORT.with(|abort| abort.borrow_mut().take()) {
+                Some(Abort::Disconnected) => break,
+                Some(Abort::Error(error)) => error,
+                None => e.into_stream_error(format),
+            };
+            let fatal = !skip_errors || matches!(error, StreamError::Io(_));
+            if let Some(index) = source {
+                error = StreamError::InSource {
+                    index,
+                    error: Box::new(error),
+                };
+            }
+            let sent = SENDER.with(|snd| snd.borrow_mut().as_ref().unwrap().send(Err(error)));
+            if fatal || sent.is_err() {
+                return;
+            }
+        }
+    }
+    if slurp {
+        let root_len = root.len();
+        let mut path = root;
+        let sender = SENDER.with(|snd| snd.borrow().as_ref().unwrap().clone());
+        let mut visitor = StreamState {
+            sink: move |value| sender.send(Ok(value)).is_ok(),
+            path: &mut path,
+            options: OPTIONS.with(|opt| opt.borrow().as_ref().unwrap().clone()),
+            abort: ABORT.with(Rc::clone),
+            hint: Hint::Any,
+            root_len,
+            document: documents,
+            collapsed: None,
+        };
+        let result: Result<(), serde::de::value::Error> = if emitted == 0 {
+            visitor.emit_value(PrimitiveValue::EmptyArray)
+        } else {
+            visitor.push(Index::Array(emitted - 1));
+            visitor.emit_close(true, emitted)
+        };
+        result.ok(); // Failing only means the receiver has been dropped.
+    }
+}
@@ -1,363 +1,1590 @@
-#![feature(generic_associated_types)]
-
 use std::{
-    cell::RefCell,
-    marker::PhantomData,
-    sync::mpsc::{sync_channel, SyncSender},
+    cell::Cell,
+    ffi::OsString,
+    fs::File,
+    io::{self, BufReader, BufWriter, IsTerminal, Read, Write},
+    ops::Range,
+    path::PathBuf,
+    rc::Rc,
+    sync::atomic::Ordering,
+    time::Duration,
+};
+
+use anyhow::{bail, Context, Result};
+use clap::{
+    error::ErrorKind, parser::ValueSource, ArgMatches, CommandFactory, FromArgMatches, Parser,
+    ValueEnum,
 };
+use stream::{
+    compression::Compression,
+    decode::{DecodeReader, Encoding},
+    detect_format_from_path,
+    filter::{
+        DistinctValues, EveryNth, LeafType, OnlyTypes, Pick, PickMode, Rename, RenameRule,
+        ReservoirSample, StripPrefix, TakeWhilePrefix, WithContext,
+    },
+    path::parse_path,
+    peek::PeekReader,
+    progress::{CountingReader, Progress},
+    render::{Indent, LineTerminator, Printer, RenderOptions},
+    schema::Schema,
+    shape::{Depths, FirstSeen, Shape, ShapeIndex},
+    stream, stream_files, stream_mmap, stream_with_spans,
+    summary::Summaries,
+    table::ParquetWriter,
+    timeout::TimeoutReader,
+    try_detect_format,
+    values::{canonical_hash, into_values, write_canonical, Tee},
+    Avro, CborSeq, CloseMarkers, Format, Framed, FromReader, Hocon, Index, Json, Jsonc, Jsonl, Kv,
+    MergeOrder, MsgpackStream, Options, Path, PathValue, PrimitiveValue, Properties, RawLines, Ron,
+    Smile, Stream, StreamError, Toml, Yaml, YamlScalars, YamlTags,
+};
+use tempfile::NamedTempFile;
 
-use anyhow::{anyhow, Result};
+/// Options can also be given in the environment, for when passing them on the command line is
+/// awkward, e.g. in a container. `XQ_STREAM_OPTS` holds options that are parsed as if they were
+/// given before the others, split like shell words, and `XQ_STREAM_FORMAT` is the default of
+/// `--format`. An option on the command line overrides the one in `XQ_STREAM_OPTS`, which
+/// overrides `XQ_STREAM_FORMAT`, which overrides the built-in default.
+#[derive(Debug, Parser)]
+#[command(version, about, args_override_self = true)]
+struct Cli {
+    /// Format of the input.
+    ///
+    /// With `auto`, the whole input is read into memory, and parsed as JSON if it's valid JSON, or
+    /// as YAML, or as TOML otherwise, which is reported to stderr. Valid JSON is parsed as `jsonl`
+    /// if there are at least two lines and each of them is a whole document, e.g. NDJSON of
+    /// objects, and as `json` otherwise, e.g. for an array spanning many lines. `--ndjson` and
+    /// `--single` override that guess. TOML of only `key = value` lines is also YAML, of a single
+    /// string, which is taken as TOML instead. Input that isn't TOML either is an error.
+    ///
+    /// Binary input is detected by its leading bytes first: `Obj` and 0x01 for Avro, `:)` and a
+    /// newline for Smile, and the self-described CBOR tag 0xD9 0xD9 0xF7 for `cbor-seq`. Since
+    /// MessagePack has no such bytes, input that isn't UTF-8 and is neither of those is parsed as
+    /// `msgpack-stream` as a last resort, if all of it is valid MessagePack. Other CBOR has to be
+    /// given its `--format`.
+    ///
+    /// Since each format is tried on the whole input before it's streamed, nothing is output until
+    /// all of it has been read, and it all has to fit in memory. See `--auto-max-bytes`.
+    ///
+    /// The input of `--mmap FILE` is instead parsed as the format of the extension of FILE if it
+    /// has one, e.g. YAML for `data.yml` or `data.yaml.zst`, and only read whole to detect its
+    /// format otherwise.
+    #[arg(long, value_enum, env = "XQ_STREAM_FORMAT", default_value_t = Format::Json)]
+    format: Format,
 
-#[derive(Clone, Debug)]
-enum Index {
-    Array(usize),
-    Map(String),
-}
-type Path = Vec<Index>;
-
-#[derive(Debug)]
-enum PrimitiveValue {
-    Null,
-    Boolean(bool),
-    Number(f64),
-    String(String),
-    EmptyArray,
-    EmptyObject,
-}
+    /// Fail if the input of `--format auto` is larger than this, instead of reading all of it into
+    /// memory to detect its format.
+    #[arg(long, value_name = "BYTES")]
+    auto_max_bytes: Option<u64>,
+
+    /// With `--format auto`, parse valid JSON input as JSON Lines, i.e. each line as a document.
+    #[arg(long, conflicts_with = "single")]
+    ndjson: bool,
+
+    /// With `--format auto`, parse valid JSON input as plain JSON, e.g. a single array over many
+    /// lines, even if each line looks like a whole document.
+    #[arg(long)]
+    single: bool,
+
+    /// Read the input from FILE instead of stdin. JSON input is parsed straight from a memory map
+    /// of the file, which is faster for large files, and other formats read the file as usual.
+    ///
+    /// FILE must not be modified while it's being read, or the output may be garbled, or the
+    /// process may even crash if the file gets truncated. Bytes read aren't counted by
+    /// `--progress` for memory-mapped input.
+    ///
+    /// FILE is decompressed if it ends with `.gz` or `.zst`, and then read as usual.
+    #[arg(long, value_name = "FILE")]
+    mmap: Option<PathBuf>,
+
+    /// Read the input from these files instead of stdin, as if each of them was an element of an
+    /// array: paths are prefixed with the index of the file, without close markers for that
+    /// array, and an error only ends the output of its file.
+    ///
+    /// Bytes read from the files aren't counted by `--progress`.
+    #[arg(value_name = "FILE", conflicts_with = "mmap")]
+    files: Vec<PathBuf>,
+
+    /// Parse up to N of the FILEs concurrently, each in its own thread.
+    #[arg(
+        long,
+        value_name = "N",
+        default_value_t = 1,
+        value_parser = clap::value_parser!(u64).range(1..),
+        requires = "files"
+    )]
+    threads: u64,
+
+    /// In which order to output the values of different FILEs.
+    ///
+    /// With `by-file`, the values of each file form one block, in the order the files are given,
+    /// and a file parsed ahead of the one being output waits once `--buffer` values are ready.
+    /// With `interleaved`, values are output as soon as they're parsed, so that the files are
+    /// mixed but no thread waits for another file.
+    #[arg(long, value_enum, value_name = "ORDER", default_value_t = MergeOrder::ByFile)]
+    merge_order: MergeOrder,
+
+    /// Read each line of the input as a string, instead of parsing it. Same as `--format raw`.
+    ///
+    /// Each line is a separate top-level document, i.e. it's output as `[[],"line"]`.
+    #[arg(short = 'R', long, conflicts_with = "format")]
+    raw_input: bool,
+
+    /// Expand YAML merge keys (`<<`) into the mapping that contains them.
+    ///
+    /// Each mapping is buffered so that its merge keys can be resolved before any of its entries
+    /// are emitted. Keys written explicitly in the mapping take precedence over merged keys, and
+    /// when merging a sequence of mappings, earlier mappings take precedence over later ones.
+    /// Only affects YAML input.
+    #[arg(long)]
+    merge_keys: bool,
+
+    /// What to do with local YAML tags such as `!Secret`.
+    ///
+    /// With `preserve`, a tagged value is streamed as the only entry of an object, keyed by the
+    /// tag: `!Secret value` is streamed like `{"!Secret": "value"}`. Only affects YAML input.
+    #[arg(long, value_enum, value_name = "WHAT", default_value_t = YamlTags::Strip)]
+    yaml_tags: YamlTags,
+
+    /// Which unquoted YAML scalars are numbers, booleans or `null` rather than strings.
+    ///
+    /// YAML is parsed by the YAML 1.2 core schema, so unlike YAML 1.1, `yes`, `no`, `on`, `off`
+    /// and `NO` are always strings. With `loose`, `True`, `~`, nothing at all, or numbers written
+    /// e.g. as `0x1F`, `1e3`, `1.0` or `1.10` are resolved too. With `strict`, only `true`,
+    /// `false`, `null`, and numbers written as they would be written back, like `42` or `0.5`, are,
+    /// and other scalars are strings as written, e.g. `"1.10"` for a version. `strict` holds the
+    /// whole input in memory twice. Only affects YAML input.
+    #[arg(long, value_enum, value_name = "MODE", default_value_t = YamlScalars::Loose)]
+    yaml_scalar_mode: YamlScalars,
+
+    /// The line that separates records of `--format kv`, instead of a blank line. Leading and
+    /// trailing whitespace is ignored, both here and in the input.
+    #[arg(long, value_name = "LINE")]
+    record_separator: Option<String>,
+
+    /// The format of the payloads of `--format framed` with the type tag TAG, e.g. `j=json` or
+    /// `0x01=msgpack-stream`. TAG is a single ASCII character or a hexadecimal byte. Can be given
+    /// multiple times, once for each tag.
+    ///
+    /// Each frame of `--format framed` is its tag byte, then the length of its payload as a 32-bit
+    /// big-endian integer, then the payload, which is a document. Only the first document of a
+    /// payload is read. A frame with a tag that isn't given here is an error, after which
+    /// `--skip-errors` goes on with the next frame.
+    #[arg(
+        long = "frame-tag",
+        value_name = "TAG=FORMAT",
+        value_parser = parse_frame_tag,
+        required_if_eq("format", "framed")
+    )]
+    frame_tags: Vec<(u8, Format)>,
+
+    /// Fail if arrays and maps are nested deeper than this. A top-level container has depth 1.
+    #[arg(long, value_name = "DEPTH")]
+    max_depth: Option<usize>,
+
+    /// Fail at the first object with more than N keys, e.g. to bound the resources that untrusted
+    /// input can use without limiting its depth.
+    #[arg(long, value_name = "N")]
+    max_keys_per_object: Option<usize>,
+
+    /// Fail at the first array with more than N elements, like `--max-keys-per-object`.
+    #[arg(long, value_name = "N")]
+    max_array_length: Option<usize>,
+
+    /// Only output values at or under this path, e.g. `.items[0].name`. Can be given multiple
+    /// times.
+    #[arg(long, value_name = "PATH", value_parser = parse_path)]
+    pick: Vec<Path>,
+
+    /// Fail if there are values at or under this path, e.g. `.debug` to check that no config
+    /// file leaves a debug flag on. Can be given multiple times. Only the leaves under the paths
+    /// are output, as the violations, and the whole input is read before failing.
+    #[arg(
+        long,
+        value_name = "PATH",
+        value_parser = parse_path,
+        conflicts_with_all = ["hash_documents", "summary_per_doc", "schema"]
+    )]
+    forbid: Vec<Path>,
+
+    /// Whether `--pick` keeps the whole subtree under the paths, or only leaves exactly at them.
+    #[arg(long, value_enum, default_value_t = PickMode::Subtree)]
+    pick_mode: PickMode,
+
+    /// Only output values under this path like `--pick`, but stop reading the input at the first
+    /// value after them that isn't under it.
+    ///
+    /// Meant for when the values under the path are contiguous, like a single subtree, so that
+    /// the rest of the input doesn't need to be parsed. Values under the path that come after
+    /// other ones are not output, e.g. from later documents.
+    #[arg(long, value_name = "PATH", value_parser = parse_path)]
+    take_while_prefix: Option<Path>,
+
+    /// Don't output anything under this path. Can be given multiple times.
+    #[arg(long, value_name = "PATH", value_parser = parse_path)]
+    drop: Vec<Path>,
+
+    /// Rename map keys OLD to NEW in the output paths, either at any depth with `OLD=NEW`, or only
+    /// at DEPTH with `DEPTH:OLD=NEW`, where depth 0 is the keys of a top-level map. Can be given
+    /// multiple times.
+    ///
+    /// Each key is renamed by the first rule that matches it, and a renamed key isn't renamed
+    /// again by later rules. Renaming is done after `--pick` and `--drop`, so those match the
+    /// original keys.
+    #[arg(long, value_name = "RULE")]
+    rename: Vec<RenameRule>,
+
+    /// Drop the first N indices of every output path, e.g. 3 with `--pick .data.results` to output
+    /// each result as if it was a document.
+    ///
+    /// Leaves with paths shorter than N are not output, nor are the close markers with paths of at
+    /// most N indices, which close the containers the new roots are in. This is done after all the
+    /// other options that match paths, so those match the original paths.
+    #[arg(long, value_name = "N")]
+    strip_prefix_depth: Option<usize>,
+
+    /// Only output leaves of this type, without close markers or empty arrays and objects, e.g.
+    /// `--only-type string` to scan all the strings. Can be given multiple times.
+    #[arg(long, value_enum, value_name = "TYPE")]
+    only_type: Vec<LeafType>,
+
+    /// Instead of only the values matched by `--pick` and `--only-type`, also output up to N
+    /// values before and after each of them, like `grep -C`, including close markers.
+    ///
+    /// Overlapping contexts are merged, so each value is output once. Nothing separates groups of
+    /// values that aren't contiguous. Without `--pick` or `--only-type`, every value matches.
+    #[arg(long, value_name = "N")]
+    context: Option<usize>,
+
+    /// Only output one out of every N leaves, starting with the first one, e.g. for a uniform
+    /// sample of a huge array. Close markers don't count towards N and are not output. Combine
+    /// with `--limit` for a sample of bounded size.
+    #[arg(
+        long,
+        value_name = "N",
+        value_parser = clap::value_parser!(u64).range(1..),
+        conflicts_with_all = ["hash_documents", "summary_per_doc", "schema"]
+    )]
+    every_nth: Option<u64>,
+
+    /// Only output a uniform random sample of K leaves, or all of them if there are fewer, in the
+    /// order they come in. The sample is only known at the end, so the whole input is read before
+    /// anything but errors is output, and the K leaves are held in memory. Close markers are not
+    /// output.
+    #[arg(
+        long,
+        value_name = "K",
+        conflicts_with_all = ["hash_documents", "summary_per_doc", "schema"]
+    )]
+    reservoir_sample: Option<usize>,
+
+    /// Seed the random sample of `--reservoir-sample`, so that the same input always gives the
+    /// same sample. Without a seed, it's different each time.
+    #[arg(long, value_name = "SEED", requires = "reservoir_sample")]
+    seed: Option<u64>,
+
+    /// Stop after outputting N leaves and close markers, without reading the rest of the input.
+    /// Counts what's left after the options that drop values, like `--pick` or `--every-nth`, and
+    /// errors count too.
+    #[arg(
+        long,
+        value_name = "N",
+        conflicts_with_all = ["hash_documents", "summary_per_doc", "schema"]
+    )]
+    limit: Option<usize>,
+
+    /// Treat all the documents as elements of one top-level array, like `jq -s`.
+    ///
+    /// Paths of the N-th document are prefixed with N. With `--raw-input`, each line becomes an
+    /// element of the array rather than the whole input becoming one string as with `jq -Rs`.
+    #[arg(short, long)]
+    slurp: bool,
+
+    /// Report a bad document to stderr and continue with the next one, instead of stopping.
+    ///
+    /// After an error in JSON input, the rest of the line is skipped and parsing resumes from the
+    /// next line, which recovers from bad lines of NDJSON. A syntax error in YAML still stops
+    /// since the whole YAML input is parsed upfront, but other errors such as `--max-depth` skip
+    /// to the next document. Values output from the bad document before the error are kept.
+    #[arg(long)]
+    skip_errors: bool,
+
+    /// Stop after outputting this many top-level documents, without reading the rest of the input.
+    #[arg(long, value_name = "N")]
+    max_documents: Option<usize>,
+
+    /// Normalize string values to Unicode NFC, so that e.g. a precomposed `é` and `e` followed by a
+    /// combining accent are output the same.
+    ///
+    /// This changes the values, and the original form can't be recovered from the output. Map
+    /// keys are not normalized.
+    #[arg(long)]
+    normalize_nfc: bool,
+
+    /// Trim leading and trailing whitespace of string values. Like `--normalize-nfc`, this changes
+    /// the values irreversibly, and map keys are left as they are.
+    #[arg(long)]
+    trim: bool,
+
+    /// Output string values that look like JSON numbers, `true`, `false` or `null` as such.
+    ///
+    /// Meant for formats whose values are all strings, like `--format properties`, but applies to
+    /// string values of any format. Map keys are left as they are.
+    #[arg(long)]
+    infer_types: bool,
+
+    /// Which close markers to output: the ones of arrays, of objects, both like jq, or none.
+    #[arg(long, value_enum, value_name = "WHICH", default_value_t = CloseMarkers::Both)]
+    close_markers: CloseMarkers,
+
+    /// Don't output `null` values, as if they were missing.
+    ///
+    /// Close markers are output as usual, so a container whose last value is `null` is still
+    /// closed at the path of that value.
+    #[arg(long)]
+    null_as_missing: bool,
+
+    /// Fail on documents that are a scalar, i.e. that would be output as `[[],VALUE]`, rather than
+    /// an array or an object. Empty arrays and objects are fine.
+    #[arg(long)]
+    require_container: bool,
+
+    /// Instead of the values, output `[N, "HASH"]` for the N-th document, where HASH is the SHA-256
+    /// of the document as written by `--output canonical-json`.
+    ///
+    /// Documents with the same content get the same hash regardless of their key order.
+    #[arg(long, conflicts_with = "pick")]
+    hash_documents: bool,
+
+    /// Only output the documents with these 0-based indices, e.g. `1000..1100`, `1000..=1099`,
+    /// `1000..` or `..100`. Stops reading the input after the range.
+    #[arg(long, value_name = "RANGE", value_parser = parse_range)]
+    documents: Option<Range<usize>>,
+
+    /// Prefix paths with the 0-based index of the document in the input, e.g. `[[3,"a"],1]` for
+    /// `.a` of the fourth document.
+    #[arg(long, conflicts_with = "slurp")]
+    with_doc_index: bool,
+
+    /// Write each object key in paths with its 0-based position in the object, as
+    /// `{"key":"b","index":1}` instead of `"b"`, e.g. `[[{"key":"items","index":0},0],1]`, so that
+    /// the order of the keys can be recovered even if something downstream sorts them. Close
+    /// markers have the position of the last key of the object they close.
+    ///
+    /// Paths given to the other options match such keys by the key alone.
+    #[arg(long, conflicts_with = "jq_compat")]
+    index_object_keys: bool,
 
-struct PathValue {
-    path: Path,
-    value: Option<PrimitiveValue>,
+    /// Output each array of only scalars as one value at the path of the array, e.g.
+    /// `[["tags"],["a","b","c"]]` instead of a line per element, as for loading into a flat store
+    /// with multivalued fields.
+    ///
+    /// Only arrays whose elements are all strings, numbers, booleans or nulls are collapsed. An
+    /// array with an array or an object in it, even an empty one, is streamed as usual, but the
+    /// arrays in it are collapsed on their own. Empty arrays are still `[]`.
+    #[arg(long, conflicts_with_all = ["jq_compat", "with_spans"])]
+    collapse_arrays: bool,
+
+    /// Instead of the values, output the types of the values seen at each path, once the whole
+    /// input has been read.
+    ///
+    /// Array indices in the paths are replaced with `[]`, so that all the elements of an array
+    /// share the same paths, e.g. `[["items",[],"id"],"number|string"]`. Non-empty arrays and
+    /// objects only show up through the paths under them.
+    #[arg(long, conflicts_with = "hash_documents")]
+    shape: bool,
+
+    /// Instead of the values, output one line per document sketching its top-level structure,
+    /// e.g. `doc 0: object{id,name,items[3]}`, where `items[3]` is an array of 3 elements and
+    /// `meta{2}` would be an object with 2 keys. A top-level array is `array[N]`, and a scalar its
+    /// type name. Nothing deeper than the values of top-level keys is kept in memory.
+    #[arg(long, conflicts_with_all = ["hash_documents", "shape", "pick"])]
+    summary_per_doc: bool,
+
+    /// Only output the first leaf at each path with array indices ignored, like the paths of
+    /// `--shape`, as an example of the values there. Close markers are not output.
+    #[arg(long, conflicts_with_all = ["hash_documents", "shape", "summary_per_doc"])]
+    head_structure: bool,
+
+    /// How to write the output.
+    #[arg(long, value_enum, value_name = "FORMAT", default_value_t = OutputFormat::Stream)]
+    output: OutputFormat,
+
+    /// Instead of the values, output how many leaves there are at each depth, e.g. `depth 2: 10
+    /// values`, once the whole input has been read. A leaf directly in a top-level container has
+    /// depth 1, and a top-level scalar depth 0. Depths without leaves are left out.
+    #[arg(long, conflicts_with_all = ["hash_documents", "shape", "summary_per_doc"])]
+    group_by_depth: bool,
+
+    /// Instead of the values with their paths, output each distinct leaf value once, where it's
+    /// first seen, e.g. to list all the country codes in a document. Close markers are not output.
+    ///
+    /// All the distinct values seen so far are kept in memory, so this is only cheap if there are
+    /// relatively few of them.
+    #[arg(
+        long,
+        conflicts_with_all = ["hash_documents", "shape", "summary_per_doc", "group_by_depth"]
+    )]
+    distinct_values: bool,
+
+    /// Instead of the values, output how many leaves are left after the options that drop values,
+    /// like `--pick`, `--only-type` or `--forbid`, once the whole input has been read. Close
+    /// markers don't count.
+    #[arg(
+        long,
+        conflicts_with_all = [
+            "hash_documents",
+            "shape",
+            "summary_per_doc",
+            "group_by_depth",
+            "distinct_values",
+        ]
+    )]
+    count_matches: bool,
+
+    /// Instead of the values, validate each document against the JSON Schema in this file, and
+    /// output `[N, true]` for the N-th document if it's valid, or `[N, false, ERRORS]` otherwise,
+    /// where ERRORS has a `[PATH, "MESSAGE"]` for each value that fails validation. Fails once the
+    /// whole input has been read if any document is invalid.
+    ///
+    /// Each document is reassembled in memory to validate it, one at a time.
+    #[arg(
+        long,
+        value_name = "PATH",
+        conflicts_with_all = [
+            "hash_documents",
+            "shape",
+            "summary_per_doc",
+            "group_by_depth",
+            "distinct_values",
+            "count_matches",
+        ]
+    )]
+    schema: Option<PathBuf>,
+
+    /// Output the byte span of each leaf in the input after its value, as `[PATH,VALUE,START,END]`
+    /// where START is the offset of its first byte and END the offset right after its last one.
+    /// Close markers have no span. Only for JSON input from stdin.
+    ///
+    /// Spans are approximate at container boundaries: a non-empty array or object only has the
+    /// spans of its leaves, which leave out its brackets. An error stops the output even with
+    /// `--skip-errors`.
+    #[arg(
+        long,
+        conflicts_with_all = [
+            "files",
+            "mmap",
+            "skip_errors",
+            "take_while_prefix",
+            "pick",
+            "rename",
+            "only_type",
+            "head_structure",
+            "strip_prefix_depth",
+            "tee_reconstruct",
+            "hash_documents",
+            "shape",
+            "summary_per_doc",
+            "group_by_depth",
+            "distinct_values",
+            "count_matches",
+            "schema",
+            "every_nth",
+            "reservoir_sample",
+            "limit",
+        ]
+    )]
+    with_spans: bool,
+
+    /// What to write after each output value.
+    #[arg(long, value_enum, default_value_t = LineTerminator::Newline)]
+    line_terminator: LineTerminator,
+
+    /// Only write the line terminator between output values, not after the last one.
+    #[arg(long)]
+    no_trailing_newline: bool,
+
+    /// Pretty-print each output value over multiple lines, like `jq --stream` without `-c`.
+    #[arg(long)]
+    pretty: bool,
+
+    /// Number of spaces to indent with when pretty-printing. 0 means compact output.
+    #[arg(long, value_name = "N", default_value_t = 2, requires = "pretty")]
+    indent: usize,
+
+    /// Indent with tabs instead of spaces when pretty-printing.
+    #[arg(long, requires = "pretty", conflicts_with = "indent")]
+    tab: bool,
+
+    /// Print the input, input format, output format and form of the paths that would be used, and
+    /// the options that differ from their defaults, to stderr, and exit without reading any input.
+    ///
+    /// `--format auto` is reported as such, since the format can only be detected from the input.
+    #[arg(long)]
+    dry_run: bool,
+
+    /// Log each change to the path and each value emitted while parsing to stderr, e.g. `TRACE:
+    /// push [Map("a"), Array(0)]`, for debugging how a format is streamed. Slow, and very verbose.
+    #[arg(long)]
+    trace: bool,
+
+    /// Write the output to this file instead of stdout.
+    ///
+    /// The output is written to a temporary file in the same directory, which is renamed to PATH
+    /// only once the whole input has been processed. If any error is reported, PATH is left
+    /// untouched.
+    #[arg(short, long, value_name = "PATH")]
+    output_file: Option<PathBuf>,
+
+    /// Also write the documents reassembled from the output stream to PATH, one JSON document per
+    /// line, as `jq -c fromstream(inputs)` would.
+    ///
+    /// Only the document being reassembled is kept in memory, so a huge document costs as much as
+    /// reassembling it would, while a stream of small ones stays cheap.
+    #[arg(long, value_name = "PATH")]
+    tee_reconstruct: Option<PathBuf>,
+
+    /// Only process what has been appended to the input since the last run with the same PATH,
+    /// e.g. to ingest a growing NDJSON log incrementally.
+    ///
+    /// PATH records the number of documents and bytes of the input processed so far, as
+    /// `{"documents":N,"bytes":B}`, and the next run skips the first B bytes without parsing
+    /// them. This assumes that the input only ever grows at the end, and that documents are
+    /// simply concatenated, so that the rest of the input parses on its own. Document indices,
+    /// e.g. of `--with-doc-index`, start over at 0 after the skipped documents. PATH is written
+    /// atomically once the whole input has been processed, and left untouched if any error is
+    /// reported, e.g. for a document that's still being written at the end of the input.
+    #[arg(
+        long,
+        value_name = "PATH",
+        conflicts_with_all = [
+            "files",
+            "mmap",
+            "decode",
+            "with_spans",
+            "take_while_prefix",
+            "max_documents",
+            "documents",
+            "limit",
+        ]
+    )]
+    state_file: Option<PathBuf>,
+
+    /// Periodically report the bytes read, values emitted and elapsed time to stderr.
+    ///
+    /// `auto`, the default when the flag is given without a value, only reports if stderr is a
+    /// terminal.
+    #[arg(
+        long,
+        value_enum,
+        value_name = "WHEN",
+        num_args = 0..=1,
+        default_missing_value = "auto"
+    )]
+    progress: Option<ProgressWhen>,
+
+    /// Let the parser get up to N values ahead of the output, instead of 1, e.g. to keep parsing
+    /// while the output is blocked for a moment.
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u64).range(1..))]
+    buffer: Option<u64>,
+
+    /// Fail if no input arrives for this many seconds, e.g. because the program writing to the
+    /// pipe hung.
+    #[arg(long, value_name = "SECONDS", value_parser = parse_seconds)]
+    read_timeout: Option<Duration>,
+
+    /// Decode the input from ENCODING before parsing it, e.g. `--decode base64` for documents
+    /// that were base64-encoded one per line. Each line is decoded on its own and followed by a
+    /// newline, and blank lines are skipped. Fails at the first line that can't be decoded.
+    #[arg(
+        long,
+        value_enum,
+        value_name = "ENCODING",
+        conflicts_with_all = ["mmap", "files", "with_spans"]
+    )]
+    decode: Option<Encoding>,
+
+    /// With `--decode`, decode the lines of the input as one, without newlines in between, e.g.
+    /// for one document wrapped over many lines like `base64` does, or for binary formats.
+    #[arg(long, requires = "decode")]
+    decode_whole: bool,
+
+    /// Output numbers as strings, e.g. `"42"` instead of `42`.
+    #[arg(long)]
+    numbers_as_strings: bool,
+
+    /// Output integers of more than THRESHOLD in magnitude as strings, e.g. `"9007199254740993"`,
+    /// so that they survive JSON parsers that read numbers as `f64`, like JavaScript's.
+    ///
+    /// THRESHOLD defaults to 2^53 = 9007199254740992, the largest magnitude up to which every
+    /// integer is exactly representable as an `f64`. Integers of up to 64 bits are output with all
+    /// their digits. Numbers with a fractional part are left as they are, as well as ones like
+    /// `1e300` that are too large to tell whether they were integers.
+    #[arg(
+        long,
+        value_name = "THRESHOLD",
+        num_args = 0..=1,
+        default_missing_value = "9007199254740992"
+    )]
+    large_ints_as_strings: Option<u64>,
+
+    /// Format numbers exactly like jq 1.6, e.g. `1e+300` rather than 1 followed by 300 zeros, for
+    /// byte-for-byte comparisons with jq's output.
+    #[arg(long)]
+    deterministic_floats: bool,
+
+    /// Escape non-ASCII characters in map keys and strings as `\uXXXX`, like `jq -a`.
+    #[arg(short, long)]
+    ascii_output: bool,
+
+    /// Output the path of a top-level scalar or empty array or object as `[TOKEN]` rather than
+    /// `[]`, e.g. `[["$"],1]` with `--root-token '$'`. Only changes how the output is written.
+    #[arg(long, value_name = "TOKEN")]
+    root_token: Option<String>,
+
+    /// Output the type of each leaf after its value, one of `null`, `boolean`, `number`, `string`,
+    /// `array` and `object`, e.g. `[["a"],1,"number"]`. Close markers get the type of the container
+    /// they close after their path, e.g. `[["a",0],"array"]`, so that they can still be told apart
+    /// from leaves by their length.
+    #[arg(
+        long,
+        conflicts_with_all = [
+            "jq_compat",
+            "hash_documents",
+            "shape",
+            "summary_per_doc",
+            "group_by_depth",
+            "distinct_values",
+            "schema",
+        ]
+    )]
+    with_type: bool,
+
+    /// Output the number of elements or entries of the container each close marker closes after
+    /// its path, as an object so that it can't be mistaken for a leaf value, e.g.
+    /// `[["a",2],{"count":3}]` for an array of 3 elements, before the type of `--with-type`.
+    #[arg(
+        long,
+        conflicts_with_all = [
+            "jq_compat",
+            "hash_documents",
+            "shape",
+            "summary_per_doc",
+            "group_by_depth",
+            "distinct_values",
+            "count_matches",
+            "schema",
+        ]
+    )]
+    close_with_count: bool,
+
+    /// Write exactly what `jq -c --stream .` of jq 1.6 writes for the same input. Same as
+    /// `--deterministic-floats`, which is the only difference from the default output, but also
+    /// rejects the options that would change the output format.
+    ///
+    /// The exception is numbers beyond the range of a double, e.g. `1e400`: jq takes them as the
+    /// largest finite number, but they're an error here.
+    #[arg(
+        long,
+        conflicts_with_all = ["pretty", "numbers_as_strings", "large_ints_as_strings", "root_token"]
+    )]
+    jq_compat: bool,
+
+    /// Cut leaf values to N characters as written, followed by `…` and their full length, e.g.
+    /// `[["a"],"aaaa… (10000 chars)]`. Paths are never cut.
+    ///
+    /// This is only for reading the output, e.g. in `less`: the cut values aren't valid JSON, so
+    /// the output can't be turned back into the documents with jq's `fromstream`. For the same
+    /// reason, it can't be combined with `--jq-compat`.
+    #[arg(long, value_name = "N", conflicts_with = "jq_compat")]
+    max_line_length: Option<usize>,
 }
-impl PathValue {
-    fn print(&self) {
-        print!("[");
-        print!("[");
-        for (i, v) in self.path.iter().enumerate() {
-            if i != 0 {
-                print!(",");
-            }
-            match v {
-                Index::Array(i) => print!("{i}"),
-                Index::Map(s) => print!("{s:?}"),
-            }
-        }
-        print!("]");
-        if let Some(value) = &self.value {
-            print!(",");
-            match value {
-                PrimitiveValue::Null => print!("null"),
-                PrimitiveValue::Boolean(v) => print!("{v}"),
-                PrimitiveValue::Number(v) => print!("{v}"),
-                PrimitiveValue::String(v) => print!("{v:?}"),
-                PrimitiveValue::EmptyArray => print!("[]"),
-                PrimitiveValue::EmptyObject => print!("{{}}"),
-            }
-        }
-        print!("]");
-        println!();
-    }
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+enum OutputFormat {
+    /// The `[path, leaf]` values of `jq --stream`, one per line.
+    Stream,
+    /// The same values as `stream`, but as the elements of a single JSON array, for tools that
+    /// expect one JSON document. An empty stream is `[]`.
+    JsonArray,
+    /// Each value of `stream` as an object with named fields on its own line, e.g.
+    /// `{"path":["a",0],"value":1}`, and close markers as `{"path":["a",0],"close":true}`, for
+    /// tools that prefer named fields to positions. `--with-type` and `--with-spans` add `type`,
+    /// and `start` and `end` fields.
+    JsonlObjects,
+    /// Each document reassembled from the stream and written as indented JSON, one after another,
+    /// e.g. to pretty-print YAML as JSON. Indented by 2 spaces, or as given by `--indent` or
+    /// `--tab`.
+    PrettyJson,
+    /// Each document reassembled from the stream and written as YAML, starting with `---`.
+    Yaml,
+    /// Each document reassembled from the stream and written in the canonical form of RFC 8785
+    /// (JCS) on one line, e.g. to sign or hash it: keys are sorted and numbers are written like
+    /// JavaScript does, so the same content is always written the same.
+    CanonicalJson,
+    /// Only the leaf values, as they are rather than as JSON, each followed by a NUL byte, e.g.
+    /// for `xargs -0`: strings are written without quotes or escapes, binary data as base64, and
+    /// other leaves as in `stream`. Close markers, empty arrays and objects, and the arrays of
+    /// `--collapse-arrays` are left out.
+    ///
+    /// This is lossy: paths and types are gone, so e.g. the string `"1"` and the number `1` are
+    /// both `1`. A string that contains a NUL byte is an error instead of being split in two.
+    NulValues,
+    /// The leaves as the rows of a Parquet table with the columns `path`, `value` and `type`, e.g.
+    /// `["a",0]`, `1` and `number`, usually with `--output-file`. Strings are written in `value`
+    /// as they are, binary data as base64, and other leaves as in `stream`. Close markers are left
+    /// out.
+    ///
+    /// Rows are written in row groups of 65536, so only that many are held in memory at a time,
+    /// but the table can't be read before the footer is written once the whole input has been
+    /// read.
+    Parquet,
 }
 
-struct StreamState<'a> {
-    sender: SyncSender<Result<PathValue>>,
-    path: &'a mut Path,
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+enum ProgressWhen {
+    Auto,
+    Always,
+    Never,
 }
 
-impl<'a> StreamState<'a> {
-    fn emit_value(&mut self, value: PrimitiveValue) {
-        self.sender
-            .send(Ok(PathValue {
-                path: self.path.clone(),
-                value: Some(value),
-            }))
-            .ok(); // Discarding err since this indicates that the recever has already been dropped so they should already know what to do.
-    }
+/// Report progress every this many values, or every [`PROGRESS_INTERVAL`], whichever comes first.
+const PROGRESS_EVERY_VALUES: u64 = 100_000;
+const PROGRESS_INTERVAL: Duration = Duration::from_secs(1);
 
-    fn emit_close(&self) {
-        self.sender
-            .send(Ok(PathValue {
-                path: self.path.clone(),
-                value: None,
-            }))
-            .ok(); // Discarding err since this indicates that the recever has already been dropped so they should already know what to do.
+fn parse_seconds(s: &str) -> Result<Duration, String> {
+    s.parse::<f64>()
+        .ok()
+        .and_then(|secs| Duration::try_from_secs_f64(secs).ok())
+        .filter(|duration| !duration.is_zero())
+        .ok_or_else(|| format!("{s:?} isn't a positive number of seconds"))
+}
+
+fn parse_frame_tag(s: &str) -> Result<(u8, Format), String> {
+    let (tag, format) = s
+        .split_once('=')
+        .ok_or_else(|| "expected `TAG=FORMAT`, e.g. `j=json`".to_string())?;
+    let tag = match tag.as_bytes() {
+        &[c] if c.is_ascii() => c,
+        _ => tag
+            .strip_prefix("0x")
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+            .ok_or_else(|| format!("{tag:?} isn't an ASCII character or a byte like `0x01`"))?,
+    };
+    match Format::from_str(format, false)? {
+        Format::Auto | Format::Framed => Err(format!("{format:?} can't be the format of a frame")),
+        format => Ok((tag, format)),
     }
 }
 
-impl<'de, 'a> serde::de::Visitor<'de> for &mut StreamState<'a> {
-    type Value = ();
+/// Parses a Rust-style range of `usize`.
+fn parse_range(s: &str) -> Result<Range<usize>, String> {
+    let (start, end) = s
+        .split_once("..")
+        .ok_or_else(|| "expected a range like `A..B`".to_string())?;
+    let parse = |s: &str| s.parse::<usize>().map_err(|e| format!("{s:?}: {e}"));
+    let start = if start.is_empty() { 0 } else { parse(start)? };
+    let end = match end.strip_prefix('=') {
+        Some(end) => parse(end)?.saturating_add(1),
+        None if end.is_empty() => usize::MAX,
+        None => parse(end)?,
+    };
+    Ok(start..end)
+}
 
-    fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
-        write!(
-            formatter,
-            "null, boolean, number, string, array, or map keyed with string"
-        )
-    }
+/// What gets printed.
+enum Item {
+    PathValue(PathValue),
+    Value(serde_json::Value),
+    Leaf(PrimitiveValue),
+    /// A leaf to write as it is rather than as JSON.
+    RawLeaf(PrimitiveValue),
+    Line(String),
+    /// A whole document to write as YAML.
+    Yaml(serde_json::Value),
+    /// A value with its byte span in the input, if it's a leaf.
+    Spanned(PathValue, Option<Range<usize>>),
+}
 
-    fn visit_bool<E>(self, v: bool) -> Result<Self::Value, E>
-    where
-        E: serde::de::Error,
-    {
-        self.emit_value(PrimitiveValue::Boolean(v));
-        Ok(())
-    }
+/// Where the items go: the output of `--output parquet` isn't made of lines.
+enum Writer<W: Write + Send> {
+    Lines(Printer<W>),
+    Table(Box<ParquetWriter<W>>),
+}
 
-    fn visit_i64<E>(self, v: i64) -> Result<Self::Value, E>
-    where
-        E: serde::de::Error,
-    {
-        self.emit_value(PrimitiveValue::Number(v as f64));
-        Ok(())
-    }
+enum Output {
+    Stdout(io::Stdout),
+    File(NamedTempFile),
+}
 
-    fn visit_u64<E>(self, v: u64) -> Result<Self::Value, E>
-    where
-        E: serde::de::Error,
-    {
-        self.emit_value(PrimitiveValue::Number(v as f64));
-        Ok(())
+impl Output {
+    fn new(path: Option<&std::path::Path>) -> Result<Self> {
+        let Some(path) = path else {
+            return Ok(Output::Stdout(io::stdout()));
+        };
+        let dir = match path.parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => dir,
+            _ => std::path::Path::new("."),
+        };
+        let file = NamedTempFile::new_in(dir)
+            .with_context(|| format!("Failed to create a temporary file in {}", dir.display()))?;
+        Ok(Output::File(file))
     }
 
-    fn visit_f64<E>(self, v: f64) -> Result<Self::Value, E>
-    where
-        E: serde::de::Error,
-    {
-        self.emit_value(PrimitiveValue::Number(v as f64));
-        Ok(())
+    fn inner(&mut self) -> &mut dyn Write {
+        match self {
+            Output::Stdout(out) => out,
+            Output::File(file) => file,
+        }
     }
+}
 
-    fn visit_str<E>(self, v: &str) -> Result<Self::Value, E>
-    where
-        E: serde::de::Error,
-    {
-        self.visit_string(v.into())
+impl Write for Output {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.inner().write(buf)
     }
 
-    fn visit_string<E>(self, v: String) -> Result<Self::Value, E>
-    where
-        E: serde::de::Error,
-    {
-        self.emit_value(PrimitiveValue::String(v));
-        Ok(())
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner().flush()
     }
+}
 
-    fn visit_none<E>(self) -> Result<Self::Value, E>
-    where
-        E: serde::de::Error,
-    {
-        self.emit_value(PrimitiveValue::Null);
-        Ok(())
-    }
+/// Writes the records of `--trace` to stderr.
+struct StderrLogger;
 
-    fn visit_unit<E>(self) -> Result<Self::Value, E>
-    where
-        E: serde::de::Error,
-    {
-        self.visit_none()
+impl log::Log for StderrLogger {
+    fn enabled(&self, metadata: &log::Metadata) -> bool {
+        // Only the records of this crate, not of the libraries it uses.
+        metadata.target().split("::").next() == Some("stream")
     }
 
-    fn visit_seq<A>(self, mut seq: A) -> Result<Self::Value, A::Error>
-    where
-        A: serde::de::SeqAccess<'de>,
-    {
-        let mut i = 0;
-        self.path.push(Index::Array(i));
-        while seq.next_element_seed(&mut *self)?.is_some() {
-            self.path.pop();
-            i += 1;
-            self.path.push(Index::Array(i));
+    fn log(&self, record: &log::Record) {
+        if self.enabled(record.metadata()) {
+            eprintln!("{}: {}", record.level(), record.args());
         }
-        self.path.pop();
-        if i == 0 {
-            self.emit_value(PrimitiveValue::EmptyArray);
-        } else {
-            i -= 1;
-            self.path.push(Index::Array(i));
-            self.emit_close();
-            self.path.pop();
-        }
-        Ok(())
     }
 
-    fn visit_map<A>(self, mut map: A) -> Result<Self::Value, A::Error>
-    where
-        A: serde::de::MapAccess<'de>,
-    {
-        struct Str;
-        impl<'de> serde::de::DeserializeSeed<'de> for Str {
-            type Value = String;
-
-            fn deserialize<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
-            where
-                D: serde::Deserializer<'de>,
-            {
-                struct V;
-                impl<'de> serde::de::Visitor<'de> for V {
-                    type Value = String;
-
-                    fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
-                        write!(formatter, "string as the key of a map")
-                    }
+    fn flush(&self) {}
+}
 
-                    fn visit_str<E>(self, v: &str) -> Result<Self::Value, E>
-                    where
-                        E: serde::de::Error,
-                    {
-                        Ok(v.into())
-                    }
+/// How much of the input has been processed by the previous runs with `--state-file`.
+struct State {
+    documents: u64,
+    bytes: u64,
+}
 
-                    fn visit_string<E>(self, v: String) -> Result<Self::Value, E>
-                    where
-                        E: serde::de::Error,
-                    {
-                        Ok(v)
-                    }
-                }
-                deserializer.deserialize_any(V)
+impl State {
+    /// The state in the file at `path`, or nothing processed yet if there's no such file.
+    fn read(path: &std::path::Path) -> Result<Self> {
+        let file = match File::open(path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                return Ok(State {
+                    documents: 0,
+                    bytes: 0,
+                })
             }
+            Err(e) => return Err(e).with_context(|| format!("Failed to open {}", path.display())),
+        };
+        let state: serde_json::Value = serde_json::from_reader(BufReader::new(file))
+            .with_context(|| format!("Failed to parse {}", path.display()))?;
+        match (state["documents"].as_u64(), state["bytes"].as_u64()) {
+            (Some(documents), Some(bytes)) => Ok(State { documents, bytes }),
+            _ => bail!(
+                "{} isn't a state file, which has the numbers of documents and bytes processed",
+                path.display()
+            ),
         }
-
-        let mut empty = true;
-        self.path.push(Index::Map("".into()));
-        while let Some(key) = map.next_key_seed(Str)? {
-            empty = false;
-            self.path.pop();
-            self.path.push(Index::Map(key));
-            map.next_value_seed(&mut *self)?;
-        }
-        if empty {
-            self.path.pop();
-            self.emit_value(PrimitiveValue::EmptyObject);
-        } else {
-            self.emit_close();
-            self.path.pop();
-        }
-        Ok(())
     }
-}
 
-impl<'de, 'a> serde::de::DeserializeSeed<'de> for &mut StreamState<'a> {
-    type Value = ();
-
-    fn deserialize<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
-    where
-        D: serde::Deserializer<'de>,
-    {
-        deserializer.deserialize_any(self)?;
+    /// Replaces the file at `path` with this state, through a temporary file so that it's never
+    /// left half-written.
+    fn write(&self, path: &std::path::Path) -> Result<()> {
+        let Output::File(mut file) = Output::new(Some(path))? else {
+            unreachable!("given a path");
+        };
+        let state = serde_json::json!({ "documents": self.documents, "bytes": self.bytes });
+        writeln!(file, "{state}")?;
+        file.as_file().sync_all()?;
+        file.persist(path)?;
         Ok(())
     }
 }
 
-trait MultiDocDeserializer<'de> {
-    type Error: std::error::Error;
-    type Iterator<T: serde::Deserialize<'de>>: Iterator<Item = Result<T, Self::Error>>;
-    fn into_multidoc_iter<T: serde::Deserialize<'de>>(self) -> Self::Iterator<T>;
+/// The FILEs to read instead of stdin, if any.
+struct Files {
+    paths: Vec<PathBuf>,
+    threads: usize,
+    order: MergeOrder,
 }
 
-impl<'de, R: serde_json::de::Read<'de>> MultiDocDeserializer<'de>
-    for serde_json::de::Deserializer<R>
-{
-    type Error = serde_json::Error;
-    type Iterator<T: serde::Deserialize<'de>> = serde_json::de::StreamDeserializer<'de, R, T>;
-
-    fn into_multidoc_iter<T: serde::Deserialize<'de>>(self) -> Self::Iterator<T> {
-        self.into_iter()
+impl Files {
+    fn stream<T: FromReader>(self, stdin: Box<dyn Read + Send>, options: Options) -> Stream {
+        // The process exits with the output complete, without waiting for the rest of the input,
+        // e.g. after `--take-while-prefix`.
+        let stream = if self.paths.is_empty() {
+            stream::<T, _>(stdin, options)
+        } else {
+            stream_files::<T>(self.paths, self.threads, self.order, options)
+        };
+        stream.detached()
     }
 }
 
-struct SerdeYamlMultiDocIter<'de, T> {
-    inner: serde_yaml::Deserializer<'de>,
-    _phantom: PhantomData<T>,
+/// Exits with a usage error if any of `conflicts` is given along with `arg` set to `value`.
+fn check_conflicts(arg: &str, value: impl ValueEnum, conflicts: Vec<(&str, bool)>) {
+    if let Some((conflict, _)) = conflicts.into_iter().find(|(_, given)| *given) {
+        let value = value.to_possible_value().expect("no value is skipped");
+        Cli::command()
+            .error(
+                ErrorKind::ArgumentConflict,
+                format!(
+                    "the argument '{arg} {}' cannot be used with '{conflict}'",
+                    value.get_name()
+                ),
+            )
+            .exit();
+    }
 }
-impl<'de, T: serde::Deserialize<'de>> Iterator for SerdeYamlMultiDocIter<'de, T> {
-    type Item = Result<T, serde_yaml::Error>;
 
-    fn next(&mut self) -> Option<Self::Item> {
-        self.inner.next().map(|de| T::deserialize(de))
+/// The command line arguments, with the words of `XQ_STREAM_OPTS` before them.
+fn args() -> Result<Vec<OsString>> {
+    let mut args: Vec<_> = std::env::args_os().collect();
+    if let Some(opts) = std::env::var_os("XQ_STREAM_OPTS") {
+        let opts = opts.to_str().context("XQ_STREAM_OPTS isn't valid UTF-8")?;
+        let words = shlex::split(opts).context("XQ_STREAM_OPTS has an unterminated quote")?;
+        args.splice(1..1, words.into_iter().map(OsString::from));
     }
+    Ok(args)
 }
-impl<'de> MultiDocDeserializer<'de> for serde_yaml::Deserializer<'de> {
-    type Error = serde_yaml::Error;
-    type Iterator<T: serde::Deserialize<'de>> = SerdeYamlMultiDocIter<'de, T>;
-
-    fn into_multidoc_iter<T: serde::Deserialize<'de>>(self) -> Self::Iterator<T> {
-        SerdeYamlMultiDocIter {
-            inner: self,
-            _phantom: PhantomData,
+
+/// The options in `matches` that differ from their defaults, as `--flag VALUE` words in the order
+/// the options are declared, e.g. to show them with `--dry-run`.
+fn active_options(matches: &ArgMatches) -> Vec<String> {
+    let mut words = vec![];
+    for arg in Cli::command().get_arguments() {
+        let id = arg.get_id().as_str();
+        let (Some(long), Some(occurrences)) = (arg.get_long(), matches.get_raw_occurrences(id))
+        else {
+            // Positional, or not given.
+            continue;
+        };
+        let given = matches!(
+            matches.value_source(id),
+            Some(ValueSource::CommandLine | ValueSource::EnvVariable)
+        );
+        let occurrences: Vec<Vec<_>> = occurrences.map(|values| values.collect()).collect();
+        let default: Vec<_> = arg
+            .get_default_values()
+            .iter()
+            .map(|v| v.as_os_str())
+            .collect();
+        if !given || long == "dry-run" || occurrences == [default] {
+            continue;
+        }
+        for values in occurrences {
+            words.push(format!("--{long}"));
+            if arg.get_action().takes_values() {
+                let values = values.into_iter().map(|v| v.to_string_lossy());
+                words.extend(values.map(|v| shlex::quote(&v).into_owned()));
+            }
         }
     }
+    words
 }
 
-trait FromReader {
-    type De<'de, R>: MultiDocDeserializer<'de>
-    where
-        R: 'de + std::io::Read;
-    fn from_reader<'de, R: 'de + std::io::Read>(read: R) -> Self::De<'de, R>;
-}
-struct Yaml;
-impl FromReader for Yaml {
-    type De<'de, R> = serde_yaml::Deserializer<'de> where R: 'de + std::io::Read;
-
-    fn from_reader<'de, R: 'de + std::io::Read>(read: R) -> Self::De<'de, R> {
-        serde_yaml::Deserializer::from_reader(read)
+fn main() -> Result<()> {
+    let matches = Cli::command().get_matches_from(args()?);
+    let cli = Cli::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
+    match run(cli, &matches) {
+        Err(e)
+            if e.downcast_ref::<io::Error>()
+                .is_some_and(|e| e.kind() == io::ErrorKind::BrokenPipe) =>
+        {
+            Ok(())
+        }
+        result => result,
     }
 }
-struct Json;
-impl FromReader for Json {
-    type De<'de, R> = serde_json::Deserializer<serde_json::de::IoRead<R>> where R: 'de + std::io::Read;
 
-    fn from_reader<'de, R: 'de + std::io::Read>(read: R) -> Self::De<'de, R> {
-        serde_json::Deserializer::from_reader(read)
+fn run(mut cli: Cli, matches: &ArgMatches) -> Result<()> {
+    if cli.trace {
+        log::set_logger(&StderrLogger).expect("no other logger is set");
+        log::set_max_level(log::LevelFilter::Trace);
     }
-}
-
-fn main_generic<T: FromReader>() -> impl Iterator<Item = Result<PathValue>> {
-    let (sender, receiver) = sync_channel(1);
-    std::thread::spawn(|| {
-        thread_local! {
-            static SENDER: RefCell<Option<SyncSender<Result<PathValue>>>> = RefCell::new(None);
+    if cli.jq_compat {
+        cli.deterministic_floats = true;
+    }
+    if cli.raw_input {
+        cli.format = Format::Raw;
+    }
+    // Conflicts that clap can't express, since they depend on the value of an argument.
+    let reassembled = matches!(
+        cli.output,
+        OutputFormat::PrettyJson | OutputFormat::Yaml | OutputFormat::CanonicalJson
+    );
+    let output_conflicts = match cli.output {
+        OutputFormat::Stream => vec![],
+        OutputFormat::JsonArray => vec![
+            ("--summary-per-doc", cli.summary_per_doc),
+            ("--group-by-depth", cli.group_by_depth),
+            ("--count-matches", cli.count_matches),
+            ("--max-line-length", cli.max_line_length.is_some()),
+        ],
+        OutputFormat::JsonlObjects => {
+            vec![("--pretty", cli.pretty), ("--jq-compat", cli.jq_compat)]
         }
-        SENDER.with(|snd| snd.borrow_mut().replace(sender));
-        struct Stream;
-        impl<'de> serde::Deserialize<'de> for Stream {
-            fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
-            where
-                D: serde::Deserializer<'de>,
-            {
-                let mut path = vec![];
-                let sender = SENDER.with(|snd| snd.borrow().as_ref().unwrap().clone());
-                let mut visitor = StreamState {
-                    sender,
-                    path: &mut path,
-                };
-                deserializer.deserialize_any(&mut visitor)?;
-                Ok(Self)
+        OutputFormat::NulValues | OutputFormat::Parquet => vec![
+            ("--hash-documents", cli.hash_documents),
+            ("--shape", cli.shape),
+            ("--summary-per-doc", cli.summary_per_doc),
+            ("--group-by-depth", cli.group_by_depth),
+            ("--distinct-values", cli.distinct_values),
+            ("--count-matches", cli.count_matches),
+            ("--schema", cli.schema.is_some()),
+            ("--with-spans", cli.with_spans),
+            ("--with-type", cli.with_type),
+            ("--close-with-count", cli.close_with_count),
+            ("--pretty", cli.pretty),
+            ("--jq-compat", cli.jq_compat),
+            ("--max-line-length", cli.max_line_length.is_some()),
+        ],
+        OutputFormat::PrettyJson | OutputFormat::Yaml | OutputFormat::CanonicalJson => vec![
+            ("--hash-documents", cli.hash_documents),
+            ("--shape", cli.shape),
+            ("--summary-per-doc", cli.summary_per_doc),
+            ("--group-by-depth", cli.group_by_depth),
+            ("--distinct-values", cli.distinct_values),
+            ("--count-matches", cli.count_matches),
+            ("--schema", cli.schema.is_some()),
+            ("--with-spans", cli.with_spans),
+            ("--with-type", cli.with_type),
+            ("--close-with-count", cli.close_with_count),
+            ("--head-structure", cli.head_structure),
+            ("--only-type", !cli.only_type.is_empty()),
+            ("--forbid", !cli.forbid.is_empty()),
+            ("--every-nth", cli.every_nth.is_some()),
+            ("--reservoir-sample", cli.reservoir_sample.is_some()),
+            ("--limit", cli.limit.is_some()),
+        ],
+    };
+    check_conflicts("--output", cli.output, output_conflicts);
+    // The format is only detected from stdin, and these only override the detected format.
+    let format_conflicts = match cli.format {
+        Format::Auto => vec![
+            ("[FILE]...", !cli.files.is_empty()),
+            ("--with-spans", cli.with_spans),
+        ],
+        Format::Json => vec![
+            ("--ndjson", cli.ndjson),
+            ("--single", cli.single),
+            ("--auto-max-bytes", cli.auto_max_bytes.is_some()),
+        ],
+        _ => vec![
+            ("--ndjson", cli.ndjson),
+            ("--single", cli.single),
+            ("--auto-max-bytes", cli.auto_max_bytes.is_some()),
+            ("--with-spans", cli.with_spans),
+        ],
+    };
+    check_conflicts("--format", cli.format, format_conflicts);
+    // These need the close markers to tell where documents end.
+    let close_markers_conflicts = match cli.close_markers {
+        CloseMarkers::Both => vec![],
+        _ => vec![
+            ("--hash-documents", cli.hash_documents),
+            ("--summary-per-doc", cli.summary_per_doc),
+            ("--schema", cli.schema.is_some()),
+            ("--output", reassembled),
+            ("--tee-reconstruct", cli.tee_reconstruct.is_some()),
+            ("--state-file", cli.state_file.is_some()),
+        ],
+    };
+    check_conflicts(
+        "--close-markers",
+        cli.close_markers,
+        close_markers_conflicts,
+    );
+    let compression = cli.mmap.as_deref().and_then(Compression::from_path);
+    if cli.dry_run {
+        match (&cli.mmap, compression) {
+            (Some(path), Some(compression)) => {
+                eprintln!("input: {} ({compression})", path.display())
+            }
+            (Some(path), None) if cli.format == Format::Json => {
+                eprintln!("input: {} (memory-mapped)", path.display())
+            }
+            (Some(path), None) => eprintln!("input: {}", path.display()),
+            (None, _) if cli.files.is_empty() => eprintln!("input: stdin"),
+            (None, _) => {
+                let files: Vec<_> = cli
+                    .files
+                    .iter()
+                    .map(|path| path.display().to_string())
+                    .collect();
+                eprintln!("input: {} ({} at a time)", files.join(", "), cli.threads)
             }
         }
-        let de = T::from_reader(std::io::stdin().lock());
-        for v in de.into_multidoc_iter::<Stream>() {
-            if let Err(e) = v {
-                SENDER
-                    .with(|snd| {
-                        snd.borrow_mut()
-                            .as_ref()
-                            .unwrap()
-                            .send(Err(anyhow!("Deserialization error: {e}")))
-                    })
-                    .ok();
-                break;
+        match cli.format {
+            Format::Auto => eprintln!("format: {} (detected from the input)", cli.format),
+            format => eprintln!("format: {format}"),
+        }
+        let output = cli.output.to_possible_value().expect("no value is skipped");
+        eprintln!("output: {}", output.get_name());
+        let mut paths = vec![match cli.index_object_keys {
+            true => r#"keys as {"key":KEY,"index":N}"#.to_string(),
+            false => r#"keys as "KEY""#.to_string(),
+        }];
+        paths.push(match &cli.root_token {
+            Some(token) => format!("the root as [{}]", serde_json::to_string(token)?),
+            None => "the root as []".to_string(),
+        });
+        if cli.with_doc_index || cli.slurp {
+            paths.push("prefixed with the index of the document".to_string());
+        }
+        if let Some(depth) = cli.strip_prefix_depth {
+            let indices = if depth == 1 { "index" } else { "indices" };
+            paths.push(format!("without their first {depth} {indices}"));
+        }
+        eprintln!("paths: {}", paths.join(", "));
+        let options = active_options(matches);
+        if !options.is_empty() {
+            eprintln!("options: {}", options.join(" "));
+        }
+        return Ok(());
+    }
+    let input: Box<dyn Read + Send> = match &cli.mmap {
+        Some(path) => Box::new(
+            File::open(path).with_context(|| format!("Failed to open {}", path.display()))?,
+        ),
+        None => Box::new(io::stdin()),
+    };
+    let mut input: Box<dyn Read + Send> = match cli.read_timeout {
+        Some(timeout) => Box::new(TimeoutReader::new(input, timeout)),
+        None => input,
+    };
+    let state = match &cli.state_file {
+        Some(path) => {
+            let state = State::read(path)?;
+            let skipped = io::copy(&mut input.by_ref().take(state.bytes), &mut io::sink())?;
+            if skipped < state.bytes {
+                bail!(
+                    "The input has {skipped} bytes, fewer than the {} bytes already processed \
+                     according to {}",
+                    state.bytes,
+                    path.display()
+                );
             }
+            Some(state)
         }
-    });
-    receiver.into_iter()
-}
-
-fn main() -> Result<()> {
-    for v in main_generic::<Json>() {
-        match v {
-            Ok(v) => v.print(),
-            Err(e) => eprintln!("{e}"),
+        None => None,
+    };
+    let input = CountingReader::new(input);
+    let bytes_read = input.counter();
+    let mut progress = match cli.progress {
+        Some(ProgressWhen::Always) => true,
+        Some(ProgressWhen::Auto) => io::stderr().is_terminal(),
+        Some(ProgressWhen::Never) | None => false,
+    }
+    .then(|| Progress::new(input.counter(), PROGRESS_EVERY_VALUES, PROGRESS_INTERVAL));
+    let input: Box<dyn Read + Send> = match compression {
+        Some(compression) => compression.decoder(input)?,
+        None => Box::new(input),
+    };
+    let input: Box<dyn Read + Send> = match cli.decode {
+        Some(encoding) => Box::new(DecodeReader::new(input, encoding, cli.decode_whole)),
+        None => Box::new(input),
+    };
+    let by_extension = cli.mmap.as_deref().and_then(detect_format_from_path);
+    let (format, input): (_, Box<dyn Read + Send>) =
+        if let (Format::Auto, Some(format)) = (cli.format, by_extension) {
+            let format = match format {
+                Format::Json | Format::Jsonl if cli.ndjson => Format::Jsonl,
+                Format::Json | Format::Jsonl if cli.single => Format::Json,
+                format => format,
+            };
+            eprintln!("Detected format: {format}");
+            (format, input)
+        } else if cli.format == Format::Auto {
+            let mut input = PeekReader::new(input);
+            let max = cli.auto_max_bytes.unwrap_or(u64::MAX);
+            let peeked = input.peek(max.saturating_add(1))?;
+            if peeked.len() as u64 > max {
+                bail!("The input is larger than {max} bytes, pass its --format");
+            }
+            let format = match try_detect_format(peeked) {
+                Some(Format::Json | Format::Jsonl) if cli.ndjson => Format::Jsonl,
+                Some(Format::Json | Format::Jsonl) if cli.single => Format::Json,
+                Some(format) => format,
+                None => bail!("The input is neither JSON, YAML nor TOML"),
+            };
+            eprintln!("Detected format: {format}");
+            (format, Box::new(input))
+        } else {
+            (cli.format, Box::new(input))
+        };
+    let options = Options::builder()
+        .merge_keys(cli.merge_keys && format == Format::Yaml)
+        .max_depth(cli.max_depth)
+        .max_keys_per_object(cli.max_keys_per_object)
+        .max_array_length(cli.max_array_length)
+        .drop(cli.drop)
+        .slurp(cli.slurp)
+        .skip_errors(cli.skip_errors)
+        .max_documents(cli.max_documents)
+        .documents(cli.documents)
+        .with_doc_index(cli.with_doc_index)
+        .normalize_nfc(cli.normalize_nfc)
+        .trim(cli.trim)
+        .infer_types(cli.infer_types)
+        .null_as_missing(cli.null_as_missing)
+        .close_markers(cli.close_markers)
+        .close_with_count(cli.close_with_count)
+        .buffer(cli.buffer.map(|n| n as usize))
+        .yaml_tags(cli.yaml_tags)
+        .yaml_scalars(cli.yaml_scalar_mode)
+        .require_container(cli.require_container)
+        .record_separator(cli.record_separator)
+        .frame_tags(cli.frame_tags)
+        .collapse_arrays(cli.collapse_arrays)
+        .index_object_keys(cli.index_object_keys)
+        .build();
+    let schema = match &cli.schema {
+        Some(path) => {
+            let file =
+                File::open(path).with_context(|| format!("Failed to open {}", path.display()))?;
+            let schema: serde_json::Value = serde_json::from_reader(BufReader::new(file))
+                .with_context(|| format!("Failed to parse {}", path.display()))?;
+            let schema = Schema::new(&schema)
+                .with_context(|| format!("Invalid JSON Schema in {}", path.display()))?;
+            Some(schema)
+        }
+        None => None,
+    };
+    let files = Files {
+        paths: cli.files,
+        threads: cli.threads as usize,
+        order: cli.merge_order,
+    };
+    // Spans are read along with the stream, which none of the filters below are used with.
+    let mut spans = None;
+    let mut stream: Box<dyn Iterator<Item = Result<PathValue, StreamError>>> = match format {
+        Format::Json if cli.with_spans => {
+            spans = Some(stream_with_spans(input, options));
+            Box::new(std::iter::empty())
+        }
+        Format::Json => match &cli.mmap {
+            // Auto-detected input has already been read from, and compressed input can only be
+            // read through its decoder.
+            Some(path) if cli.format == Format::Json && compression.is_none() => {
+                // SAFETY: Documented on `--mmap`, the file must not be modified while it's read.
+                let stream = unsafe { stream_mmap::<Json>(path, options) };
+                Box::new(
+                    stream
+                        .with_context(|| format!("Failed to map {}", path.display()))?
+                        .detached(),
+                )
+            }
+            _ => Box::new(files.stream::<Json>(input, options)),
+        },
+        Format::Jsonl => Box::new(files.stream::<Jsonl>(input, options)),
+        Format::Jsonc => Box::new(files.stream::<Jsonc>(input, options)),
+        Format::Yaml => Box::new(files.stream::<Yaml>(input, options)),
+        Format::Hocon => Box::new(files.stream::<Hocon>(input, options)),
+        Format::Toml => Box::new(files.stream::<Toml>(input, options)),
+        Format::Raw => Box::new(files.stream::<RawLines>(input, options)),
+        Format::Properties => Box::new(files.stream::<Properties>(input, options)),
+        Format::Avro => Box::new(files.stream::<Avro>(input, options)),
+        Format::Ron => Box::new(files.stream::<Ron>(input, options)),
+        Format::Smile => Box::new(files.stream::<Smile>(input, options)),
+        Format::MsgpackStream => Box::new(files.stream::<MsgpackStream>(input, options)),
+        Format::CborSeq => Box::new(files.stream::<CborSeq>(input, options)),
+        Format::Framed => Box::new(files.stream::<Framed>(input, options)),
+        Format::Kv => Box::new(files.stream::<Kv>(input, options)),
+        Format::Auto => unreachable!("detected above"),
+    };
+    let documents = Rc::new(Cell::new(0));
+    if state.is_some() {
+        // A document ends with a top-level leaf, or the close marker of a top-level container.
+        let depth = cli.slurp as usize + cli.with_doc_index as usize;
+        let documents = documents.clone();
+        stream = Box::new(stream.inspect(move |v| {
+            if let Ok(v) = v {
+                let len = if v.value.is_some() { depth } else { depth + 1 };
+                if v.path.len() == len {
+                    documents.set(documents.get() + 1);
+                }
+            }
+        }));
+    }
+    if let Some(prefix) = cli.take_while_prefix {
+        stream = Box::new(TakeWhilePrefix::new(stream, prefix));
+    }
+    if let Some(lines) = cli.context {
+        // Matched where `--pick` would be, which is fine for `--only-type` as it ignores paths.
+        let paths = std::mem::take(&mut cli.pick);
+        let mode = cli.pick_mode;
+        let types = std::mem::take(&mut cli.only_type);
+        stream = Box::new(WithContext::new(stream, lines, move |value: &PathValue| {
+            let typed = |value: &PrimitiveValue| types.iter().any(|t| t.matches(value));
+            (paths.is_empty() || mode.matches(&paths, value))
+                && (types.is_empty() || value.value.as_ref().is_some_and(typed))
+        }));
+    }
+    let forbidden = Rc::new(Cell::new(0));
+    if !cli.forbid.is_empty() {
+        let forbidden = forbidden.clone();
+        stream = Box::new(
+            Pick::new(stream, cli.forbid, PickMode::Subtree)
+                .filter(|v| !matches!(v, Ok(PathValue { value: None, .. })))
+                .inspect(move |v| {
+                    if v.is_ok() {
+                        forbidden.set(forbidden.get() + 1)
+                    }
+                }),
+        );
+    }
+    if !cli.pick.is_empty() {
+        stream = Box::new(Pick::new(stream, cli.pick, cli.pick_mode));
+    }
+    if !cli.rename.is_empty() {
+        stream = Box::new(Rename::new(stream, cli.rename));
+    }
+    if !cli.only_type.is_empty() {
+        stream = Box::new(OnlyTypes::new(stream, cli.only_type));
+    }
+    if let Some(n) = cli.every_nth {
+        stream = Box::new(EveryNth::new(stream, n as usize));
+    }
+    if let Some(k) = cli.reservoir_sample {
+        stream = Box::new(ReservoirSample::new(stream, k, cli.seed));
+    }
+    if cli.head_structure {
+        stream = Box::new(FirstSeen::new(stream));
+    }
+    if let Some(depth) = cli.strip_prefix_depth {
+        stream = Box::new(StripPrefix::new(stream, depth));
+    }
+    if let Some(limit) = cli.limit {
+        stream = Box::new(stream.take(limit));
+    }
+    if let Some(path) = &cli.tee_reconstruct {
+        let file =
+            File::create(path).with_context(|| format!("Failed to create {}", path.display()))?;
+        stream = Box::new(Tee::new(stream, BufWriter::new(file)));
+    }
+    let invalid = Rc::new(Cell::new(0));
+    let items: Box<dyn Iterator<Item = Result<Item, StreamError>>> = if let Some(spans) = spans {
+        Box::new(spans.map(|v| v.map(|(v, span)| Item::Spanned(v, span))))
+    } else if let Some(schema) = schema {
+        let mut documents = 0..;
+        let invalid = invalid.clone();
+        Box::new(into_values(stream).map(move |doc| {
+            let n = documents.next();
+            let errors = schema.validate(&doc?);
+            if errors.is_empty() {
+                return Ok(Item::Value(serde_json::json!([n, true])));
+            }
+            invalid.set(invalid.get() + 1);
+            let errors: Vec<_> = errors
+                .into_iter()
+                .map(|(path, message)| {
+                    let path: Vec<_> = path
+                        .into_iter()
+                        .map(|index| match index {
+                            Index::Array(i) => i.into(),
+                            Index::Map(key) => serde_json::Value::from(key),
+                            Index::OrderedKey { key, index } => {
+                                serde_json::json!({ "key": key, "index": index })
+                            }
+                        })
+                        .collect();
+                    serde_json::json!([path, message])
+                })
+                .collect();
+            Ok(Item::Value(serde_json::json!([n, false, errors])))
+        }))
+    } else if cli.hash_documents {
+        let mut documents = 0..;
+        Box::new(into_values(stream).map(move |doc| {
+            let hash = canonical_hash(&doc?);
+            Ok(Item::Value(serde_json::json!([documents.next(), hash])))
+        }))
+    } else if cli.shape {
+        Box::new(Shape::new(stream).map(|shape| {
+            let (path, types) = shape?;
+            let path: Vec<_> = path
+                .into_iter()
+                .map(|index| match index {
+                    ShapeIndex::Array => serde_json::json!([]),
+                    ShapeIndex::Map(key) => key.into(),
+                })
+                .collect();
+            Ok(Item::Value(serde_json::json!([path, types.to_string()])))
+        }))
+    } else if cli.output == OutputFormat::CanonicalJson {
+        Box::new(into_values(stream).map(|doc| {
+            let mut canonical = vec![];
+            write_canonical(&mut canonical, &doc?).expect("writing to a Vec doesn't fail");
+            Ok(Item::Line(
+                String::from_utf8(canonical).expect("JSON is UTF-8"),
+            ))
+        }))
+    } else if cli.output == OutputFormat::PrettyJson {
+        Box::new(into_values(stream).map(|doc| doc.map(Item::Value)))
+    } else if cli.output == OutputFormat::Yaml {
+        Box::new(into_values(stream).map(|doc| doc.map(Item::Yaml)))
+    } else if cli.output == OutputFormat::NulValues {
+        Box::new(stream.filter_map(|v| match v {
+            Ok(PathValue {
+                value:
+                    Some(
+                        PrimitiveValue::EmptyArray
+                        | PrimitiveValue::EmptyObject
+                        | PrimitiveValue::Scalars(_),
+                    )
+                    | None,
+                ..
+            }) => None,
+            Ok(PathValue {
+                value: Some(value), ..
+            }) => Some(Ok(Item::RawLeaf(value))),
+            Err(e) => Some(Err(e)),
+        }))
+    } else if cli.distinct_values {
+        Box::new(DistinctValues::new(stream).map(|value| value.map(Item::Leaf)))
+    } else if cli.group_by_depth {
+        Box::new(Depths::new(stream).map(|depth| {
+            let (depth, count) = depth?;
+            Ok(Item::Line(format!("depth {depth}: {count} values")))
+        }))
+    } else if cli.summary_per_doc {
+        Box::new(Summaries::new(stream).map(|summary| summary.map(Item::Line)))
+    } else if cli.count_matches {
+        let count = Rc::new(Cell::new(0));
+        let counted = count.clone();
+        Box::new(
+            stream
+                .filter_map(move |v| match v {
+                    Ok(PathValue { value: None, .. }) => None,
+                    Ok(_) => {
+                        counted.set(counted.get() + 1);
+                        None
+                    }
+                    Err(e) => Some(Err(e)),
+                })
+                .chain(std::iter::once_with(move || {
+                    Ok(Item::Line(count.get().to_string()))
+                })),
+        )
+    } else {
+        Box::new(stream.map(|v| v.map(Item::PathValue)))
+    };
+    let render_options = RenderOptions {
+        line_terminator: match cli.output {
+            OutputFormat::NulValues => LineTerminator::Nul,
+            _ => cli.line_terminator,
+        },
+        json_array: cli.output == OutputFormat::JsonArray,
+        no_trailing_terminator: cli.no_trailing_newline,
+        indent: match (cli.pretty, cli.tab) {
+            (false, _) if cli.output == OutputFormat::PrettyJson => Some(Indent::Spaces(2)),
+            (false, _) => None,
+            (true, false) => Some(Indent::Spaces(cli.indent)),
+            (true, true) => Some(Indent::Tab),
+        },
+        numbers_as_strings: cli.numbers_as_strings,
+        jq_floats: cli.deterministic_floats,
+        ascii: cli.ascii_output,
+        max_value_length: cli.max_line_length,
+        large_ints_as_strings: cli.large_ints_as_strings,
+        root_token: cli.root_token,
+        with_type: cli.with_type,
+        objects: cli.output == OutputFormat::JsonlObjects,
+    };
+    let output = BufWriter::new(Output::new(cli.output_file.as_deref())?);
+    let mut writer = match cli.output {
+        OutputFormat::Parquet => {
+            Writer::Table(Box::new(ParquetWriter::new(output, render_options)?))
         }
+        _ => Writer::Lines(Printer::new(output, render_options)),
+    };
+    let mut failed = false;
+    for item in items {
+        match item {
+            Ok(item) => {
+                match (&mut writer, item) {
+                    (Writer::Table(table), Item::PathValue(v)) => table.write(&v)?,
+                    (Writer::Table(_), _) => unreachable!("only values are written to a table"),
+                    (Writer::Lines(printer), item) => match item {
+                        Item::PathValue(v) => printer.print(&v)?,
+                        Item::Spanned(v, span) => printer.print_with_span(&v, span.as_ref())?,
+                        Item::Value(v) => printer.print_value(&v)?,
+                        Item::Leaf(v) => printer.print_leaf(&v)?,
+                        Item::RawLeaf(v) => printer.print_raw_leaf(&v)?,
+                        Item::Line(line) => printer.print_line(&line)?,
+                        Item::Yaml(v) => {
+                            // serde_yaml writes a single document, so separate them explicitly.
+                            let yaml = serde_yaml::to_string(&v)?;
+                            let yaml = yaml.trim_end_matches('\n');
+                            printer.print_line(&format!("---\n{yaml}"))?
+                        }
+                    },
+                }
+                if let Some(progress) = &mut progress {
+                    progress.value();
+                }
+            }
+            Err(e) => {
+                if let Some(progress) = &mut progress {
+                    progress.suspend();
+                }
+                eprintln!("{e}");
+                failed = true;
+            }
+        }
+    }
+    if let Some(progress) = progress {
+        progress.finish();
+    }
+    let output = match writer {
+        Writer::Lines(printer) => printer.finish()?,
+        Writer::Table(table) => table.finish()?,
+    };
+    let output = output.into_inner().map_err(|e| e.into_error())?;
+    if let (Output::File(file), Some(path)) = (output, cli.output_file) {
+        if failed {
+            // Dropping the temporary file removes it.
+            bail!(
+                "Not writing to {} because of the errors above",
+                path.display()
+            );
+        }
+        file.as_file().sync_all()?;
+        file.persist(&path)
+            .with_context(|| format!("Failed to write to {}", path.display()))?;
+    }
+    if let (Some(state), Some(path)) = (state, cli.state_file) {
+        if failed {
+            bail!(
+                "Not updating {} because of the errors above",
+                path.display()
+            );
+        }
+        let state = State {
+            documents: state.documents + documents.get(),
+            bytes: state.bytes + bytes_read.load(Ordering::Relaxed),
+        };
+        state
+            .write(&path)
+            .with_context(|| format!("Failed to write to {}", path.display()))?;
+    }
+    if invalid.get() != 0 {
+        bail!("{} documents don't match the schema", invalid.get());
+    }
+    if forbidden.get() != 0 {
+        bail!("{} values are under forbidden paths", forbidden.get());
     }
     Ok(())
 }
@@ -0,0 +1,79 @@
+//! The shortest round-tripping digits of numbers, and their layout, shared by the formatters that
+//! write them like jq and like ECMAScript.
+
+use std::{
+    io::{self, Write},
+    ops::Range,
+};
+
+/// The shortest digits of `v`, which must be finite and positive, that round-trip, and the exponent
+/// of the first of them, e.g. `("1234", -5)` for `1.234e-5`.
+///
+/// Of two such digits equally close to `v`, the even one is taken like jq and ECMAScript do, e.g.
+/// `1424953923781206.2` for 1424953923781206.25, whereas Rust's `{:e}` takes the larger one.
+pub(crate) fn shortest_digits(v: f64) -> (String, i32) {
+    let formatted = format!("{v:e}");
+    let (mantissa, exponent) = formatted.split_once('e').unwrap();
+    let digits = mantissa.replace('.', "");
+    let exponent = exponent.parse().unwrap();
+    match even_tie(&digits, exponent, v) {
+        Some(even) => (even, exponent),
+        None => (digits, exponent),
+    }
+}
+
+/// Returns the even digits if `digits`, with the decimal point after `exponent + 1` of them, is the
+/// larger one of a tie.
+fn even_tie(digits: &str, exponent: i32, v: f64) -> Option<String> {
+    // Both of the tied digits only round-trip when there are as many as a double can tell apart,
+    // so fewer digits are never a tie, and the exact expansion below isn't needed for them.
+    if digits.len() < 15 {
+        return None;
+    }
+    let (rest, last) = digits.split_at(digits.len() - 1);
+    let last: u8 = last.parse().unwrap();
+    if last.is_multiple_of(2) {
+        return None;
+    }
+    let even = format!("{rest}{}", last - 1);
+    // All the digits of `v`, of which there are at most 767.
+    let exact = format!("{v:.767e}");
+    let exact_digits = exact.split_once('e').unwrap().0.replace('.', "");
+    let tie = exact_digits.trim_end_matches('0') == format!("{even}5");
+    (tie && format!("0.{even}e{}", exponent + 1).parse() == Ok(v)).then_some(even)
+}
+
+/// Writes `sign` and `digits`, with the decimal point after `exponent + 1` of them, e.g. from
+/// [`shortest_digits`]. That's in exponent notation with at least `exponent_width` digits of
+/// exponent, unless the decimal point is in `fixed`, counted from the start of `digits`. Then the
+/// zeros between `digits` and the decimal point are written out, e.g. `0.00001234` or `1200`.
+pub(crate) fn write_digits<W: Write>(
+    out: &mut W,
+    sign: &str,
+    digits: &str,
+    exponent: i32,
+    fixed: Range<i32>,
+    exponent_width: usize,
+) -> io::Result<()> {
+    // Position of the decimal point relative to the start of `digits`.
+    let point = exponent + 1;
+    if !fixed.contains(&point) {
+        let (first, rest) = digits.split_at(1);
+        let dot = if rest.is_empty() { "" } else { "." };
+        let exponent_sign = if exponent < 0 { '-' } else { '+' };
+        write!(
+            out,
+            "{sign}{first}{dot}{rest}e{exponent_sign}{:0exponent_width$}",
+            exponent.abs()
+        )
+    } else if point <= 0 {
+        let zeros = -point as usize;
+        write!(out, "{sign}0.{:0>zeros$}{digits}", "")
+    } else if point as usize >= digits.len() {
+        let zeros = point as usize - digits.len();
+        write!(out, "{sign}{digits}{:0>zeros$}", "")
+    } else {
+        let (integral, fractional) = digits.split_at(point as usize);
+        write!(out, "{sign}{integral}.{fractional}")
+    }
+}
@@ -0,0 +1,132 @@
+//! jq-like textual representation of [`Path`]s, e.g. `.items[0]."key with space"`.
+
+use crate::{Index, Path};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ParsePathError {
+    /// Byte offset in the input where parsing failed.
+    pub position: usize,
+    pub reason: &'static str,
+}
+
+impl std::fmt::Display for ParsePathError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} at position {}", self.reason, self.position)
+    }
+}
+
+impl std::error::Error for ParsePathError {}
+
+/// Parses a path written in jq syntax.
+///
+/// `.` is the root path, `.foo` or `."foo"` or `.["foo"]` indexes a map, and `.[0]` or `[0]`
+/// indexes an array. The segments can be chained like `.foo[0].bar`.
+pub fn parse_path(s: &str) -> Result<Path, ParsePathError> {
+    let bytes = s.as_bytes();
+    let err = |position, reason| Err(ParsePathError { position, reason });
+    if s == "." {
+        return Ok(vec![]);
+    }
+    if s.is_empty() {
+        return err(0, "empty path");
+    }
+    let mut path = vec![];
+    let mut pos = 0;
+    while pos < bytes.len() {
+        match bytes[pos] {
+            b'.' if bytes.get(pos + 1) == Some(&b'[') => pos += 1,
+            b'.' if bytes.get(pos + 1) == Some(&b'"') => {
+                let (key, end) = parse_string(s, pos + 1)?;
+                path.push(Index::Map(key));
+                pos = end;
+            }
+            b'.' => {
+                let start = pos + 1;
+                let end = bytes[start..]
+                    .iter()
+                    .position(|&c| !(c.is_ascii_alphanumeric() || c == b'_'))
+                    .map_or(bytes.len(), |len| start + len);
+                if start == end || bytes[start].is_ascii_digit() {
+                    return err(start, "expected an identifier");
+                }
+                path.push(Index::Map(s[start..end].into()));
+                pos = end;
+            }
+            b'[' => {
+                let start = pos + 1;
+                let end = if bytes.get(start) == Some(&b'"') {
+                    let (key, end) = parse_string(s, start)?;
+                    path.push(Index::Map(key));
+                    end
+                } else {
+                    let end = bytes[start..]
+                        .iter()
+                        .position(|c| !c.is_ascii_digit())
+                        .map_or(bytes.len(), |len| start + len);
+                    match s[start..end].parse() {
+                        Ok(i) => path.push(Index::Array(i)),
+                        Err(_) => return err(start, "expected an array index or a string"),
+                    }
+                    end
+                };
+                if bytes.get(end) != Some(&b']') {
+                    return err(end, "expected `]`");
+                }
+                pos = end + 1;
+            }
+            _ => return err(pos, "expected `.` or `[`"),
+        }
+    }
+    Ok(path)
+}
+
+/// Parses a JSON string literal starting at `start`, returning it and the position right after it.
+fn parse_string(s: &str, start: usize) -> Result<(String, usize), ParsePathError> {
+    let bytes = s.as_bytes();
+    let mut pos = start + 1;
+    while pos < bytes.len() {
+        match bytes[pos] {
+            b'\\' => pos += 2,
+            b'"' => {
+                return serde_json::from_str(&s[start..=pos])
+                    .map(|key| (key, pos + 1))
+                    .map_err(|_| ParsePathError {
+                        position: start,
+                        reason: "invalid string literal",
+                    })
+            }
+            _ => pos += 1,
+        }
+    }
+    Err(ParsePathError {
+        position: start,
+        reason: "unterminated string literal",
+    })
+}
+
+/// Formats a path in the syntax accepted by [`parse_path`].
+pub fn format_path(path: &[Index]) -> String {
+    use std::fmt::Write;
+    if path.is_empty() {
+        return ".".into();
+    }
+    let mut ret = String::new();
+    for index in path {
+        match index {
+            Index::Array(i) => write!(ret, "[{i}]").unwrap(),
+            Index::Map(key) | Index::OrderedKey { key, .. } if is_identifier(key) => {
+                write!(ret, ".{key}").unwrap()
+            }
+            Index::Map(key) | Index::OrderedKey { key, .. } => {
+                write!(ret, ".{}", serde_json::to_string(key).unwrap()).unwrap()
+            }
+        }
+    }
+    ret
+}
+
+fn is_identifier(s: &str) -> bool {
+    let mut chars = s.chars();
+    matches!(chars.next(), Some(c) if c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
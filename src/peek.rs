@@ -0,0 +1,56 @@
+//! Looking at the start of input that can't be rewound, like stdin.
+
+use std::io::{self, Read};
+
+/// A [`Read`] adapter that can look ahead in the inner reader without losing what it looked at:
+/// the bytes read by [`PeekReader::peek`] are kept, and then read again before the rest of the
+/// inner reader. So the format of a pipe can be detected from its start, and the whole of it still
+/// parsed in that format afterwards.
+///
+/// The peeked bytes are held in memory until they've all been read, then dropped.
+pub struct PeekReader<R> {
+    inner: R,
+    peeked: Vec<u8>,
+    /// How many of the peeked bytes have been read.
+    pos: usize,
+}
+
+impl<R> PeekReader<R> {
+    pub fn new(inner: R) -> Self {
+        Self {
+            inner,
+            peeked: vec![],
+            pos: 0,
+        }
+    }
+}
+
+impl<R: Read> PeekReader<R> {
+    /// The next `len` bytes of the input, without reading them. Fewer only if the input ends
+    /// before that, so e.g. `peek(u64::MAX)` is the whole input.
+    pub fn peek(&mut self, len: u64) -> io::Result<&[u8]> {
+        let have = (self.peeked.len() - self.pos) as u64;
+        if have < len {
+            (&mut self.inner)
+                .take(len - have)
+                .read_to_end(&mut self.peeked)?;
+        }
+        let end = len.min(self.peeked.len() as u64 - self.pos as u64) as usize;
+        Ok(&self.peeked[self.pos..self.pos + end])
+    }
+}
+
+impl<R: Read> Read for PeekReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.pos == self.peeked.len() {
+            if !self.peeked.is_empty() {
+                self.peeked = vec![];
+                self.pos = 0;
+            }
+            return self.inner.read(buf);
+        }
+        let n = (&self.peeked[self.pos..]).read(buf)?;
+        self.pos += n;
+        Ok(n)
+    }
+}
@@ -0,0 +1,105 @@
+//! Periodic status reports for long runs.
+
+use std::{
+    io::{self, Read, Write},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+/// A [`Read`] adapter counting the bytes read through it. The count can be observed from another
+/// thread through [`CountingReader::counter`].
+pub struct CountingReader<R> {
+    inner: R,
+    count: Arc<AtomicU64>,
+}
+
+impl<R> CountingReader<R> {
+    pub fn new(inner: R) -> Self {
+        Self {
+            inner,
+            count: Default::default(),
+        }
+    }
+
+    pub fn counter(&self) -> Arc<AtomicU64> {
+        self.count.clone()
+    }
+}
+
+impl<R: Read> Read for CountingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.count.fetch_add(n as u64, Ordering::Relaxed);
+        Ok(n)
+    }
+}
+
+/// Writes a status line to stderr every `every_values` values or every `interval`, whichever comes
+/// first. Each report overwrites the previous one with `\r`.
+pub struct Progress {
+    bytes: Arc<AtomicU64>,
+    values: u64,
+    every_values: u64,
+    interval: Duration,
+    start: Instant,
+    last_report: Instant,
+    shown: bool,
+}
+
+impl Progress {
+    pub fn new(bytes: Arc<AtomicU64>, every_values: u64, interval: Duration) -> Self {
+        let now = Instant::now();
+        Self {
+            bytes,
+            values: 0,
+            every_values,
+            interval,
+            start: now,
+            last_report: now,
+            shown: false,
+        }
+    }
+
+    /// Counts one emitted value, reporting if it's time to.
+    pub fn value(&mut self) {
+        self.values += 1;
+        if self.values.is_multiple_of(self.every_values)
+            || self.last_report.elapsed() >= self.interval
+        {
+            self.report();
+        }
+    }
+
+    /// Ends the status line, so that other messages written to stderr start on a fresh line. The
+    /// next report starts a new status line.
+    pub fn suspend(&mut self) {
+        if self.shown {
+            eprintln!();
+            self.shown = false;
+        }
+    }
+
+    /// Writes the final report and ends the status line.
+    pub fn finish(mut self) {
+        self.report();
+        self.suspend();
+    }
+
+    fn report(&mut self) {
+        self.last_report = Instant::now();
+        self.shown = true;
+        let mut stderr = io::stderr().lock();
+        // There's nothing useful to do if stderr is gone.
+        let _ = write!(
+            stderr,
+            "\r{} bytes read, {} values emitted, {:.1}s elapsed",
+            self.bytes.load(Ordering::Relaxed),
+            self.values,
+            self.start.elapsed().as_secs_f64(),
+        );
+        let _ = stderr.flush();
+    }
+}
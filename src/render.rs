@@ -0,0 +1,538 @@
+//! Writing [`PathValue`]s out in the format of `jq --stream -c`.
+
+use std::{
+    io::{self, Write},
+    ops::Range,
+};
+
+use crate::{
+    number::{shortest_digits, write_digits},
+    Index, PathValue, PrimitiveValue,
+};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum LineTerminator {
+    /// `\n`
+    #[default]
+    Newline,
+    /// `\0`, e.g. for `xargs -0`.
+    Nul,
+}
+
+impl LineTerminator {
+    fn as_bytes(self) -> &'static [u8] {
+        match self {
+            LineTerminator::Newline => b"\n",
+            LineTerminator::Nul => b"\0",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Indent {
+    Spaces(usize),
+    Tab,
+}
+
+impl Indent {
+    fn write<W: Write>(self, out: &mut W, level: usize) -> io::Result<()> {
+        for _ in 0..level {
+            match self {
+                Indent::Spaces(n) => write!(out, "{:n$}", "")?,
+                Indent::Tab => write!(out, "\t")?,
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct RenderOptions {
+    pub line_terminator: LineTerminator,
+    /// Write the values as the elements of one JSON array, e.g. `[[["a"],1],[["a"]]]`, followed by
+    /// a single terminator, instead of one value per line.
+    pub json_array: bool,
+    /// Only put the terminator between values, not after the last one.
+    pub no_trailing_terminator: bool,
+    /// Pretty-print each value over multiple lines like `jq --stream` without `-c`. `None` or
+    /// zero spaces means compact output.
+    pub indent: Option<Indent>,
+    /// Write numbers as strings, e.g. `"42"` instead of `42`.
+    pub numbers_as_strings: bool,
+    /// Write numbers exactly like jq 1.6 does, e.g. `1e+300` and `1e-05`, instead of Rust's
+    /// formatting, which never uses an exponent.
+    pub jq_floats: bool,
+    /// Escape every non-ASCII character in strings and map keys as `\uXXXX`, like `jq -a`.
+    pub ascii: bool,
+    /// Cut leaf values longer than this many characters as rendered, e.g. `"abc…" (1000 chars)`.
+    /// Paths are never cut. The cut values aren't valid JSON, so this is only for reading the
+    /// output, not for feeding it back to `fromstream`.
+    pub max_value_length: Option<usize>,
+    /// Write integers of more than this magnitude as strings, e.g. so that 64-bit IDs survive
+    /// consumers that parse numbers as `f64`. Numbers that aren't integers are left as they are, as
+    /// well as numbers beyond 2^53 that weren't [`PrimitiveValue::Integer`]s, like `1e300`.
+    pub large_ints_as_strings: Option<u64>,
+    /// Write the empty path of a top-level scalar or empty container as `[TOKEN]` instead of
+    /// `[]`, e.g. `[["$"],1]`, for consumers that can't handle empty paths.
+    pub root_token: Option<String>,
+    /// Write the jq type name of each leaf after its value, e.g. `[["a"],1,"number"]`, and the
+    /// type of the container each close marker closes after its path, e.g. `[["a",0],"array"]`.
+    pub with_type: bool,
+    /// Write each [`PathValue`] as an object with named fields instead of an array, e.g.
+    /// `{"path":["a"],"value":1}`, and close markers as `{"path":["a"],"close":true}`. The type of
+    /// [`RenderOptions::with_type`] and the span of [`Printer::print_with_span`] are `type`, and
+    /// `start` and `end`. Objects are always written on one line, whatever the indentation.
+    pub objects: bool,
+}
+
+/// Writes [`PathValue`]s to `W`, one per line.
+pub struct Printer<W> {
+    out: W,
+    options: RenderOptions,
+    first: bool,
+}
+
+impl<W: Write> Printer<W> {
+    pub fn new(out: W, options: RenderOptions) -> Self {
+        Self {
+            out,
+            options,
+            first: true,
+        }
+    }
+
+    pub fn print(&mut self, value: &PathValue) -> io::Result<()> {
+        self.print_with_span(value, None)
+    }
+
+    /// Like [`Printer::print`], but with the byte span of a leaf after its value, e.g.
+    /// `[["a"],1,6,7]`, as from [`crate::stream_with_spans`].
+    pub fn print_with_span(
+        &mut self,
+        value: &PathValue,
+        span: Option<&Range<usize>>,
+    ) -> io::Result<()> {
+        let root;
+        let value = match &self.options.root_token {
+            Some(token) if value.path.is_empty() => {
+                root = PathValue {
+                    path: vec![Index::Map(token.clone())],
+                    value: value.value.clone(),
+                    count: value.count,
+                };
+                &root
+            }
+            _ => value,
+        };
+        self.begin()?;
+        match self.options.indent {
+            _ if self.options.objects => {
+                write_path_value_object(&mut self.out, value, span, &self.options)?
+            }
+            None | Some(Indent::Spaces(0)) => {
+                write_path_value(&mut self.out, value, span, &self.options)?
+            }
+            Some(indent) => {
+                write_path_value_pretty(&mut self.out, value, span, indent, &self.options)?
+            }
+        }
+        self.end()
+    }
+
+    /// Writes an arbitrary JSON value instead of a [`PathValue`], with the same terminators and
+    /// indentation.
+    pub fn print_value(&mut self, value: &serde_json::Value) -> io::Result<()> {
+        use serde::Serialize;
+        self.begin()?;
+        let mut json = vec![];
+        match self.options.indent {
+            None | Some(Indent::Spaces(0)) => serde_json::to_writer(&mut json, value)?,
+            Some(indent) => {
+                let indent = match indent {
+                    Indent::Spaces(n) => vec![b' '; n],
+                    Indent::Tab => vec![b'\t'],
+                };
+                let formatter = serde_json::ser::PrettyFormatter::with_indent(&indent);
+                value.serialize(&mut serde_json::Serializer::with_formatter(
+                    &mut json, formatter,
+                ))?
+            }
+        }
+        if self.options.ascii {
+            // Non-ASCII characters can only be in strings, so they can be escaped afterwards.
+            let json = String::from_utf8(json).expect("serde_json writes UTF-8");
+            for c in json.chars() {
+                if c.is_ascii() {
+                    self.out.write_all(&[c as u8])?;
+                } else {
+                    write_unicode_escape(&mut self.out, c)?;
+                }
+            }
+        } else {
+            self.out.write_all(&json)?;
+        }
+        self.end()
+    }
+
+    /// Writes a leaf value without a path, with the same terminators.
+    pub fn print_leaf(&mut self, value: &PrimitiveValue) -> io::Result<()> {
+        self.begin()?;
+        write_leaf(&mut self.out, value, &self.options)?;
+        self.end()
+    }
+
+    /// Like [`Printer::print_leaf`], but strings without quotes or escapes, e.g. `a "b"` for the
+    /// string `"a \"b\""`, and binary data as bare base64. Fails with
+    /// [`io::ErrorKind::InvalidData`] for a string that contains the terminator, which would be
+    /// read as two values.
+    pub fn print_raw_leaf(&mut self, value: &PrimitiveValue) -> io::Result<()> {
+        let terminator = self.options.line_terminator.as_bytes();
+        self.begin()?;
+        match value {
+            PrimitiveValue::String(v) if v.as_bytes().contains(&terminator[0]) => {
+                let message = format!(
+                    "The string {v:?} contains the terminator `{}`",
+                    terminator.escape_ascii()
+                );
+                return Err(io::Error::new(io::ErrorKind::InvalidData, message));
+            }
+            PrimitiveValue::String(v) => self.out.write_all(v.as_bytes())?,
+            PrimitiveValue::Bytes(v) => write!(self.out, "{}", PrimitiveValue::base64(v))?,
+            value => write_leaf(&mut self.out, value, &self.options)?,
+        }
+        self.end()
+    }
+
+    /// Writes `line` as it is, with the same terminators.
+    pub fn print_line(&mut self, line: &str) -> io::Result<()> {
+        self.begin()?;
+        self.out.write_all(line.as_bytes())?;
+        self.end()
+    }
+
+    fn begin(&mut self) -> io::Result<()> {
+        if self.options.json_array {
+            self.out.write_all(if self.first { b"[" } else { b"," })?;
+        } else if self.options.no_trailing_terminator && !self.first {
+            self.out
+                .write_all(self.options.line_terminator.as_bytes())?;
+        }
+        self.first = false;
+        Ok(())
+    }
+
+    fn end(&mut self) -> io::Result<()> {
+        if !self.options.json_array && !self.options.no_trailing_terminator {
+            self.out
+                .write_all(self.options.line_terminator.as_bytes())?;
+        }
+        Ok(())
+    }
+
+    /// Flushes the output. Call this at the end, since errors on drop are ignored.
+    pub fn finish(mut self) -> io::Result<W> {
+        if self.options.json_array {
+            if self.first {
+                self.out.write_all(b"[")?;
+            }
+            self.out.write_all(b"]")?;
+            if !self.options.no_trailing_terminator {
+                self.out
+                    .write_all(self.options.line_terminator.as_bytes())?;
+            }
+        }
+        self.out.flush()?;
+        Ok(self.out)
+    }
+}
+
+fn write_path_value<W: Write>(
+    out: &mut W,
+    value: &PathValue,
+    span: Option<&Range<usize>>,
+    options: &RenderOptions,
+) -> io::Result<()> {
+    write!(out, "[")?;
+    write!(out, "[")?;
+    for (i, v) in value.path.iter().enumerate() {
+        if i != 0 {
+            write!(out, ",")?;
+        }
+        write_index(out, v, options)?;
+    }
+    write!(out, "]")?;
+    if let Some(value) = &value.value {
+        write!(out, ",")?;
+        write_leaf(out, value, options)?;
+    }
+    if let Some(count) = value.count {
+        write!(out, ",{{\"count\":{count}}}")?;
+    }
+    if let Some(name) = type_name(value, options) {
+        write!(out, ",\"{name}\"")?;
+    }
+    if let Some(span) = span {
+        write!(out, ",{},{}", span.start, span.end)?;
+    }
+    write!(out, "]")
+}
+
+fn write_path_value_object<W: Write>(
+    out: &mut W,
+    value: &PathValue,
+    span: Option<&Range<usize>>,
+    options: &RenderOptions,
+) -> io::Result<()> {
+    write!(out, "{{\"path\":[")?;
+    for (i, v) in value.path.iter().enumerate() {
+        if i != 0 {
+            write!(out, ",")?;
+        }
+        write_index(out, v, options)?;
+    }
+    write!(out, "]")?;
+    match &value.value {
+        Some(value) => {
+            write!(out, ",\"value\":")?;
+            write_leaf(out, value, options)?;
+        }
+        None => write!(out, ",\"close\":true")?,
+    }
+    if let Some(count) = value.count {
+        write!(out, ",\"count\":{count}")?;
+    }
+    if let Some(name) = type_name(value, options) {
+        write!(out, ",\"type\":\"{name}\"")?;
+    }
+    if let Some(span) = span {
+        write!(out, ",\"start\":{},\"end\":{}", span.start, span.end)?;
+    }
+    write!(out, "}}")
+}
+
+fn write_path_value_pretty<W: Write>(
+    out: &mut W,
+    value: &PathValue,
+    span: Option<&Range<usize>>,
+    indent: Indent,
+    options: &RenderOptions,
+) -> io::Result<()> {
+    writeln!(out, "[")?;
+    indent.write(out, 1)?;
+    if value.path.is_empty() {
+        write!(out, "[]")?;
+    } else {
+        writeln!(out, "[")?;
+        for (i, v) in value.path.iter().enumerate() {
+            if i != 0 {
+                writeln!(out, ",")?;
+            }
+            indent.write(out, 2)?;
+            write_index(out, v, options)?;
+        }
+        writeln!(out)?;
+        indent.write(out, 1)?;
+        write!(out, "]")?;
+    }
+    if let Some(value) = &value.value {
+        writeln!(out, ",")?;
+        indent.write(out, 1)?;
+        write_leaf(out, value, options)?;
+    }
+    if let Some(count) = value.count {
+        writeln!(out, ",")?;
+        indent.write(out, 1)?;
+        write!(out, "{{\"count\":{count}}}")?;
+    }
+    if let Some(name) = type_name(value, options) {
+        writeln!(out, ",")?;
+        indent.write(out, 1)?;
+        write!(out, "\"{name}\"")?;
+    }
+    if let Some(span) = span {
+        for offset in [span.start, span.end] {
+            writeln!(out, ",")?;
+            indent.write(out, 1)?;
+            write!(out, "{offset}")?;
+        }
+    }
+    writeln!(out)?;
+    write!(out, "]")
+}
+
+/// The type written with [`RenderOptions::with_type`], if it's set.
+fn type_name(value: &PathValue, options: &RenderOptions) -> Option<&'static str> {
+    if !options.with_type {
+        return None;
+    }
+    match (&value.value, value.path.last()) {
+        (Some(leaf), _) => Some(leaf.type_name()),
+        (None, Some(Index::Array(_))) => Some("array"),
+        (None, Some(Index::Map(_) | Index::OrderedKey { .. })) => Some("object"),
+        (None, None) => None,
+    }
+}
+
+pub(crate) fn write_index<W: Write>(
+    out: &mut W,
+    index: &Index,
+    options: &RenderOptions,
+) -> io::Result<()> {
+    match index {
+        Index::Array(i) => write!(out, "{i}"),
+        Index::Map(s) => write_string(out, s, options.ascii),
+        Index::OrderedKey { key, index } => {
+            write!(out, "{{\"key\":")?;
+            write_string(out, key, options.ascii)?;
+            write!(out, ",\"index\":{index}}}")
+        }
+    }
+}
+
+/// Writes `s` as a JSON string like jq does: control characters and DEL are escaped, as well as
+/// every other non-ASCII character if `ascii` is set.
+fn write_string<W: Write>(out: &mut W, s: &str, ascii: bool) -> io::Result<()> {
+    out.write_all(b"\"")?;
+    let mut start = 0;
+    for (i, c) in s.char_indices() {
+        let escape = match c {
+            '"' => Some("\\\""),
+            '\\' => Some("\\\\"),
+            '\n' => Some("\\n"),
+            '\t' => Some("\\t"),
+            '\r' => Some("\\r"),
+            '\x08' => Some("\\b"),
+            '\x0c' => Some("\\f"),
+            _ if c < ' ' || c == '\x7f' || (ascii && !c.is_ascii()) => None,
+            _ => continue,
+        };
+        out.write_all(&s.as_bytes()[start..i])?;
+        start = i + c.len_utf8();
+        match escape {
+            Some(escape) => out.write_all(escape.as_bytes())?,
+            None => write_unicode_escape(out, c)?,
+        }
+    }
+    out.write_all(&s.as_bytes()[start..])?;
+    out.write_all(b"\"")
+}
+
+/// Writes `c` as `\uXXXX`, or as a surrogate pair of them outside of the BMP.
+fn write_unicode_escape<W: Write>(out: &mut W, c: char) -> io::Result<()> {
+    for unit in c.encode_utf16(&mut [0; 2]) {
+        write!(out, "\\u{unit:04x}")?;
+    }
+    Ok(())
+}
+
+/// Writes `value`, cut to [`RenderOptions::max_value_length`].
+pub(crate) fn write_leaf<W: Write>(
+    out: &mut W,
+    value: &PrimitiveValue,
+    options: &RenderOptions,
+) -> io::Result<()> {
+    let Some(max) = options.max_value_length else {
+        return write_primitive(out, value, options);
+    };
+    let mut rendered = vec![];
+    write_primitive(&mut rendered, value, options)?;
+    let rendered = String::from_utf8(rendered).expect("values are written as UTF-8");
+    let len = rendered.chars().count();
+    match rendered.char_indices().nth(max) {
+        Some((end, _)) => write!(out, "{}… ({len} chars)", &rendered[..end]),
+        None => out.write_all(rendered.as_bytes()),
+    }
+}
+
+fn write_primitive<W: Write>(
+    out: &mut W,
+    value: &PrimitiveValue,
+    options: &RenderOptions,
+) -> io::Result<()> {
+    match value {
+        PrimitiveValue::Null => write!(out, "null"),
+        PrimitiveValue::Boolean(v) => write!(out, "{v}"),
+        PrimitiveValue::Number(v) if number_as_string(*v, options) => {
+            write!(out, "\"")?;
+            write_number(out, *v, options)?;
+            write!(out, "\"")
+        }
+        PrimitiveValue::Number(v) => write_number(out, *v, options),
+        PrimitiveValue::Integer(v) if integer_as_string(*v, options) => {
+            write!(out, "\"")?;
+            write_integer(out, *v, options)?;
+            write!(out, "\"")
+        }
+        PrimitiveValue::Integer(v) => write_integer(out, *v, options),
+        PrimitiveValue::String(v) => write_string(out, v, options.ascii),
+        PrimitiveValue::Bytes(v) => write!(out, "\"{}\"", PrimitiveValue::base64(v)),
+        PrimitiveValue::EmptyArray => write!(out, "[]"),
+        PrimitiveValue::EmptyObject => write!(out, "{{}}"),
+        PrimitiveValue::Scalars(values) => {
+            write!(out, "[")?;
+            for (i, value) in values.iter().enumerate() {
+                if i != 0 {
+                    write!(out, ",")?;
+                }
+                write_primitive(out, value, options)?;
+            }
+            write!(out, "]")
+        }
+    }
+}
+
+fn number_as_string(v: f64, options: &RenderOptions) -> bool {
+    // Integers of more than 2^53 are `PrimitiveValue::Integer`s, so a larger `Number` may have
+    // been anything, like `1e300`.
+    let integer = v.fract() == 0.0 && v.abs() <= (1u64 << 53) as f64;
+    options.numbers_as_strings
+        || options
+            .large_ints_as_strings
+            .is_some_and(|max| integer && v.abs() > max as f64)
+}
+
+fn integer_as_string(v: i128, options: &RenderOptions) -> bool {
+    options.numbers_as_strings
+        || options
+            .large_ints_as_strings
+            .is_some_and(|max| v.unsigned_abs() > max.into())
+}
+
+/// Writes `v` exactly, unless it should be written like jq, which only has `f64`s.
+fn write_integer<W: Write>(out: &mut W, v: i128, options: &RenderOptions) -> io::Result<()> {
+    if options.jq_floats {
+        write_jq_number(out, v as f64)
+    } else {
+        write!(out, "{v}")
+    }
+}
+
+/// Writes `v` as valid JSON even if it isn't finite, which e.g. YAML's `.nan` and `.inf` aren't:
+/// `NaN` is `null` and infinities are the largest finite numbers, like jq does.
+fn write_number<W: Write>(out: &mut W, v: f64, options: &RenderOptions) -> io::Result<()> {
+    if options.jq_floats {
+        write_jq_number(out, v)
+    } else if v.is_nan() {
+        write!(out, "null")
+    } else {
+        write!(out, "{}", v.clamp(f64::MIN, f64::MAX))
+    }
+}
+
+/// Writes `v` like jq 1.6's `jvp_dtoa_fmt`: the shortest digits that round-trip, the even ones of
+/// a tie, in exponent notation if the decimal point is at least 4 places before the first digit or
+/// more than 15 places after the last one. `NaN` is `null` and infinities are the largest finite
+/// numbers.
+fn write_jq_number<W: Write>(out: &mut W, v: f64) -> io::Result<()> {
+    if v.is_nan() {
+        return write!(out, "null");
+    }
+    let v = v.clamp(f64::MIN, f64::MAX);
+    let sign = if v.is_sign_negative() { "-" } else { "" };
+    if v == 0.0 {
+        return write!(out, "{sign}0");
+    }
+    let (digits, exponent) = shortest_digits(v.abs());
+    let fixed = -3..digits.len() as i32 + 16;
+    write_digits(out, sign, &digits, exponent, fixed, 2)
+}
@@ -0,0 +1,35 @@
+//! Validating documents against a [JSON Schema](https://json-schema.org/).
+
+use jsonschema::{paths::LocationSegment, ValidationError, Validator};
+
+use crate::{Index, Path};
+
+/// A compiled JSON Schema, of whichever draft its `$schema` says, or the latest one.
+///
+/// Only local references are resolved, there's no fetching of remote schemas.
+pub struct Schema(Validator);
+
+impl Schema {
+    pub fn new(schema: &serde_json::Value) -> Result<Self, ValidationError<'static>> {
+        jsonschema::validator_for(schema).map(Self)
+    }
+
+    /// Validates a whole document, returning the path of each value that fails validation along
+    /// with why. Empty if the document is valid.
+    pub fn validate(&self, document: &serde_json::Value) -> Vec<(Path, String)> {
+        self.0
+            .iter_errors(document)
+            .map(|error| {
+                let path = error
+                    .instance_path()
+                    .segments()
+                    .map(|segment| match segment {
+                        LocationSegment::Property(key) => Index::Map(key.into_owned()),
+                        LocationSegment::Index(i) => Index::Array(i),
+                    })
+                    .collect();
+                (path, error.to_string())
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,192 @@
+//! Summarizing a stream by the structural paths in it.
+
+use std::collections::{HashMap, HashSet};
+
+use crate::{Index, PathValue, PrimitiveValue, StreamError};
+
+/// An [`Index`] with array indices generalized away, so that all the elements of an array share
+/// the same paths.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum ShapeIndex {
+    /// Any index of an array.
+    Array,
+    Map(String),
+}
+
+pub fn generalize(path: &[Index]) -> Vec<ShapeIndex> {
+    path.iter()
+        .map(|index| match index {
+            Index::Array(_) => ShapeIndex::Array,
+            Index::Map(key) | Index::OrderedKey { key, .. } => ShapeIndex::Map(key.clone()),
+        })
+        .collect()
+}
+
+/// A set of jq type names.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Types(u8);
+
+impl Types {
+    const NAMES: [&'static str; 6] = ["null", "boolean", "number", "string", "array", "object"];
+
+    pub fn insert(&mut self, value: &PrimitiveValue) {
+        let bit = match value {
+            PrimitiveValue::Null => 0,
+            PrimitiveValue::Boolean(_) => 1,
+            PrimitiveValue::Number(_) | PrimitiveValue::Integer(_) => 2,
+            PrimitiveValue::String(_) | PrimitiveValue::Bytes(_) => 3,
+            PrimitiveValue::EmptyArray | PrimitiveValue::Scalars(_) => 4,
+            PrimitiveValue::EmptyObject => 5,
+        };
+        self.0 |= 1 << bit;
+    }
+}
+
+/// The names joined by `|`, e.g. `number|string`, always in the same order.
+impl std::fmt::Display for Types {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut names = (0..Self::NAMES.len())
+            .filter(|bit| self.0 & (1 << bit) != 0)
+            .map(|bit| Self::NAMES[bit]);
+        if let Some(name) = names.next() {
+            write!(f, "{name}")?;
+        }
+        names.try_for_each(|name| write!(f, "|{name}"))
+    }
+}
+
+/// Folds the stream into the types of the leaves seen at each generalized path, yielding them in
+/// the order each path is first seen once the inner stream ends. Errors are passed through as
+/// they come.
+///
+/// Only leaves count, so a non-empty container only shows up through the paths under it, while an
+/// empty one is an `array` or `object` at its own path. Close markers are ignored.
+pub struct Shape<I> {
+    inner: Option<I>,
+    indices: HashMap<Vec<ShapeIndex>, usize>,
+    shapes: Vec<(Vec<ShapeIndex>, Types)>,
+}
+
+impl<I> Shape<I> {
+    pub fn new(inner: I) -> Self {
+        Self {
+            inner: Some(inner),
+            indices: HashMap::new(),
+            shapes: vec![],
+        }
+    }
+}
+
+impl<I: Iterator<Item = Result<PathValue, StreamError>>> Iterator for Shape<I> {
+    type Item = Result<(Vec<ShapeIndex>, Types), StreamError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some(inner) = &mut self.inner {
+            match inner.next() {
+                Some(Ok(PathValue {
+                    path,
+                    value: Some(value),
+                    ..
+                })) => {
+                    let path = generalize(&path);
+                    let i = *self.indices.entry(path.clone()).or_insert_with(|| {
+                        self.shapes.push((path, Types::default()));
+                        self.shapes.len() - 1
+                    });
+                    self.shapes[i].1.insert(&value);
+                }
+                Some(Ok(_)) => {}
+                Some(Err(e)) => return Some(Err(e)),
+                None => {
+                    self.inner = None;
+                    self.indices.clear();
+                    self.shapes.reverse();
+                }
+            }
+        }
+        self.shapes.pop().map(Ok)
+    }
+}
+
+/// Keeps only the first leaf seen at each generalized path, as an example of the values there.
+/// Close markers are dropped, and errors are passed through.
+pub struct FirstSeen<I> {
+    inner: I,
+    seen: HashSet<Vec<ShapeIndex>>,
+}
+
+impl<I> FirstSeen<I> {
+    pub fn new(inner: I) -> Self {
+        Self {
+            inner,
+            seen: HashSet::new(),
+        }
+    }
+}
+
+impl<I: Iterator<Item = Result<PathValue, StreamError>>> Iterator for FirstSeen<I> {
+    type Item = Result<PathValue, StreamError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            match self.inner.next()? {
+                Ok(value)
+                    if value.value.is_none() || !self.seen.insert(generalize(&value.path)) =>
+                {
+                    continue
+                }
+                v => return Some(v),
+            }
+        }
+    }
+}
+
+/// Counts the leaves at each depth, i.e. length of their paths, yielding `(depth, count)` by
+/// increasing depth once the whole stream has been read. Depths without leaves are skipped, and
+/// errors are passed through.
+pub struct Depths<I> {
+    inner: Option<I>,
+    counts: Vec<usize>,
+    next: usize,
+}
+
+impl<I> Depths<I> {
+    pub fn new(inner: I) -> Self {
+        Self {
+            inner: Some(inner),
+            counts: vec![],
+            next: 0,
+        }
+    }
+}
+
+impl<I: Iterator<Item = Result<PathValue, StreamError>>> Iterator for Depths<I> {
+    type Item = Result<(usize, usize), StreamError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some(inner) = &mut self.inner {
+            match inner.next() {
+                Some(Ok(PathValue {
+                    path,
+                    value: Some(_),
+                    ..
+                })) => {
+                    if self.counts.len() <= path.len() {
+                        self.counts.resize(path.len() + 1, 0);
+                    }
+                    self.counts[path.len()] += 1;
+                }
+                Some(Ok(_)) => {}
+                Some(Err(e)) => return Some(Err(e)),
+                None => self.inner = None,
+            }
+        }
+        while let Some(&count) = self.counts.get(self.next) {
+            self.next += 1;
+            if count != 0 {
+                return Some(Ok((self.next - 1, count)));
+            }
+        }
+        None
+    }
+}
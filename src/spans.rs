@@ -0,0 +1,145 @@
+//! Byte offsets of the leaves of JSON input, for [`crate::stream_with_spans`].
+
+use std::{
+    cell::RefCell,
+    io::{self, Read},
+    ops::Range,
+    rc::Rc,
+    sync::mpsc::{Receiver, Sender},
+};
+
+use crate::{PathValue, PrimitiveValue, Stream, StreamError};
+
+/// Where the tokens that were read last are, as far as telling leaves apart goes. Strings and
+/// other literals are tokens, and so are the brackets of arrays and objects.
+#[derive(Default)]
+struct Scanner {
+    /// Bytes read so far.
+    offset: usize,
+    in_string: bool,
+    escape: bool,
+    /// Whether the last byte was part of a literal other than a string, e.g. a number.
+    in_literal: bool,
+    /// The last string or literal.
+    token: Range<usize>,
+    /// Offset of the last `[` or `{`.
+    open: usize,
+    /// Offset right after the last `]` or `}`.
+    close: usize,
+}
+
+impl Scanner {
+    fn scan(&mut self, b: u8) {
+        let offset = self.offset;
+        self.offset += 1;
+        if self.in_string {
+            match b {
+                _ if self.escape => self.escape = false,
+                b'\\' => self.escape = true,
+                b'"' => {
+                    self.in_string = false;
+                    self.token.end = self.offset;
+                }
+                _ => {}
+            }
+            return;
+        }
+        let in_literal = std::mem::take(&mut self.in_literal);
+        match b {
+            b'"' => {
+                self.in_string = true;
+                self.token.start = offset;
+            }
+            b'[' | b'{' => self.open = offset,
+            b']' | b'}' => self.close = self.offset,
+            b' ' | b'\t' | b'\n' | b'\r' | b',' | b':' => {}
+            _ => {
+                if !in_literal {
+                    self.token.start = offset;
+                }
+                self.token.end = self.offset;
+                self.in_literal = true;
+            }
+        }
+    }
+}
+
+/// Scans the bytes that serde_json reads. serde_json reads one byte at a time, and at most one
+/// byte past the end of a value, so the [`Scanner`] knows where the leaf it has just parsed is.
+pub(crate) struct SpanReader<R> {
+    read: R,
+    scanner: Rc<RefCell<Scanner>>,
+}
+
+impl<R: Read> Read for SpanReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.read.read(buf)?;
+        let mut scanner = self.scanner.borrow_mut();
+        buf[..n].iter().for_each(|&b| scanner.scan(b));
+        Ok(n)
+    }
+}
+
+/// The [`Scanner`] of the input parsed in this thread, and where to send the span of each leaf
+/// before the leaf itself.
+struct Spans {
+    scanner: Rc<RefCell<Scanner>>,
+    sender: Sender<Range<usize>>,
+}
+
+thread_local! {
+    static SPANS: RefCell<Option<Spans>> = const { RefCell::new(None) };
+}
+
+/// Starts tracking the spans of the leaves parsed from `read` in this thread.
+pub(crate) fn track<R>(read: R, sender: Sender<Range<usize>>) -> SpanReader<R> {
+    let scanner = Rc::new(RefCell::new(Scanner::default()));
+    SPANS.set(Some(Spans {
+        scanner: scanner.clone(),
+        sender,
+    }));
+    SpanReader { read, scanner }
+}
+
+/// Sends the span of `value` if spans are tracked in this thread and it's a leaf, right before it
+/// is sent itself.
+pub(crate) fn record(value: &PathValue) {
+    let Some(leaf) = &value.value else {
+        return;
+    };
+    SPANS.with_borrow(|spans| {
+        let Some(spans) = spans else {
+            return;
+        };
+        let scanner = spans.scanner.borrow();
+        let span = match leaf {
+            PrimitiveValue::EmptyArray | PrimitiveValue::EmptyObject => scanner.open..scanner.close,
+            _ => scanner.token.clone(),
+        };
+        // The receiver is only dropped along with the one of the values, which is noticed then.
+        let _ = spans.sender.send(span);
+    })
+}
+
+/// The `(path, value)` pairs of [`crate::stream_with_spans`], each leaf along with its byte span
+/// in the input, as `start..end` offsets. Close markers have no span.
+pub struct SpannedStream {
+    pub(crate) stream: Stream,
+    pub(crate) spans: Receiver<Range<usize>>,
+}
+
+impl Iterator for SpannedStream {
+    type Item = Result<(PathValue, Option<Range<usize>>), StreamError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let value = match self.stream.next()? {
+            Ok(value) => value,
+            Err(e) => return Some(Err(e)),
+        };
+        let span = match value.value {
+            Some(_) => Some(self.spans.recv().expect("sent before the value")),
+            None => None,
+        };
+        Some(Ok((value, span)))
+    }
+}
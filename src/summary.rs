@@ -0,0 +1,189 @@
+//! One-line sketches of the top-level structure of each document.
+
+use crate::{Index, PathValue, PrimitiveValue, StreamError};
+
+/// Collapses each document of the stream into a line like `doc 0: object{id,name,items[3]}`,
+/// yielded once the document ends. Errors are passed through, dropping the partial sketch.
+///
+/// A sketch only shows the first two levels of a document, so that nothing deeper is kept in
+/// memory:
+/// - A top-level object lists its keys, where a key whose value is an array is followed by the
+///   array's length, like `items[3]`, and one whose value is an object by its number of keys, like
+///   `meta{2}`.
+/// - A top-level array only shows its length, like `array[3]`.
+/// - Anything else is its jq type name, like `number`.
+///
+/// Keys are written as they are if they only consist of alphanumerics, `_` and `-`, and as JSON
+/// strings otherwise. Documents are numbered from 0 in the order they're streamed, and a document
+/// ends like in [`crate::values::into_values`].
+pub struct Summaries<I> {
+    inner: I,
+    documents: usize,
+    current: Option<Sketch>,
+}
+
+impl<I> Summaries<I> {
+    pub fn new(inner: I) -> Self {
+        Self {
+            inner,
+            documents: 0,
+            current: None,
+        }
+    }
+
+    fn finish(&mut self, sketch: String) -> String {
+        self.current = None;
+        self.documents += 1;
+        format!("doc {}: {sketch}", self.documents - 1)
+    }
+}
+
+impl<I: Iterator<Item = Result<PathValue, StreamError>>> Iterator for Summaries<I> {
+    type Item = Result<String, StreamError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let value = match self.inner.next()? {
+                Ok(value) => value,
+                Err(e) => {
+                    self.current = None;
+                    return Some(Err(e));
+                }
+            };
+            let Some((first, rest)) = value.path.split_first() else {
+                let sketch = match value.value {
+                    Some(PrimitiveValue::Scalars(values)) => {
+                        return Some(Ok(self.finish(format!("array[{}]", values.len()))));
+                    }
+                    Some(PrimitiveValue::EmptyArray) => "array[0]",
+                    Some(PrimitiveValue::EmptyObject) => "object{}",
+                    Some(PrimitiveValue::Null) | None => "null",
+                    Some(PrimitiveValue::Boolean(_)) => "boolean",
+                    Some(PrimitiveValue::Number(_) | PrimitiveValue::Integer(_)) => "number",
+                    Some(PrimitiveValue::String(_) | PrimitiveValue::Bytes(_)) => "string",
+                };
+                return Some(Ok(self.finish(sketch.to_string())));
+            };
+            let top = self.current.get_or_insert_with(|| Sketch {
+                top: Children::new(first),
+                members: vec![],
+            });
+            if top.top.see(first) {
+                if let Index::Map(key) | Index::OrderedKey { key, .. } = first {
+                    top.members.push(Member {
+                        key: key.clone(),
+                        children: None,
+                    });
+                }
+            }
+            if let (Index::Map(_) | Index::OrderedKey { .. }, Some(member)) =
+                (first, top.members.last_mut())
+            {
+                match (rest.first(), &value.value) {
+                    (Some(index), _) => {
+                        member
+                            .children
+                            .get_or_insert_with(|| Children::new(index))
+                            .see(index);
+                    }
+                    (None, Some(PrimitiveValue::EmptyArray)) => {
+                        member.children = Some(Children::empty(false))
+                    }
+                    (None, Some(PrimitiveValue::EmptyObject)) => {
+                        member.children = Some(Children::empty(true))
+                    }
+                    (None, Some(PrimitiveValue::Scalars(values))) => {
+                        member.children = Some(Children {
+                            len: values.len(),
+                            ..Children::empty(false)
+                        })
+                    }
+                    (None, _) => {}
+                }
+            }
+            if rest.is_empty() && value.value.is_none() {
+                let sketch = self.current.take().unwrap().to_string();
+                return Some(Ok(self.finish(sketch)));
+            }
+        }
+    }
+}
+
+/// The top level of a document, and the second level if it's an object.
+struct Sketch {
+    top: Children,
+    members: Vec<Member>,
+}
+
+struct Member {
+    key: String,
+    /// `None` unless the value is a container.
+    children: Option<Children>,
+}
+
+/// Counts the direct children of a container.
+struct Children {
+    object: bool,
+    len: usize,
+    /// The index of the last child, to tell when the next one starts.
+    last: Option<Index>,
+}
+
+impl Children {
+    /// The children of the container with a child at `index`.
+    fn new(index: &Index) -> Self {
+        Self::empty(matches!(index, Index::Map(_) | Index::OrderedKey { .. }))
+    }
+
+    fn empty(object: bool) -> Self {
+        Self {
+            object,
+            len: 0,
+            last: None,
+        }
+    }
+
+    /// Counts the child at `index`, returning whether it's a new one.
+    fn see(&mut self, index: &Index) -> bool {
+        if self.last.as_ref() == Some(index) {
+            return false;
+        }
+        self.len += 1;
+        self.last = Some(index.clone());
+        true
+    }
+}
+
+impl std::fmt::Display for Sketch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if !self.top.object {
+            return write!(f, "array[{}]", self.top.len);
+        }
+        write!(f, "object{{")?;
+        for (i, member) in self.members.iter().enumerate() {
+            if i != 0 {
+                write!(f, ",")?;
+            }
+            let key = &member.key;
+            if !key.is_empty()
+                && key
+                    .chars()
+                    .all(|c| c.is_alphanumeric() || c == '_' || c == '-')
+            {
+                write!(f, "{key}")?;
+            } else {
+                write!(f, "{}", serde_json::Value::from(key.as_str()))?;
+            }
+            match &member.children {
+                Some(Children {
+                    object: false, len, ..
+                }) => write!(f, "[{len}]")?,
+                Some(Children {
+                    object: true, len, ..
+                }) => write!(f, "{{{len}}}")?,
+                None => {}
+            }
+        }
+        write!(f, "}}")
+    }
+}
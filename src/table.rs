@@ -0,0 +1,132 @@
+//! Writing leaves as the rows of a Parquet table, for analytics tools that load columnar data.
+
+use std::{
+    io::{self, Write},
+    mem,
+    sync::Arc,
+};
+
+use parquet::{
+    data_type::{ByteArray, ByteArrayType},
+    errors::ParquetError,
+    file::{properties::WriterProperties, writer::SerializedFileWriter},
+    schema::parser::parse_message_type,
+};
+
+use crate::{
+    render::{write_index, write_leaf, RenderOptions},
+    PathValue, PrimitiveValue,
+};
+
+/// The columns of each leaf, all of them strings.
+const SCHEMA: &str = "
+    message leaf {
+        required binary path (STRING);
+        required binary value (STRING);
+        required binary type (STRING);
+    }
+";
+
+/// How many rows are buffered before they're written as a row group.
+pub const ROWS_PER_GROUP: usize = 64 * 1024;
+
+/// Writes the leaves of [`PathValue`]s as the rows of a Parquet table with the columns `path`,
+/// `value` and `type`, e.g. `["a",0]`, `1` and `number`. The path is written as in the output of
+/// [`crate::render::Printer`], and so is the value, except for strings, which are written as they
+/// are, and binary data, which is bare base64. The type is the jq type name of the value. Close
+/// markers are left out.
+///
+/// Rows are buffered in memory and written [`ROWS_PER_GROUP`] at a time as row groups, so memory
+/// use is bounded by the size of that many rows, whatever the size of the input.
+pub struct ParquetWriter<W: Write + Send> {
+    writer: SerializedFileWriter<W>,
+    options: RenderOptions,
+    /// The rows that haven't been written yet, column by column.
+    columns: [Vec<ByteArray>; 3],
+}
+
+impl<W: Write + Send> ParquetWriter<W> {
+    /// Starts a table in `out`, with values rendered as with `options`.
+    pub fn new(out: W, options: RenderOptions) -> io::Result<Self> {
+        let schema = parse_message_type(SCHEMA).expect("the schema is valid");
+        let properties = WriterProperties::builder().build();
+        let writer = SerializedFileWriter::new(out, Arc::new(schema), Arc::new(properties))
+            .map_err(io_error)?;
+        Ok(Self {
+            writer,
+            options,
+            columns: Default::default(),
+        })
+    }
+
+    /// Adds the row of `value` if it's a leaf.
+    pub fn write(&mut self, value: &PathValue) -> io::Result<()> {
+        let Some(leaf) = &value.value else {
+            return Ok(());
+        };
+        let mut path = vec![];
+        path.push(b'[');
+        for (i, index) in value.path.iter().enumerate() {
+            if i != 0 {
+                path.push(b',');
+            }
+            write_index(&mut path, index, &self.options)?;
+        }
+        path.push(b']');
+        let rendered = match leaf {
+            PrimitiveValue::String(v) => v.clone().into_bytes(),
+            PrimitiveValue::Bytes(v) => PrimitiveValue::base64(v).into_bytes(),
+            leaf => {
+                let mut rendered = vec![];
+                write_leaf(&mut rendered, leaf, &self.options)?;
+                rendered
+            }
+        };
+        let [paths, values, types] = &mut self.columns;
+        paths.push(path.into());
+        values.push(rendered.into());
+        types.push(leaf.type_name().into());
+        if paths.len() >= ROWS_PER_GROUP {
+            self.flush_rows()?;
+        }
+        Ok(())
+    }
+
+    /// Writes the rows left and the footer of the table, which is unreadable without it.
+    pub fn finish(mut self) -> io::Result<W> {
+        self.flush_rows()?;
+        let mut out = self.writer.into_inner().map_err(io_error)?;
+        out.flush()?;
+        Ok(out)
+    }
+
+    fn flush_rows(&mut self) -> io::Result<()> {
+        if self.columns[0].is_empty() {
+            return Ok(());
+        }
+        let mut row_group = self.writer.next_row_group().map_err(io_error)?;
+        for column in &mut self.columns {
+            let mut writer = row_group
+                .next_column()
+                .map_err(io_error)?
+                .expect("there's a writer for each column of the schema");
+            writer
+                .typed::<ByteArrayType>()
+                .write_batch(&mem::take(column), None, None)
+                .map_err(io_error)?;
+            writer.close().map_err(io_error)?;
+        }
+        row_group.close().map_err(io_error)?;
+        Ok(())
+    }
+}
+
+fn io_error(e: ParquetError) -> io::Error {
+    match e {
+        ParquetError::External(e) => match e.downcast::<io::Error>() {
+            Ok(e) => *e,
+            Err(e) => io::Error::other(e),
+        },
+        e => io::Error::other(e),
+    }
+}
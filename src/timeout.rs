@@ -0,0 +1,71 @@
+//! Giving up on input that stops arriving.
+
+use std::{
+    io::{self, Read},
+    sync::mpsc::{sync_channel, Receiver, RecvTimeoutError},
+    time::Duration,
+};
+
+const CHUNK_SIZE: usize = 8 * 1024;
+
+/// A [`Read`] adapter that fails with [`io::ErrorKind::TimedOut`] if no bytes arrive from the
+/// inner reader for `timeout`.
+///
+/// Most readers, like pipes, can't time out by themselves, so the inner reader is read from a
+/// background thread instead. That thread can't be interrupted in the middle of a read: after a
+/// timeout, it stays blocked until the inner reader returns, and then ends.
+pub struct TimeoutReader {
+    receiver: Receiver<io::Result<Vec<u8>>>,
+    timeout: Duration,
+    chunk: Vec<u8>,
+    pos: usize,
+}
+
+impl TimeoutReader {
+    pub fn new<R: Read + Send + 'static>(mut inner: R, timeout: Duration) -> Self {
+        let (sender, receiver) = sync_channel(1);
+        std::thread::spawn(move || loop {
+            let mut chunk = vec![0; CHUNK_SIZE];
+            let result = match inner.read(&mut chunk) {
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                result => result.map(|n| {
+                    chunk.truncate(n);
+                    chunk
+                }),
+            };
+            let last = !matches!(&result, Ok(chunk) if !chunk.is_empty());
+            if sender.send(result).is_err() || last {
+                break;
+            }
+        });
+        Self {
+            receiver,
+            timeout,
+            chunk: vec![],
+            pos: 0,
+        }
+    }
+}
+
+impl Read for TimeoutReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.pos == self.chunk.len() {
+            self.chunk = match self.receiver.recv_timeout(self.timeout) {
+                Ok(chunk) => chunk?,
+                Err(RecvTimeoutError::Timeout) => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::TimedOut,
+                        format!("No input received for {:?}", self.timeout),
+                    ))
+                }
+                // The thread has already sent the end of the input or an error.
+                Err(RecvTimeoutError::Disconnected) => vec![],
+            };
+            self.pos = 0;
+        }
+        let n = buf.len().min(self.chunk.len() - self.pos);
+        buf[..n].copy_from_slice(&self.chunk[self.pos..self.pos + n]);
+        self.pos += n;
+        Ok(n)
+    }
+}
@@ -0,0 +1,243 @@
+//! Reconstructing whole documents from a stream of [`PathValue`]s, like jq's `fromstream`.
+
+use std::io::{self, Write};
+
+use serde_json::{Map, Value};
+use sha2::{Digest, Sha256};
+
+use crate::{
+    number::{shortest_digits, write_digits},
+    Index, PathValue, PrimitiveValue, StreamError,
+};
+
+/// Folds a stream back into the documents it came from, yielding each document once its last
+/// value has been seen. Errors are passed through, dropping the partially built document.
+///
+/// A document ends with a leaf at the root path, or with the close marker of the top-level
+/// container, i.e. a close marker with a path of length 1. Array elements missing from the stream,
+/// e.g. because of [`crate::Options::drop`], are filled with `null`.
+pub fn into_values<I>(inner: I) -> Values<I> {
+    Values {
+        inner,
+        builder: DocumentBuilder::default(),
+    }
+}
+
+pub struct Values<I> {
+    inner: I,
+    builder: DocumentBuilder,
+}
+
+impl<I: Iterator<Item = Result<PathValue, StreamError>>> Iterator for Values<I> {
+    type Item = Result<Value, StreamError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            match self.inner.next()? {
+                Ok(value) => {
+                    if let Some(doc) = self.builder.push(&value.path, value.value) {
+                        return Some(Ok(doc));
+                    }
+                }
+                Err(e) => {
+                    self.builder.reset();
+                    return Some(Err(e));
+                }
+            }
+        }
+    }
+}
+
+/// The document being reassembled by [`Values`], for when the [`PathValue`]s are needed too.
+#[derive(Default)]
+pub struct DocumentBuilder {
+    current: Option<Value>,
+}
+
+impl DocumentBuilder {
+    /// Adds a leaf, or a close marker if `value` is `None`, returning the document it completes.
+    pub fn push(&mut self, path: &[Index], value: Option<PrimitiveValue>) -> Option<Value> {
+        match value {
+            Some(leaf) if path.is_empty() => Some(to_value(leaf)),
+            Some(leaf) => {
+                let slot = path
+                    .iter()
+                    .fold(self.current.get_or_insert(Value::Null), child);
+                *slot = to_value(leaf);
+                None
+            }
+            None if path.len() == 1 => Some(self.current.take().unwrap_or(Value::Null)),
+            None => None,
+        }
+    }
+
+    /// Drops the partially built document.
+    pub fn reset(&mut self) {
+        self.current = None;
+    }
+}
+
+/// Passes the stream through, while also reassembling it like [`into_values`] and writing each
+/// document to `out` as a line of JSON. Only the document being built is kept in memory.
+///
+/// `out` is flushed when the inner stream ends, and an error writing to it is yielded once, in
+/// place of the rest of the stream.
+pub struct Tee<I, W> {
+    inner: I,
+    builder: DocumentBuilder,
+    out: Option<W>,
+}
+
+impl<I, W> Tee<I, W> {
+    pub fn new(inner: I, out: W) -> Self {
+        Self {
+            inner,
+            builder: DocumentBuilder::default(),
+            out: Some(out),
+        }
+    }
+}
+
+impl<I: Iterator<Item = Result<PathValue, StreamError>>, W: Write> Iterator for Tee<I, W> {
+    type Item = Result<PathValue, StreamError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let out = self.out.as_mut()?;
+        let result = match self.inner.next() {
+            Some(Ok(value)) => match self.builder.push(&value.path, value.value.clone()) {
+                Some(doc) => serde_json::to_writer(&mut *out, &doc)
+                    .map_err(io::Error::from)
+                    .and_then(|()| out.write_all(b"\n"))
+                    .map(|()| Some(Ok(value))),
+                None => Ok(Some(Ok(value))),
+            },
+            Some(Err(e)) => {
+                self.builder.reset();
+                Ok(Some(Err(e)))
+            }
+            None => {
+                let flushed = out.flush();
+                self.out = None;
+                flushed.map(|()| None)
+            }
+        };
+        result.unwrap_or_else(|e| {
+            self.out = None;
+            Some(Err(e.into()))
+        })
+    }
+}
+
+/// Returns the child of `value` at `index`, first turning `value` into a container of the right
+/// kind if it isn't one.
+fn child<'a>(value: &'a mut Value, index: &Index) -> &'a mut Value {
+    match index {
+        Index::Array(i) => {
+            if !value.is_array() {
+                *value = Value::Array(vec![]);
+            }
+            let array = value.as_array_mut().unwrap();
+            if array.len() <= *i {
+                array.resize(i + 1, Value::Null);
+            }
+            &mut array[*i]
+        }
+        Index::Map(key) | Index::OrderedKey { key, .. } => {
+            if !value.is_object() {
+                *value = Value::Object(Map::new());
+            }
+            let map = value.as_object_mut().unwrap();
+            map.entry(key.clone()).or_insert(Value::Null)
+        }
+    }
+}
+
+/// Integers up to this magnitude are exactly representable in `f64`.
+const MAX_SAFE_INTEGER: f64 = 9007199254740992.0;
+
+fn to_value(value: PrimitiveValue) -> Value {
+    match value {
+        PrimitiveValue::Null => Value::Null,
+        PrimitiveValue::Boolean(v) => Value::Bool(v),
+        // Integers are kept as such, so that they're written without a fractional part.
+        PrimitiveValue::Number(v) if v.fract() == 0.0 && v.abs() <= MAX_SAFE_INTEGER => {
+            Value::from(v as i64)
+        }
+        PrimitiveValue::Number(v) => {
+            serde_json::Number::from_f64(v).map_or(Value::Null, Value::Number)
+        }
+        PrimitiveValue::Integer(v) => match (i64::try_from(v), u64::try_from(v)) {
+            (Ok(v), _) => Value::from(v),
+            (_, Ok(v)) => Value::from(v),
+            _ => serde_json::Number::from_f64(v as f64).map_or(Value::Null, Value::Number),
+        },
+        PrimitiveValue::String(v) => Value::String(v),
+        PrimitiveValue::Bytes(v) => Value::String(PrimitiveValue::base64(&v)),
+        PrimitiveValue::EmptyArray => Value::Array(vec![]),
+        PrimitiveValue::EmptyObject => Value::Object(Map::new()),
+        PrimitiveValue::Scalars(values) => Value::Array(values.into_iter().map(to_value).collect()),
+    }
+}
+
+/// Writes `value` in the canonical form of RFC 8785 (JCS), so that documents differing only in key
+/// order or number formatting are written the same: compact, with the keys of every object sorted
+/// by their UTF-16 code units, numbers written like ECMAScript does, e.g. `1e+21` and `0.000001`,
+/// and only `"`, `\` and control characters escaped in strings.
+///
+/// Like in ECMAScript, every number is an `f64`, so integers beyond 2^53 are rounded.
+pub fn write_canonical<W: Write>(out: &mut W, value: &Value) -> io::Result<()> {
+    match value {
+        Value::Array(array) => {
+            out.write_all(b"[")?;
+            for (i, v) in array.iter().enumerate() {
+                if i != 0 {
+                    out.write_all(b",")?;
+                }
+                write_canonical(out, v)?;
+            }
+            out.write_all(b"]")
+        }
+        Value::Object(map) => {
+            let mut entries: Vec<_> = map.iter().collect();
+            entries.sort_by(|(a, _), (b, _)| a.encode_utf16().cmp(b.encode_utf16()));
+            out.write_all(b"{")?;
+            for (i, (k, v)) in entries.into_iter().enumerate() {
+                if i != 0 {
+                    out.write_all(b",")?;
+                }
+                serde_json::to_writer(&mut *out, k)?;
+                out.write_all(b":")?;
+                write_canonical(out, v)?;
+            }
+            out.write_all(b"}")
+        }
+        Value::Number(v) => write_es_number(out, v.as_f64().expect("always an f64")),
+        _ => Ok(serde_json::to_writer(out, value)?),
+    }
+}
+
+/// Writes `v` like ECMAScript's `Number.prototype.toString`, as RFC 8785 requires: the shortest
+/// digits that round-trip, in exponent notation if that would take more than 21 digits before the
+/// decimal point, or at least 6 zeros right after it.
+fn write_es_number<W: Write>(out: &mut W, v: f64) -> io::Result<()> {
+    if v == 0.0 {
+        // Including `-0`.
+        return write!(out, "0");
+    }
+    let sign = if v.is_sign_negative() { "-" } else { "" };
+    let (digits, exponent) = shortest_digits(v.abs());
+    write_digits(out, sign, &digits, exponent, -5..22, 1)
+}
+
+/// SHA-256 of the [`write_canonical`] form of `value`, in lowercase hex.
+pub fn canonical_hash(value: &Value) -> String {
+    use std::fmt::Write;
+    let mut canonical = vec![];
+    write_canonical(&mut canonical, value).expect("writing to a Vec doesn't fail");
+    Sha256::digest(&canonical)
+        .iter()
+        .fold(String::new(), |mut s, b| {
+            write!(s, "{b:02x}").unwrap();
+            s
+        })
+}
@@ -0,0 +1,56 @@
+use apache_avro::{types::Record, Schema, Writer};
+use serde_json::json;
+use stream::{stream, values::into_values, Avro, Options, StreamError};
+
+const SCHEMA: &str = r#"{
+    "type": "record",
+    "name": "user",
+    "fields": [
+        {"name": "name", "type": "string"},
+        {"name": "nick", "type": ["null", "string"]},
+        {"name": "scores", "type": {"type": "map", "values": "long"}}
+    ]
+}"#;
+
+#[test]
+fn records_are_documents() {
+    let schema = Schema::parse_str(SCHEMA).unwrap();
+    let mut writer = Writer::new(&schema, vec![]).unwrap();
+    for (name, nick) in [("alice", None), ("bob", Some("bobby"))] {
+        let mut record = Record::new(&schema).unwrap();
+        record.put("name", name);
+        record.put("nick", nick.map(str::to_string));
+        record.put(
+            "scores",
+            std::collections::HashMap::from([("z".to_string(), 1i64), ("a".to_string(), 2i64)]),
+        );
+        writer.append_value(record).unwrap();
+    }
+    let input = writer.into_inner().unwrap();
+
+    let values: Vec<_> = into_values(stream::<Avro, _>(
+        std::io::Cursor::new(input),
+        Options::default(),
+    ))
+    .collect::<Result<_, _>>()
+    .unwrap();
+    assert_eq!(
+        values,
+        [
+            json!({"name": "alice", "nick": null, "scores": {"a": 2, "z": 1}}),
+            json!({"name": "bob", "nick": "bobby", "scores": {"a": 2, "z": 1}}),
+        ]
+    );
+    // Keys of maps are sorted.
+    assert_eq!(
+        serde_json::to_string(&values[0]["scores"]).unwrap(),
+        r#"{"a":2,"z":1}"#
+    );
+}
+
+#[test]
+fn bad_header_is_an_error() {
+    let input: &[u8] = b"not avro";
+    let result: Vec<_> = stream::<Avro, _>(input, Options::default()).collect();
+    assert!(matches!(result[..], [Err(StreamError::Deserialize { .. })]));
+}
@@ -0,0 +1,64 @@
+mod common;
+
+use ciborium::Value;
+use stream::{stream, CborSeq, Options, StreamError};
+
+fn encode(values: &[Value]) -> Vec<u8> {
+    let mut out = vec![];
+    for value in values {
+        ciborium::into_writer(value, &mut out).unwrap();
+    }
+    out
+}
+
+/// `{"id": 1, "ok": true}` followed by `["x", <bytes 1 2>]`, the bytes tagged as expected base64.
+fn items() -> Vec<u8> {
+    encode(&[
+        Value::Map(vec![
+            (Value::Text("id".into()), Value::Integer(1.into())),
+            (Value::Text("ok".into()), Value::Bool(true)),
+        ]),
+        Value::Array(vec![
+            Value::Text("x".into()),
+            Value::Tag(22, Box::new(Value::Bytes(vec![1, 2]))),
+        ]),
+    ])
+}
+
+#[test]
+fn cbor_items_are_documents() {
+    common::assert_two_documents::<CborSeq>(items());
+}
+
+#[test]
+fn truncated_trailing_item_is_an_error() {
+    common::assert_truncated::<CborSeq>(items(), 3);
+}
+
+#[test]
+fn invalid_item_is_reported_at_its_offset_in_the_input() {
+    let mut input = encode(&[Value::Bool(true)]);
+    // A reserved additional information of major type 0.
+    input.push(0x1c);
+    let values: Vec<_> =
+        stream::<CborSeq, _>(std::io::Cursor::new(input), Options::default()).collect();
+    assert_eq!(values.len(), 2);
+    match values.last() {
+        Some(Err(StreamError::Deserialize { message, .. })) => {
+            assert_eq!(message, "Invalid CBOR at byte 1")
+        }
+        v => panic!("{v:?}"),
+    }
+}
+
+#[test]
+fn keys_that_arent_strings_are_json_encoded() {
+    let input = encode(&[Value::Map(vec![
+        (
+            Value::Array(vec![Value::Integer(1.into()), Value::Text("a".into())]),
+            Value::Integer(2.into()),
+        ),
+        (Value::Integer(3.into()), Value::Bool(true)),
+    ])]);
+    common::assert_keys_json_encoded::<CborSeq>(input);
+}
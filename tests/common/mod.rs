@@ -0,0 +1,83 @@
+//! Builders and checks shared by the tests of the binary formats, which stream the same documents.
+
+// Each test crate only uses some of them.
+#![allow(dead_code)]
+
+use stream::{stream, FromReader, Index, Options, PathValue, PrimitiveValue, StreamError};
+
+pub fn key(k: &str) -> Index {
+    Index::Map(k.into())
+}
+
+pub fn leaf(path: Vec<Index>, value: PrimitiveValue) -> PathValue {
+    PathValue {
+        path,
+        value: Some(value),
+        count: None,
+    }
+}
+
+pub fn close(path: Vec<Index>) -> PathValue {
+    PathValue {
+        path,
+        value: None,
+        count: None,
+    }
+}
+
+/// Checks that `input`, `{"id": 1, "ok": true}` followed by `["x", <bytes 1 2>]` in `T`, is
+/// streamed as two documents.
+pub fn assert_two_documents<T: FromReader>(input: Vec<u8>) {
+    let options = Options::builder().with_doc_index(true).build();
+    let values: Vec<_> = stream::<T, _>(std::io::Cursor::new(input), options)
+        .map(Result::unwrap)
+        .collect();
+    assert_eq!(
+        values,
+        [
+            leaf(
+                vec![Index::Array(0), key("id")],
+                PrimitiveValue::Number(1.0)
+            ),
+            leaf(
+                vec![Index::Array(0), key("ok")],
+                PrimitiveValue::Boolean(true)
+            ),
+            close(vec![Index::Array(0), key("ok")]),
+            leaf(
+                vec![Index::Array(1), Index::Array(0)],
+                PrimitiveValue::String("x".into())
+            ),
+            leaf(
+                vec![Index::Array(1), Index::Array(1)],
+                PrimitiveValue::Bytes(vec![1, 2])
+            ),
+            close(vec![Index::Array(1), Index::Array(1)]),
+        ]
+    );
+}
+
+/// Checks that `input` in `T` without its last byte has `streamed` values before it's reported as
+/// truncated.
+pub fn assert_truncated<T: FromReader>(mut input: Vec<u8>, streamed: usize) {
+    input.pop();
+    let values: Vec<_> = stream::<T, _>(std::io::Cursor::new(input), Options::default()).collect();
+    assert_eq!(values.iter().filter(|v| v.is_ok()).count(), streamed);
+    match values.last() {
+        Some(Err(StreamError::Deserialize { message, .. })) => {
+            assert_eq!(message, "Truncated value at the end of the input")
+        }
+        v => panic!("{v:?}"),
+    }
+}
+
+/// Checks that `input`, the entries `[1, "a"]: 2` and `3: true` in `T`, has the keys `[1,"a"]` and
+/// `3`.
+pub fn assert_keys_json_encoded<T: FromReader>(input: Vec<u8>) {
+    let paths: Vec<_> = stream::<T, _>(std::io::Cursor::new(input), Options::default())
+        .map(|v| v.unwrap())
+        .filter(|v| v.value.is_some())
+        .map(|v| v.path)
+        .collect();
+    assert_eq!(paths, [vec![key(r#"[1,"a"]"#)], vec![key("3")]]);
+}
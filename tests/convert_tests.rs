@@ -0,0 +1,42 @@
+use stream::{convert, ConvertError, Json, StreamError, Yaml};
+
+fn to_json<T: stream::FromReader>(input: &str) -> Result<String, ConvertError<serde_json::Error>> {
+    let mut out = vec![];
+    convert::<T, _, _>(input.as_bytes(), &mut serde_json::Serializer::new(&mut out))?;
+    Ok(String::from_utf8(out).unwrap())
+}
+
+#[test]
+fn documents_become_a_sequence() {
+    assert_eq!(
+        to_json::<Json>(r#"{"a": [1, {}], "b": null} "s" 1.5"#).unwrap(),
+        r#"[{"a":[1,{}],"b":null},"s",1.5]"#
+    );
+    assert_eq!(to_json::<Json>("").unwrap(), "[]");
+    assert_eq!(
+        to_json::<Yaml>("a: [x, 2]\n---\n- true\n").unwrap(),
+        r#"[{"a":["x",2]},[true]]"#
+    );
+}
+
+#[test]
+fn errors_are_told_apart() {
+    assert!(matches!(
+        to_json::<Json>("1x"),
+        Err(ConvertError::Deserialize(StreamError::Deserialize {
+            line: 1,
+            column: 2,
+            ..
+        }))
+    ));
+    // Found while the document is being forwarded, so the serializer reports it.
+    assert!(matches!(
+        to_json::<Json>("{} [1,"),
+        Err(ConvertError::Serialize(e)) if e.to_string().starts_with("EOF while parsing")
+    ));
+    // JSON only has string keys.
+    assert!(matches!(
+        to_json::<Yaml>("? [1]\n: x\n"),
+        Err(ConvertError::Serialize(e)) if e.to_string().contains("key must be a string")
+    ));
+}
@@ -0,0 +1,94 @@
+use serde_json::json;
+use stream::{diff, stream_value, Diff, Index, Options, Path, PrimitiveValue};
+
+fn diffs(a: serde_json::Value, b: serde_json::Value) -> Vec<Diff> {
+    let a = stream_value(&a, Options::default());
+    let b = stream_value(&b, Options::default());
+    diff(a, b).collect::<Result<_, _>>().unwrap()
+}
+
+fn key(k: &str) -> Index {
+    Index::Map(k.to_string())
+}
+
+fn path(keys: &[&str]) -> Path {
+    keys.iter().map(|k| key(k)).collect()
+}
+
+#[test]
+fn equal_documents_have_no_diff() {
+    assert_eq!(diffs(json!({"a": [1, {}]}), json!({"a": [1, {}]})), vec![]);
+}
+
+#[test]
+fn reordered_keys_have_no_diff() {
+    assert_eq!(
+        diffs(
+            json!({"a": 1, "b": {"c": 2}}),
+            json!({"b": {"c": 2}, "a": 1})
+        ),
+        vec![]
+    );
+}
+
+#[test]
+fn added_keys() {
+    assert_eq!(
+        diffs(json!({"a": 1, "c": 3}), json!({"a": 1, "b": 2, "c": 3})),
+        vec![Diff::Added {
+            path: path(&["b"]),
+            value: PrimitiveValue::Number(2.0),
+        }]
+    );
+}
+
+#[test]
+fn removed_keys() {
+    assert_eq!(
+        diffs(json!({"a": 1, "b": {"x": null}}), json!({"a": 1})),
+        vec![Diff::Removed {
+            path: path(&["b", "x"]),
+            value: PrimitiveValue::Null,
+        }]
+    );
+}
+
+#[test]
+fn changed_values() {
+    // Reported as soon as both sides have had the path.
+    assert_eq!(
+        diffs(
+            json!({"a": [1, "x"], "b": true}),
+            json!({"b": false, "a": [1, "y"]})
+        ),
+        vec![
+            Diff::Changed {
+                path: path(&["b"]),
+                old: PrimitiveValue::Boolean(true),
+                new: PrimitiveValue::Boolean(false),
+            },
+            Diff::Changed {
+                path: vec![key("a"), Index::Array(1)],
+                old: PrimitiveValue::String("x".to_string()),
+                new: PrimitiveValue::String("y".to_string()),
+            },
+        ]
+    );
+}
+
+#[test]
+fn containers_replaced_by_leaves() {
+    assert_eq!(
+        diffs(json!({"a": {"b": 1}}), json!({"a": []})),
+        vec![
+            Diff::Removed {
+                path: path(&["a", "b"]),
+                value: PrimitiveValue::Number(1.0),
+            },
+            Diff::Added {
+                path: path(&["a"]),
+                value: PrimitiveValue::EmptyArray,
+            },
+        ]
+    );
+}
@@ -0,0 +1,94 @@
+mod common;
+
+use common::leaf;
+use stream::{
+    stream, Format, Framed, Index, Options, OptionsBuilder, PathValue, PrimitiveValue, StreamError,
+};
+
+fn frame(tag: u8, payload: &[u8]) -> Vec<u8> {
+    let mut out = vec![tag];
+    out.extend((payload.len() as u32).to_be_bytes());
+    out.extend(payload);
+    out
+}
+
+fn options() -> OptionsBuilder {
+    Options::builder()
+        .frame_tags(vec![(b'j', Format::Json), (1, Format::MsgpackStream)])
+        .with_doc_index(true)
+}
+
+fn leaves(input: Vec<u8>, options: Options) -> Vec<Result<PathValue, StreamError>> {
+    stream::<Framed, _>(std::io::Cursor::new(input), options)
+        .filter(|v| v.as_ref().map_or(true, |v| v.value.is_some()))
+        .collect()
+}
+
+#[test]
+fn each_frame_is_parsed_as_the_format_of_its_tag() {
+    let mut input = frame(b'j', br#"{"a": 1} ["ignored"]"#);
+    input.extend(frame(b'j', b"[true]"));
+    input.extend(frame(1, &rmp_serde::to_vec(&("x",)).unwrap()));
+    let values: Vec<_> = leaves(input, options().build())
+        .into_iter()
+        .map(Result::unwrap)
+        .collect();
+    assert_eq!(
+        values,
+        [
+            leaf(
+                vec![Index::Array(0), Index::Map("a".into())],
+                PrimitiveValue::Number(1.0)
+            ),
+            leaf(
+                vec![Index::Array(1), Index::Array(0)],
+                PrimitiveValue::Boolean(true)
+            ),
+            leaf(
+                vec![Index::Array(2), Index::Array(0)],
+                PrimitiveValue::String("x".into())
+            ),
+        ]
+    );
+}
+
+#[test]
+fn errors_in_a_frame_dont_lose_track_of_the_next_ones() {
+    let mut input = frame(b'x', b"1");
+    input.extend(frame(b'j', b"[1"));
+    input.extend(frame(b'j', b"2"));
+    let options = options().skip_errors(true).build();
+    let messages: Vec<_> = leaves(input, options)
+        .into_iter()
+        .map(|v| match v {
+            Ok(v) => format!("{:?}", v.value),
+            Err(StreamError::Deserialize { message, .. }) => message,
+            Err(e) => panic!("{e}"),
+        })
+        .collect();
+    assert_eq!(
+        messages,
+        [
+            "Frame 0 has the tag `x` of no format",
+            // Streamed before the error.
+            "Some(Number(1.0))",
+            "Frame 1 (JSON): EOF while parsing a list at line 1 column 2",
+            "Some(Number(2.0))",
+        ]
+    );
+}
+
+#[test]
+fn truncated_frame_ends_the_stream() {
+    let mut input = frame(b'j', b"1");
+    input.extend(&frame(b'j', b"[2, 3]")[..8]);
+    let options = options().skip_errors(true).build();
+    let values = leaves(input, options);
+    assert_eq!(values.len(), 2);
+    match values.last() {
+        Some(Err(StreamError::Deserialize { message, .. })) => {
+            assert_eq!(message, "Frame 1 is truncated at the end of the input")
+        }
+        v => panic!("{v:?}"),
+    }
+}
@@ -0,0 +1,95 @@
+//! A format that isn't self-describing: each line is the space-separated values of the fields
+//! `id` and `name`, which only `deserialize_struct` knows about.
+
+use std::io::{BufRead, BufReader, Read};
+
+use serde::de::{value::MapDeserializer, Deserializer, Error as _, IntoDeserializer, Visitor};
+use stream::{stream, Format, FromReader, Hint, Index, MultiDocDeserializer, Options, PathValue};
+
+struct Fields;
+
+impl FromReader for Fields {
+    const FORMAT: Format = Format::Raw;
+    type De<'de, R> = FieldsDocuments<R> where R: 'de + Read;
+
+    fn from_reader<'de, R: 'de + Read>(read: R) -> Self::De<'de, R> {
+        FieldsDocuments(read)
+    }
+}
+
+struct FieldsDocuments<R>(R);
+
+impl<'de, R: Read + 'de> MultiDocDeserializer<'de> for FieldsDocuments<R> {
+    const HINT: Hint = Hint::Struct {
+        name: "Record",
+        fields: &["id", "name"],
+    };
+    type Error = serde_json::Error;
+    type Iterator<T: serde::Deserialize<'de>> =
+        Box<dyn Iterator<Item = Result<T, Self::Error>> + 'de>;
+
+    fn into_multidoc_iter<T: serde::Deserialize<'de>>(self) -> Self::Iterator<T> {
+        Box::new(BufReader::new(self.0).lines().map(|line| {
+            let line = line.map_err(serde_json::Error::io)?;
+            T::deserialize(Line(line))
+        }))
+    }
+}
+
+struct Line(String);
+
+impl<'de> Deserializer<'de> for Line {
+    type Error = serde_json::Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, _: V) -> Result<V::Value, Self::Error> {
+        Err(Self::Error::custom("the fields have to be known"))
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        _: &'static str,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        let values: Vec<String> = self.0.split(' ').map(String::from).collect();
+        let entries = fields
+            .iter()
+            .zip(values)
+            .map(|(field, value)| (*field, value.into_deserializer()));
+        visitor.visit_map(MapDeserializer::new(entries))
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string bytes byte_buf option
+        unit unit_struct newtype_struct seq tuple tuple_struct map enum identifier ignored_any
+    }
+}
+
+#[test]
+fn struct_hint_drives_the_deserializer() {
+    let input: &[u8] = b"1 a\n2 b\n";
+    let values: Vec<_> = stream::<Fields, _>(input, Options::default())
+        .map(Result::unwrap)
+        .collect();
+    let leaf = |key: &str, value: &str| PathValue {
+        path: vec![Index::Map(key.into())],
+        value: Some(stream::PrimitiveValue::String(value.into())),
+        count: None,
+    };
+    let close = || PathValue {
+        path: vec![Index::Map("name".into())],
+        value: None,
+        count: None,
+    };
+    assert_eq!(
+        values,
+        [
+            leaf("id", "1"),
+            leaf("name", "a"),
+            close(),
+            leaf("id", "2"),
+            leaf("name", "b"),
+            close(),
+        ]
+    );
+}
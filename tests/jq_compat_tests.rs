@@ -0,0 +1,28 @@
+use std::{
+    io::Write,
+    process::{Command, Stdio},
+};
+
+/// `jq_compat/jq-1.6.out` is the output of `jq -c --stream . jq_compat/corpus.json` with jq 1.6.
+/// It's compared here rather than in `tests/cmd`, which can't check backslashes in the output.
+#[test]
+fn jq_compat_matches_jq() {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_stream"))
+        .arg("--jq-compat")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(include_bytes!("jq_compat/corpus.json"))
+        .unwrap();
+    let output = child.wait_with_output().unwrap();
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        include_str!("jq_compat/jq-1.6.out")
+    );
+}
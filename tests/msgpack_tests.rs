@@ -0,0 +1,38 @@
+mod common;
+
+use serde::Serialize;
+use stream::MsgpackStream;
+
+/// Serializes as MessagePack binary rather than an array of numbers.
+struct Bytes(&'static [u8]);
+
+impl Serialize for Bytes {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_bytes(self.0)
+    }
+}
+
+/// `{"id": 1, "ok": true}` followed by `["x", <bytes 1 2>]`.
+fn frames() -> Vec<u8> {
+    let mut out = rmp_serde::to_vec(&serde_json::json!({"id": 1, "ok": true})).unwrap();
+    out.extend(rmp_serde::to_vec(&("x", Bytes(&[1, 2]))).unwrap());
+    out
+}
+
+#[test]
+fn msgpack_values_are_documents() {
+    common::assert_two_documents::<MsgpackStream>(frames());
+}
+
+#[test]
+fn truncated_trailing_value_is_an_error() {
+    common::assert_truncated::<MsgpackStream>(frames(), 4);
+}
+
+#[test]
+fn keys_that_arent_strings_are_json_encoded() {
+    let composite = std::collections::BTreeMap::from([((1, "a".to_string()), 2)]);
+    let mut input = rmp_serde::to_vec(&composite).unwrap();
+    input.extend(rmp_serde::to_vec(&std::collections::BTreeMap::from([(3, true)])).unwrap());
+    common::assert_keys_json_encoded::<MsgpackStream>(input);
+}
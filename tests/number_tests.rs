@@ -0,0 +1,172 @@
+//! Numbers of each format, as they arrive at the visitor.
+
+use stream::{
+    stream, FromReader, Hocon, Json, Options, PrimitiveValue, Properties, Ron, Yaml, YamlScalars,
+};
+
+/// The leaves of `input` parsed as `T`, which must be a single document.
+fn leaves<T: FromReader>(input: &str, options: Options) -> Vec<PrimitiveValue> {
+    stream::<T, _>(std::io::Cursor::new(input.to_string()), options)
+        .map(Result::unwrap)
+        .filter_map(|value| value.value)
+        .collect()
+}
+
+fn numbers(values: &[f64]) -> Vec<PrimitiveValue> {
+    values.iter().copied().map(PrimitiveValue::Number).collect()
+}
+
+#[test]
+fn json_numbers() {
+    assert_eq!(
+        leaves::<Json>(
+            "[1e2, 1E-2, 2.5e+3, -0, -0.0, 0.1, 9007199254740992]",
+            Options::default()
+        ),
+        numbers(&[100.0, 0.01, 2500.0, -0.0, -0.0, 0.1, 9007199254740992.0])
+    );
+    assert_eq!(
+        leaves::<Json>(
+            "[12345678901234567890, -9223372036854775808, 123456789012345678901234]",
+            Options::default()
+        ),
+        [
+            PrimitiveValue::Integer(12345678901234567890),
+            PrimitiveValue::Integer(-9223372036854775808),
+            PrimitiveValue::Number(123456789012345678901234.0),
+        ]
+    );
+}
+
+#[test]
+fn yaml_numbers() {
+    assert_eq!(
+        leaves::<Yaml>(
+            "[0x1F, 0o17, 0b101, +12, 1.5e3, 1e3, 1.0, -0.0, .inf, -.inf]",
+            Options::default()
+        ),
+        numbers(&[
+            31.0,
+            15.0,
+            5.0,
+            12.0,
+            1500.0,
+            1000.0,
+            1.0,
+            -0.0,
+            f64::INFINITY,
+            f64::NEG_INFINITY
+        ])
+    );
+    assert!(matches!(
+        leaves::<Yaml>(".nan", Options::default())[..],
+        [PrimitiveValue::Number(v)] if v.is_nan()
+    ));
+    assert_eq!(
+        leaves::<Yaml>("12345678901234567890", Options::default()),
+        [PrimitiveValue::Integer(12345678901234567890)]
+    );
+    // Not numbers in YAML 1.2.
+    assert_eq!(
+        leaves::<Yaml>("[1_000, '1', 1:30]", Options::default()),
+        ["1_000", "1", "1:30"].map(|s| PrimitiveValue::String(s.into()))
+    );
+}
+
+#[test]
+fn yaml_strict_scalars_are_strings_unless_written_back_the_same() {
+    let strict = || Options::builder().yaml_scalars(YamlScalars::Strict).build();
+    let strings = |values: &[&str]| -> Vec<_> {
+        values
+            .iter()
+            .map(|s| PrimitiveValue::String(s.to_string()))
+            .collect()
+    };
+    // YAML 1.1 booleans aren't booleans in YAML 1.2 in the first place.
+    let norway = "[no, yes, on, off, NO, y]";
+    let expected = strings(&["no", "yes", "on", "off", "NO", "y"]);
+    assert_eq!(leaves::<Yaml>(norway, Options::default()), expected);
+    assert_eq!(leaves::<Yaml>(norway, strict()), expected);
+    let versions = "{a: 1.10, b: 1.0, c: 2, d: 1.2.3, e: '1.10'}";
+    assert_eq!(
+        leaves::<Yaml>(versions, Options::default()),
+        [
+            PrimitiveValue::Number(1.1),
+            PrimitiveValue::Number(1.0),
+            PrimitiveValue::Number(2.0),
+            PrimitiveValue::String("1.2.3".into()),
+            PrimitiveValue::String("1.10".into()),
+        ]
+    );
+    assert_eq!(
+        leaves::<Yaml>(versions, strict()),
+        [
+            PrimitiveValue::String("1.10".into()),
+            PrimitiveValue::String("1.0".into()),
+            PrimitiveValue::Number(2.0),
+            PrimitiveValue::String("1.2.3".into()),
+            PrimitiveValue::String("1.10".into()),
+        ]
+    );
+    assert_eq!(
+        leaves::<Yaml>(
+            "[0x1F, +1, 1e3, .inf, ~, True, -3, 0.5, true, null]",
+            strict()
+        ),
+        [
+            strings(&["0x1F", "+1", "1e3", ".inf", "~", "True"]),
+            vec![
+                PrimitiveValue::Number(-3.0),
+                PrimitiveValue::Number(0.5),
+                PrimitiveValue::Boolean(true),
+                PrimitiveValue::Null,
+            ],
+        ]
+        .concat()
+    );
+}
+
+#[test]
+fn hocon_numbers() {
+    assert_eq!(
+        leaves::<Hocon>(
+            "a = 1e2, b = 1.5, c = -3, d = 9007199254740993",
+            Options::default()
+        ),
+        [
+            PrimitiveValue::Number(100.0),
+            PrimitiveValue::Number(1.5),
+            PrimitiveValue::Number(-3.0),
+            PrimitiveValue::Integer(9007199254740993),
+        ]
+    );
+}
+
+#[test]
+fn ron_numbers() {
+    assert_eq!(
+        leaves::<Ron>(
+            "(a: 0x1F, b: 0o17, c: 0b101, d: 10_000, e: 1e2, f: -2.5)",
+            Options::default()
+        ),
+        numbers(&[31.0, 15.0, 5.0, 10000.0, 100.0, -2.5])
+    );
+    assert_eq!(
+        leaves::<Ron>("[12345678901234567890]", Options::default()),
+        [PrimitiveValue::Integer(12345678901234567890)]
+    );
+}
+
+#[test]
+fn inferred_numbers() {
+    let options = Options::builder().infer_types(true).build();
+    assert_eq!(
+        leaves::<Properties>("a=1e2\nb=-0\nc=12345678901234567890\nd=0x1F\n", options),
+        [
+            PrimitiveValue::Number(100.0),
+            PrimitiveValue::Number(-0.0),
+            PrimitiveValue::Integer(12345678901234567890),
+            PrimitiveValue::String("0x1F".into()),
+        ]
+    );
+}
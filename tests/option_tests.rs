@@ -0,0 +1,87 @@
+//! A format whose values are `Option`s, which its deserializer visits with `visit_some` and
+//! `visit_none` like one that tells `null` and missing values apart would.
+
+use std::io::{BufReader, Read};
+
+use serde::de::{value::SeqDeserializer, Deserializer, IntoDeserializer, Visitor};
+use stream::{stream, Format, FromReader, Index, MultiDocDeserializer, Options, PathValue};
+
+/// Each byte of the input is a document: `0` is `None`, and any other `n` is `Some` of the
+/// numbers from 1 to `n`, wrapped in another `Some` if `n` is even.
+struct Optional;
+
+impl FromReader for Optional {
+    const FORMAT: Format = Format::Raw;
+    type De<'de, R> = OptionDocuments<R> where R: 'de + Read;
+
+    fn from_reader<'de, R: 'de + Read>(read: R) -> Self::De<'de, R> {
+        OptionDocuments(read)
+    }
+}
+
+struct OptionDocuments<R>(R);
+
+impl<'de, R: Read + 'de> MultiDocDeserializer<'de> for OptionDocuments<R> {
+    type Error = serde_json::Error;
+    type Iterator<T: serde::Deserialize<'de>> =
+        Box<dyn Iterator<Item = Result<T, Self::Error>> + 'de>;
+
+    fn into_multidoc_iter<T: serde::Deserialize<'de>>(self) -> Self::Iterator<T> {
+        Box::new(BufReader::new(self.0).bytes().map(|byte| {
+            let byte = byte.map_err(serde_json::Error::io)?;
+            T::deserialize(Opt(byte, byte % 2 == 0))
+        }))
+    }
+}
+
+/// The document of a byte, possibly wrapped in one more `Some`.
+struct Opt(u8, bool);
+
+impl<'de> Deserializer<'de> for Opt {
+    type Error = serde_json::Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        match self {
+            Opt(0, _) => visitor.visit_none(),
+            Opt(n, true) => visitor.visit_some(Opt(n, false)),
+            Opt(n, false) => visitor.visit_some(SeqDeserializer::new(
+                (1..=u64::from(n)).map(IntoDeserializer::into_deserializer),
+            )),
+        }
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string bytes byte_buf option
+        unit unit_struct newtype_struct seq tuple tuple_struct map struct enum identifier
+        ignored_any
+    }
+}
+
+#[test]
+fn some_is_streamed_like_its_value() {
+    let input: &[u8] = &[1, 0, 2];
+    let values: Vec<_> = stream::<Optional, _>(input, Options::default())
+        .map(Result::unwrap)
+        .collect();
+    let leaf = |path: Vec<usize>, value: Option<f64>| PathValue {
+        path: path.into_iter().map(Index::Array).collect(),
+        value: Some(value.map_or(stream::PrimitiveValue::Null, stream::PrimitiveValue::Number)),
+        count: None,
+    };
+    let close = |i| PathValue {
+        path: vec![Index::Array(i)],
+        value: None,
+        count: None,
+    };
+    assert_eq!(
+        values,
+        [
+            leaf(vec![0], Some(1.0)),
+            close(0),
+            leaf(vec![], None),
+            leaf(vec![0], Some(1.0)),
+            leaf(vec![1], Some(2.0)),
+            close(1),
+        ]
+    );
+}
@@ -0,0 +1,45 @@
+use stream::{
+    path::{format_path, parse_path},
+    Index,
+};
+
+#[test]
+fn parse() {
+    assert_eq!(parse_path("."), Ok(vec![]));
+    assert_eq!(
+        parse_path(".a[0].b_2"),
+        Ok(vec![
+            Index::Map("a".into()),
+            Index::Array(0),
+            Index::Map("b_2".into())
+        ])
+    );
+    assert_eq!(
+        parse_path(r#"[1]."a b".["c\"d"]"#),
+        Ok(vec![
+            Index::Array(1),
+            Index::Map("a b".into()),
+            Index::Map("c\"d".into())
+        ])
+    );
+    assert_eq!(parse_path(".[0]"), Ok(vec![Index::Array(0)]));
+}
+
+#[test]
+fn parse_error() {
+    for s in ["", "a", ".a.", "..", ".0", "[x]", "[0", r#"."a"#, ".a b"] {
+        assert!(parse_path(s).is_err(), "{s:?} should be rejected");
+    }
+}
+
+#[test]
+fn format_round_trip() {
+    for s in [".", ".a[0].b", r#".a."b c"[1]."""#, r#"."0a".["é"]"#] {
+        let path = parse_path(s).unwrap();
+        assert_eq!(parse_path(&format_path(&path)), Ok(path));
+    }
+    assert_eq!(
+        format_path(&[Index::Map("a b".into()), Index::Array(3)]),
+        r#"."a b"[3]"#
+    );
+}
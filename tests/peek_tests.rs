@@ -0,0 +1,45 @@
+use std::io::{self, Read};
+
+use stream::{peek::PeekReader, stream, try_detect_format, Format, Options, Yaml};
+
+/// Reads a few bytes at a time and can't be rewound, like a pipe.
+struct Pipe<'a>(&'a [u8]);
+
+impl Read for Pipe<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let len = buf.len().min(self.0.len()).min(3);
+        (&mut self.0).take(len as u64).read(buf)
+    }
+}
+
+#[test]
+fn peeked_bytes_are_read_again() {
+    let mut input = PeekReader::new(Pipe(b"0123456789"));
+    assert_eq!(input.peek(4).unwrap(), b"0123");
+    assert_eq!(input.peek(2).unwrap(), b"01");
+    let mut buf = [0; 2];
+    input.read_exact(&mut buf).unwrap();
+    assert_eq!(&buf, b"01");
+    assert_eq!(input.peek(5).unwrap(), b"23456");
+    assert_eq!(input.peek(u64::MAX).unwrap(), b"23456789");
+    let mut rest = vec![];
+    input.read_to_end(&mut rest).unwrap();
+    assert_eq!(rest, b"23456789");
+    assert_eq!(input.peek(1).unwrap(), b"");
+}
+
+#[test]
+fn format_detected_from_a_pipe_is_parsed_from_its_start() {
+    let mut input = PeekReader::new(Pipe(b"a: 1\nb: [2, x]\n"));
+    let format = try_detect_format(input.peek(u64::MAX).unwrap());
+    assert_eq!(format, Some(Format::Yaml));
+    let leaves: Vec<_> = stream::<Yaml, _>(input, Options::default())
+        .map(Result::unwrap)
+        .filter_map(|v| Some((stream::path::format_path(&v.path), v.value?)))
+        .map(|(path, value)| format!("{path} {value:?}"))
+        .collect();
+    assert_eq!(
+        leaves,
+        [".a Number(1.0)", ".b[0] Number(2.0)", ".b[1] String(\"x\")"]
+    );
+}
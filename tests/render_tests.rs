@@ -0,0 +1,131 @@
+use stream::{
+    render::{Printer, RenderOptions},
+    Index, PathValue, PrimitiveValue,
+};
+
+fn render(value: &PathValue, options: RenderOptions) -> String {
+    let mut printer = Printer::new(vec![], options);
+    printer.print(value).unwrap();
+    String::from_utf8(printer.finish().unwrap()).unwrap()
+}
+
+#[test]
+fn strings_are_escaped_like_jq() {
+    let value = PathValue {
+        path: vec![Index::Map("a\u{1}\"\\".into())],
+        value: Some(PrimitiveValue::String("\u{7f}\u{8}\u{c}\t\n/é".into())),
+        count: None,
+    };
+    assert_eq!(
+        render(&value, RenderOptions::default()),
+        "[[\"a\\u0001\\\"\\\\\"],\"\\u007f\\b\\f\\t\\n/é\"]\n"
+    );
+}
+
+#[test]
+fn ascii_output_escapes_keys_and_values() {
+    let value = PathValue {
+        path: vec![Index::Map("é😀".into()), Index::Array(0)],
+        value: Some(PrimitiveValue::String("ü".into())),
+        count: None,
+    };
+    let options = RenderOptions {
+        ascii: true,
+        ..Default::default()
+    };
+    assert_eq!(
+        render(&value, options),
+        "[[\"\\u00e9\\ud83d\\ude00\",0],\"\\u00fc\"]\n"
+    );
+}
+
+#[test]
+fn non_finite_numbers_are_valid_json() {
+    let leaf = |v| PathValue {
+        path: vec![],
+        value: Some(PrimitiveValue::Number(v)),
+        count: None,
+    };
+    for jq_floats in [false, true] {
+        let options = RenderOptions {
+            jq_floats,
+            ..Default::default()
+        };
+        assert_eq!(render(&leaf(f64::NAN), options.clone()), "[[],null]\n");
+        for v in [f64::INFINITY, f64::NEG_INFINITY] {
+            let rendered = render(&leaf(v), options.clone());
+            let parsed: serde_json::Value = serde_json::from_str(&rendered).unwrap();
+            assert_eq!(
+                parsed[1].as_f64(),
+                Some(v.clamp(f64::MIN, f64::MAX)),
+                "{rendered}"
+            );
+        }
+    }
+}
+
+#[test]
+fn with_type_names_every_kind_of_leaf() {
+    let options = RenderOptions {
+        with_type: true,
+        ..Default::default()
+    };
+    let cases = [
+        (PrimitiveValue::Null, "null"),
+        (PrimitiveValue::Boolean(true), "boolean"),
+        (PrimitiveValue::Number(1.5), "number"),
+        (PrimitiveValue::Integer(1 << 60), "number"),
+        (PrimitiveValue::String("x".into()), "string"),
+        (PrimitiveValue::Bytes(vec![1]), "string"),
+        (PrimitiveValue::EmptyArray, "array"),
+        (PrimitiveValue::EmptyObject, "object"),
+    ];
+    for (leaf, name) in cases {
+        let value = PathValue {
+            path: vec![Index::Map("a".into())],
+            value: Some(leaf),
+            count: None,
+        };
+        let rendered = render(&value, options.clone());
+        let parsed: serde_json::Value = serde_json::from_str(&rendered).unwrap();
+        assert_eq!(parsed[2], name, "{rendered}");
+    }
+    let close = |index| PathValue {
+        path: vec![Index::Map("a".into()), index],
+        value: None,
+        count: None,
+    };
+    assert_eq!(
+        render(&close(Index::Array(1)), options.clone()),
+        "[[\"a\",1],\"array\"]\n"
+    );
+    assert_eq!(
+        render(&close(Index::Map("b".into())), options),
+        "[[\"a\",\"b\"],\"object\"]\n"
+    );
+}
+
+#[test]
+fn raw_leaves_are_written_without_quotes() {
+    let options = RenderOptions {
+        line_terminator: stream::render::LineTerminator::Nul,
+        ..Default::default()
+    };
+    let mut printer = Printer::new(vec![], options);
+    for leaf in [
+        PrimitiveValue::String("a \"b\"\nc".into()),
+        PrimitiveValue::Bytes(vec![1, 2]),
+        PrimitiveValue::Number(1.5),
+        PrimitiveValue::Null,
+    ] {
+        printer.print_raw_leaf(&leaf).unwrap();
+    }
+    let error = printer
+        .print_raw_leaf(&PrimitiveValue::String("a\0b".into()))
+        .unwrap_err();
+    assert_eq!(error.kind(), std::io::ErrorKind::InvalidData);
+    assert_eq!(
+        printer.finish().unwrap(),
+        b"a \"b\"\nc\0AQI=\x001.5\0null\0"
+    );
+}
@@ -0,0 +1,47 @@
+mod common;
+
+use common::{close, key, leaf};
+use serde::{ser::SerializeMap, Serialize, Serializer};
+use stream::{stream, Index, Options, PrimitiveValue, Smile};
+
+/// `{"name": "x", "data": <bytes 1 2 3>, "n": [1, 2.5]}`, with `data` as Smile binary.
+struct Document;
+
+impl Serialize for Document {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        struct Bytes;
+        impl Serialize for Bytes {
+            fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                serializer.serialize_bytes(&[1, 2, 3])
+            }
+        }
+        let mut map = serializer.serialize_map(Some(3))?;
+        map.serialize_entry("name", "x")?;
+        map.serialize_entry("data", &Bytes)?;
+        map.serialize_entry("n", &[1.0, 2.5])?;
+        map.end()
+    }
+}
+
+#[test]
+fn smile_round_trips() {
+    let mut serializer = serde_smile::Serializer::new(vec![]);
+    Document.serialize(&mut serializer).unwrap();
+    true.serialize(&mut serializer).unwrap();
+    let input = serializer.into_inner();
+    let values: Vec<_> = stream::<Smile, _>(std::io::Cursor::new(input), Options::default())
+        .map(Result::unwrap)
+        .collect();
+    assert_eq!(
+        values,
+        [
+            leaf(vec![key("name")], PrimitiveValue::String("x".into())),
+            leaf(vec![key("data")], PrimitiveValue::Bytes(vec![1, 2, 3])),
+            leaf(vec![key("n"), Index::Array(0)], PrimitiveValue::Number(1.0)),
+            leaf(vec![key("n"), Index::Array(1)], PrimitiveValue::Number(2.5)),
+            close(vec![key("n"), Index::Array(1)]),
+            close(vec![key("n")]),
+            leaf(vec![], PrimitiveValue::Boolean(true)),
+        ]
+    );
+}
@@ -0,0 +1,785 @@
+use stream::{stream, Format, Json, Options, StreamError};
+
+#[test]
+fn deserialize_error_has_location() {
+    let input: &[u8] = b"{\"a\": 1}\n{\"a\": }";
+    let result: Vec<_> = stream::<Json, _>(input, Options::default()).collect();
+    match result.last() {
+        Some(Err(StreamError::Deserialize {
+            format: Format::Json,
+            line: 2,
+            column,
+            ..
+        })) => assert!(*column > 0),
+        _ => panic!("expected a deserialization error"),
+    }
+}
+
+#[test]
+fn depth_exceeded_is_reported_once() {
+    let input: &[u8] = b"[[[1]]]";
+    let options = Options::builder().max_depth(2).build();
+    let mut errors = stream::<Json, _>(input, options).filter_map(Result::err);
+    assert!(matches!(
+        errors.next(),
+        Some(StreamError::DepthExceeded { max_depth: 2, .. })
+    ));
+    assert!(errors.next().is_none());
+}
+
+#[test]
+fn too_many_keys_is_reported_with_the_path_of_the_object() {
+    let input: &[u8] = br#"{"a": {"b": 1, "c": 2}} {"a": {"b": 1, "c": 2, "d": 3}}"#;
+    let options = Options::builder().max_keys_per_object(2).build();
+    let values: Vec<_> = stream::<Json, _>(input, options).collect();
+    assert_eq!(values.iter().filter(|v| v.is_ok()).count(), 4 + 2);
+    match values.last() {
+        Some(Err(StreamError::TooManyKeys { path, max_keys: 2 })) => {
+            assert_eq!(path, &[stream::Index::Map("a".into())])
+        }
+        v => panic!("{v:?}"),
+    }
+}
+
+#[test]
+fn too_long_array_is_reported_with_the_path_of_the_array() {
+    let input: &[u8] = b"[[1, 2], [3, 4, 5]]";
+    let options = Options::builder().max_array_length(2).build();
+    let values: Vec<_> = stream::<Json, _>(input, options).collect();
+    assert_eq!(values.iter().filter(|v| v.is_ok()).count(), 3 + 2);
+    match values.last() {
+        Some(Err(StreamError::ArrayTooLong {
+            path,
+            max_length: 2,
+        })) => assert_eq!(path, &[stream::Index::Array(1)]),
+        v => panic!("{v:?}"),
+    }
+}
+
+#[test]
+fn merged_sources_are_prefixed() {
+    use stream::{stream_merged, Index};
+    let readers: Vec<Box<dyn std::io::Read + Send>> = vec![
+        Box::new(&b"[1, 2]"[..]),
+        Box::new(&b"{\"a\": true} {\"b\""[..]),
+    ];
+    let mut values = [vec![], vec![]];
+    let mut errors = vec![];
+    for v in stream_merged::<Json>(readers, Options::default()) {
+        match v {
+            Ok(v) => match v.path.split_first() {
+                Some((Index::Array(i), rest)) => values[*i].push(rest.to_vec()),
+                _ => panic!("path without a source index"),
+            },
+            Err(e) => errors.push(e),
+        }
+    }
+    assert_eq!(
+        values[0],
+        vec![
+            vec![Index::Array(0)],
+            vec![Index::Array(1)],
+            vec![Index::Array(1)]
+        ]
+    );
+    assert_eq!(
+        values[1],
+        vec![vec![Index::Map("a".into())], vec![Index::Map("a".into())]]
+    );
+    assert!(matches!(
+        errors.as_slice(),
+        [StreamError::InSource { index: 1, .. }]
+    ));
+}
+
+#[test]
+fn path_values_compare_numbers_bitwise() {
+    use std::collections::HashSet;
+    use stream::{Index, PathValue, PrimitiveValue};
+    let leaf = |i, v| PathValue {
+        path: vec![Index::Array(i)],
+        value: Some(PrimitiveValue::Number(v)),
+        count: None,
+    };
+    assert_eq!(leaf(0, f64::NAN), leaf(0, f64::NAN));
+    assert_ne!(leaf(0, 0.0), leaf(0, -0.0));
+    assert_ne!(leaf(0, 1.0), leaf(1, 1.0));
+
+    let input: &[u8] = b"[1, 1, null] [1]";
+    let distinct: HashSet<_> = stream::<Json, _>(input, Options::default())
+        .map(Result::unwrap)
+        .collect();
+    assert_eq!(distinct.len(), 5);
+    assert!(distinct.contains(&leaf(1, 1.0)));
+}
+
+#[test]
+fn approx_eq_allows_rounding_errors() {
+    use stream::PrimitiveValue::{self, Number};
+    assert!(Number(0.1 + 0.2).approx_eq(&Number(0.3), 1e-9));
+    assert!(Number(0.0).approx_eq(&Number(-0.0), 0.0));
+    assert!(!Number(1.0).approx_eq(&Number(1.1), 1e-9));
+    assert!(Number(f64::INFINITY).approx_eq(&Number(f64::INFINITY), 1e-9));
+    assert!(!Number(f64::INFINITY).approx_eq(&Number(f64::MAX), 1e-9));
+    assert!(!Number(1.0).approx_eq(&PrimitiveValue::String("1".into()), 1.0));
+    assert!(PrimitiveValue::Null.approx_eq(&PrimitiveValue::Null, 0.0));
+}
+
+#[test]
+fn max_documents_stops_reading() {
+    let input: &[u8] = b"[1] [2] {";
+    let options = Options::builder().max_documents(2).build();
+    let values: Vec<_> = stream::<Json, _>(input, options).collect();
+    assert_eq!(values.len(), 4);
+    assert!(values.iter().all(Result::is_ok));
+}
+
+#[test]
+fn detect_format_prefers_json() {
+    use stream::detect_format;
+    assert_eq!(detect_format(b"{\"n\": 1e400}\n[1] \"s\""), Format::Json);
+    assert_eq!(detect_format(b"{n: 1}"), Format::Yaml);
+    assert_eq!(detect_format(b"a: 1\n---\nb: 2\n"), Format::Yaml);
+}
+
+#[test]
+fn detect_format_tells_ndjson_from_json() {
+    use stream::detect_format;
+    assert_eq!(
+        detect_format(b"{\"a\": 1}\n\n[2]\r\n\"s\"\n"),
+        Format::Jsonl
+    );
+    assert_eq!(
+        detect_format(b"[\n  {\"a\": 1},\n  {\"a\": 2}\n]\n"),
+        Format::Json
+    );
+    assert_eq!(detect_format(b"{\"a\": 1}\n"), Format::Json);
+    assert_eq!(
+        detect_format(b"{\"a\": 1} {\"a\": 2}\n{\"a\": 3}"),
+        Format::Json
+    );
+    assert_eq!(detect_format(b"{\"a\":\n1}\n{\"a\": 2}"), Format::Json);
+    assert_eq!(detect_format(b""), Format::Json);
+}
+
+#[test]
+fn stream_value_matches_stream() {
+    use stream::stream_value;
+    let input = r#"{"a": [1, {"b": null}], "c": [], "d": "e"}"#;
+    let value: serde_json::Value = serde_json::from_str(input).unwrap();
+    let from_value: Vec<_> = stream_value(&value, Options::default())
+        .map(Result::unwrap)
+        .collect();
+    let from_reader: Vec<_> = stream::<Json, _>(input.as_bytes(), Options::default())
+        .map(Result::unwrap)
+        .collect();
+    assert_eq!(from_value, from_reader);
+}
+
+#[test]
+fn join_stops_the_thread() {
+    /// Endless `[1]` documents.
+    struct Endless;
+    impl std::io::Read for Endless {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            for (i, c) in buf.iter_mut().enumerate() {
+                *c = b"[1]\n"[i % 4];
+            }
+            Ok(buf.len() / 4 * 4)
+        }
+    }
+    let mut values = stream::<Json, _>(Endless, Options::default());
+    assert!(values.by_ref().take(3).all(|value| value.is_ok()));
+    assert!(values.join().is_ok());
+}
+
+#[test]
+fn extract_doesnt_wait_for_the_rest_of_the_input() {
+    use std::{sync::mpsc, time::Duration};
+    use stream::{extract, path::parse_path};
+    /// Sends the start of a document, and then nothing until the sender of `unblock` is dropped.
+    struct Stalled {
+        sent: bool,
+        unblock: mpsc::Receiver<()>,
+    }
+    impl std::io::Read for Stalled {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            if std::mem::replace(&mut self.sent, true) {
+                let _ = self.unblock.recv();
+                return Ok(0);
+            }
+            let start = br#"{"a": [1], "#;
+            buf[..start.len()].copy_from_slice(start);
+            Ok(start.len())
+        }
+    }
+    let (_sender, unblock) = mpsc::channel();
+    let (done, extracted) = mpsc::channel();
+    std::thread::spawn(move || {
+        let read = Stalled {
+            sent: false,
+            unblock,
+        };
+        let at = parse_path(".a").unwrap();
+        let _ = done.send(extract::<Json, Vec<u32>, _>(read, &at, Options::default()).unwrap());
+    });
+    assert_eq!(extracted.recv_timeout(Duration::from_secs(10)), Ok(vec![1]));
+}
+
+#[test]
+fn stalled_input_times_out() {
+    use std::{sync::mpsc, time::Duration};
+    use stream::timeout::TimeoutReader;
+    use stream::{FromReader, RawLines};
+    /// Sends `start`, and then nothing until the sender of `unblock` is dropped.
+    struct Stalled {
+        start: &'static [u8],
+        sent: bool,
+        unblock: mpsc::Receiver<()>,
+    }
+    impl std::io::Read for Stalled {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            if std::mem::replace(&mut self.sent, true) {
+                let _ = self.unblock.recv();
+                return Ok(0);
+            }
+            buf[..self.start.len()].copy_from_slice(self.start);
+            Ok(self.start.len())
+        }
+    }
+    fn check<T: FromReader>(start: &'static [u8], options: Options) {
+        let (_sender, unblock) = mpsc::channel();
+        let stalled = Stalled {
+            start,
+            sent: false,
+            unblock,
+        };
+        let read = TimeoutReader::new(stalled, Duration::from_millis(50));
+        // Not waiting forever for the end of a stream that goes on after the timeout.
+        let result: Vec<_> = stream::<T, _>(read, options).take(3).collect();
+        match &result[..] {
+            [Ok(_), Err(StreamError::Io(e))] => {
+                assert_eq!(e.kind(), std::io::ErrorKind::TimedOut)
+            }
+            _ => panic!("expected a timeout after the first value, got {result:?}"),
+        }
+    }
+    check::<Json>(b"[1,", Options::default());
+    // Not a bad line to skip.
+    let options = Options::builder().skip_errors(true).build();
+    check::<RawLines>(b"a\n", options);
+}
+
+#[test]
+fn io_errors_end_the_stream_in_every_format() {
+    use stream::{FromReader, Jsonc, Kv, Properties, RawLines};
+    /// `valid`, then an I/O error, then `valid` again and the end of the input.
+    struct Failing {
+        valid: &'static [u8],
+        reads: usize,
+    }
+    impl std::io::Read for Failing {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            self.reads += 1;
+            match self.reads {
+                2 => return Err(std::io::Error::other("broken pipe")),
+                4.. => return Ok(0),
+                _ => {}
+            }
+            let n = self.valid.len().min(buf.len());
+            buf[..n].copy_from_slice(&self.valid[..n]);
+            Ok(n)
+        }
+    }
+    fn check<T: FromReader>(valid: &'static [u8]) {
+        let options = Options::builder().skip_errors(true).build();
+        let read = Failing { valid, reads: 0 };
+        let result: Vec<_> = stream::<T, _>(read, options).collect();
+        match result.last() {
+            Some(Err(StreamError::Io(e))) => assert_eq!(e.to_string(), "broken pipe"),
+            _ => panic!(
+                "expected an I/O error at the end in {:?}, got {result:?}",
+                T::FORMAT
+            ),
+        }
+    }
+    check::<RawLines>(b"a\n");
+    check::<Kv>(b"a: 1\n");
+    check::<Jsonc>(b"[1]\n");
+    check::<Properties>(b"a=1\n");
+}
+
+#[test]
+fn walk_matches_stream() {
+    use serde_json::{json, Value};
+    use stream::walk;
+    /// A deterministic pseudo-random value, with nesting up to `depth`.
+    fn arbitrary(seed: &mut u64, depth: usize) -> Value {
+        *seed = seed
+            .wrapping_mul(6364136223846793005)
+            .wrapping_add(1442695040888963407);
+        let n = (*seed >> 33) as usize;
+        match n % if depth == 0 { 5 } else { 7 } {
+            0 => Value::Null,
+            1 => json!(n.is_multiple_of(2)),
+            2 => json!(n as f64 / 7.0),
+            3 => json!(n as i64 - (1 << 30)),
+            4 => json!(format!("s{}", n % 10)),
+            5 => (0..n % 4).map(|_| arbitrary(seed, depth - 1)).collect(),
+            _ => (0..n % 4)
+                .map(|i| (format!("k{i}"), arbitrary(seed, depth - 1)))
+                .collect::<serde_json::Map<_, _>>()
+                .into(),
+        }
+    }
+    let mut seed = 42;
+    for _ in 0..200 {
+        let value = arbitrary(&mut seed, 4);
+        let input = std::io::Cursor::new(serde_json::to_vec(&value).unwrap());
+        let streamed: Vec<_> = stream::<Json, _>(input, Options::default())
+            .map(Result::unwrap)
+            .collect();
+        assert_eq!(walk(&value), streamed, "{value}");
+    }
+}
+
+#[test]
+fn trailing_whitespace_is_not_an_error() {
+    for input in ["{\"a\":1}\n\n", "[1,2]  ", "1 \t\r\n ", "", " \n"] {
+        for skip_errors in [false, true] {
+            let options = Options::builder().skip_errors(skip_errors).build();
+            let result: Vec<_> = stream::<Json, _>(input.as_bytes(), options).collect();
+            assert!(result.iter().all(Result::is_ok), "{input:?}: {result:?}");
+        }
+    }
+    // Nor after resyncing past a bad document.
+    let options = Options::builder().skip_errors(true).build();
+    let result: Vec<_> = stream::<Json, _>(&b"{\"a\":}\n\n  "[..], options).collect();
+    assert!(matches!(result[..], [Err(StreamError::Deserialize { .. })]));
+}
+
+#[test]
+fn integers_beyond_2_53_are_exact() {
+    use stream::{Index, PathValue, PrimitiveValue};
+    let input: &[u8] = b"[9007199254740992, 9007199254740993, -18446744073709551615, 1e16]";
+    let leaves: Vec<_> = stream::<Json, _>(input, Options::default())
+        .map(Result::unwrap)
+        .filter_map(|PathValue { path, value, .. }| Some((path, value?)))
+        .collect();
+    assert_eq!(
+        leaves,
+        [
+            (
+                vec![Index::Array(0)],
+                PrimitiveValue::Number(9007199254740992.0)
+            ),
+            (
+                vec![Index::Array(1)],
+                PrimitiveValue::Integer(9007199254740993)
+            ),
+            (
+                vec![Index::Array(2)],
+                PrimitiveValue::Number(-18446744073709551615.0)
+            ),
+            (vec![Index::Array(3)], PrimitiveValue::Number(1e16)),
+        ]
+    );
+}
+
+#[test]
+fn stream_mmap_matches_stream() {
+    use std::io::Write;
+    use stream::stream_mmap;
+    let input = b"{\"a\": [1, \"x\"]}\n[] 3\n{\"b\": ";
+    let mut file = tempfile::NamedTempFile::new().unwrap();
+    file.write_all(input).unwrap();
+    // SAFETY: Nothing else knows about the temporary files.
+    let mapped: Vec<_> = unsafe { stream_mmap::<Json>(file.path(), Options::default()) }
+        .unwrap()
+        .collect();
+    let read: Vec<_> = stream::<Json, _>(&input[..], Options::default()).collect();
+    assert_eq!(mapped.len(), read.len());
+    for (mapped, read) in mapped.iter().zip(&read) {
+        match (mapped, read) {
+            (Ok(mapped), Ok(read)) => assert_eq!(mapped, read),
+            (
+                Err(StreamError::Deserialize { line: 3, .. }),
+                Err(StreamError::Deserialize { line: 3, .. }),
+            ) => {}
+            _ => panic!("{mapped:?} != {read:?}"),
+        }
+    }
+
+    let empty = tempfile::NamedTempFile::new().unwrap();
+    assert_eq!(
+        unsafe { stream_mmap::<Json>(empty.path(), Options::default()) }
+            .unwrap()
+            .count(),
+        0
+    );
+}
+
+#[test]
+fn take_while_prefix_stops_reading() {
+    use stream::{filter::TakeWhilePrefix, path::parse_path};
+    /// `{"a": 1}`, followed by endless `{"b": 1}` documents.
+    struct Endless {
+        started: bool,
+    }
+    impl std::io::Read for Endless {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            let chunk: &[u8] = if std::mem::replace(&mut self.started, true) {
+                b"{\"b\": 1}\n"
+            } else {
+                b"{\"a\": 1}\n"
+            };
+            let len = chunk.len().min(buf.len());
+            buf[..len].copy_from_slice(&chunk[..len]);
+            Ok(len)
+        }
+    }
+    let values = stream::<Json, _>(Endless { started: false }, Options::default());
+    let taken: Vec<_> = TakeWhilePrefix::new(values, parse_path(".a").unwrap())
+        .map(Result::unwrap)
+        .collect();
+    assert_eq!(taken.len(), 1);
+}
+
+#[test]
+fn buffer_does_not_change_the_stream() {
+    let input = b"{\"a\": [1, {\"b\": null}]} [] \"x\"";
+    let options = Options::builder().buffer(4).build();
+    let buffered: Vec<_> = stream::<Json, _>(&input[..], options)
+        .map(Result::unwrap)
+        .collect();
+    let unbuffered: Vec<_> = stream::<Json, _>(&input[..], Options::default())
+        .map(Result::unwrap)
+        .collect();
+    assert_eq!(buffered, unbuffered);
+}
+
+#[test]
+fn extract_deserializes_a_subtree() {
+    use std::collections::BTreeMap;
+    use stream::{extract, path::parse_path};
+    let input =
+        b"{\"a\": 1} {\"config\": {\"limits\": {\"cpu\": 2, \"mem\": 512}, \"x\": [true, \"y\"]}}";
+    let at = |path| parse_path(path).unwrap();
+    let limits: BTreeMap<String, u32> =
+        extract::<Json, _, _>(&input[..], &at(".config.limits"), Options::default()).unwrap();
+    assert_eq!(
+        limits,
+        BTreeMap::from([("cpu".into(), 2), ("mem".into(), 512)])
+    );
+    let x: (bool, String) =
+        extract::<Json, _, _>(&input[..], &at(".config.x"), Options::default()).unwrap();
+    assert_eq!(x, (true, "y".into()));
+    let cpu: u32 =
+        extract::<Json, _, _>(&input[..], &at(".config.limits.cpu"), Options::default()).unwrap();
+    assert_eq!(cpu, 2);
+
+    assert!(matches!(
+        extract::<Json, u32, _>(&input[..], &at(".b"), Options::default()),
+        Err(StreamError::NotFound { .. })
+    ));
+    assert!(matches!(
+        extract::<Json, u32, _>(&input[..], &at(".config.x"), Options::default()),
+        Err(StreamError::Deserialize { .. })
+    ));
+}
+
+#[test]
+fn stream_files_orders_by_file_or_interleaves() {
+    use std::io::Write;
+    use stream::{stream_files, Index, MergeOrder, PathValue};
+    let files: Vec<_> = (0..4)
+        .map(|i| {
+            let mut file = tempfile::NamedTempFile::new().unwrap();
+            write!(file, "[{i}, {i}] {i}").unwrap();
+            file
+        })
+        .collect();
+    let paths: Vec<_> = files.iter().map(|file| file.path().to_path_buf()).collect();
+    let by_file: Vec<_> =
+        stream_files::<Json>(paths.clone(), 3, MergeOrder::ByFile, Options::default())
+            .map(Result::unwrap)
+            .collect();
+    let one_by_one: Vec<_> = paths
+        .iter()
+        .enumerate()
+        .flat_map(|(i, path)| {
+            let values = stream::<Json, _>(std::fs::File::open(path).unwrap(), Options::default());
+            values.map(move |value| {
+                let mut value = value.unwrap();
+                value.path.insert(0, Index::Array(i));
+                value
+            })
+        })
+        .collect();
+    assert_eq!(by_file, one_by_one);
+
+    let mut interleaved: Vec<_> =
+        stream_files::<Json>(paths, 3, MergeOrder::Interleaved, Options::default())
+            .map(Result::unwrap)
+            .collect();
+    // Sorting is stable, so this only brings the values of each file together.
+    interleaved.sort_by_key(|value: &PathValue| match value.path[0] {
+        Index::Array(i) => i,
+        Index::Map(_) | Index::OrderedKey { .. } => unreachable!(),
+    });
+    assert_eq!(interleaved, one_by_one);
+}
+
+#[test]
+fn path_values_can_be_replayed() {
+    use stream::{Index, PathValue, PrimitiveValue};
+    let input: &[u8] = b"{\"a\": [1.5, \"x\", [], {}], \"b\": 9007199254740993}";
+    let mut values: Vec<PathValue> = stream::<Json, _>(input, Options::default())
+        .map(Result::unwrap)
+        .collect();
+    values.push(PathValue {
+        path: vec![Index::Map("c".to_string()), Index::Array(0)],
+        value: Some(PrimitiveValue::Bytes(vec![0, 255])),
+        count: None,
+    });
+    let replayed = values.clone();
+    assert_eq!(replayed, values);
+    assert!(replayed
+        .iter()
+        .any(|v| matches!(v.value, Some(PrimitiveValue::Integer(_)))));
+}
+
+#[test]
+fn spans_point_at_the_leaves() {
+    let input = "[\"a\\\"b\", -1.5e3, null, {\"k\": []}]\n[\n  42\n]";
+    let leaves: Vec<_> = stream::stream_with_spans(input.as_bytes(), Options::default())
+        .map(Result::unwrap)
+        .filter_map(|(value, span)| Some((value.value?, span.unwrap())))
+        .map(|(_, span)| &input[span])
+        .collect();
+    assert_eq!(leaves, ["\"a\\\"b\"", "-1.5e3", "null", "[]", "42"]);
+}
+
+#[test]
+fn try_detect_format_falls_back_to_yaml_only_if_it_parses() {
+    use stream::try_detect_format;
+    assert_eq!(try_detect_format(b"1 2"), Some(Format::Json));
+    assert_eq!(try_detect_format(b"[1]\n{}\n"), Some(Format::Jsonl));
+    assert_eq!(try_detect_format(b"{a: 1}"), Some(Format::Yaml));
+    assert_eq!(try_detect_format(b"- 1\n- [2, 3]\n"), Some(Format::Yaml));
+    assert_eq!(try_detect_format(b"[1, 2"), None);
+    assert_eq!(try_detect_format(b"a: 1\n b: 2\n"), None);
+}
+
+#[test]
+fn try_detect_format_falls_back_to_toml() {
+    use stream::try_detect_format;
+    assert_eq!(try_detect_format(b"[a]\nb = 1\n"), Some(Format::Toml));
+    // A single string in YAML.
+    assert_eq!(try_detect_format(b"a = 1\nb = \"x\"\n"), Some(Format::Toml));
+    assert_eq!(try_detect_format(b"a = 1 2\n"), Some(Format::Yaml));
+    assert_eq!(try_detect_format(b"a:\n  b = 1\n"), Some(Format::Yaml));
+    assert_eq!(try_detect_format(b"[a]\nb = \n"), None);
+}
+
+#[test]
+fn reservoir_sample_is_uniform_and_in_order() {
+    use stream::{filter::ReservoirSample, stream_value};
+    let input = serde_json::Value::from((0..10).collect::<Vec<_>>());
+    let mut counts = [0; 10];
+    for seed in 0..2000 {
+        let values = stream_value(&input, Options::default());
+        let sample: Vec<_> = ReservoirSample::new(values, 3, Some(seed))
+            .map(|v| match v.unwrap().path[..] {
+                [stream::Index::Array(i)] => i,
+                ref path => panic!("{path:?}"),
+            })
+            .collect();
+        assert_eq!(sample.len(), 3);
+        assert!(sample.windows(2).all(|w| w[0] < w[1]), "{sample:?}");
+        sample.iter().for_each(|&i| counts[i] += 1);
+    }
+    // Each leaf is sampled 600 times on average.
+    assert!(
+        counts.iter().all(|&n| (500..700).contains(&n)),
+        "{counts:?}"
+    );
+}
+
+#[test]
+fn detect_format_from_path_skips_the_compression_extension() {
+    use std::path::Path;
+    use stream::{compression::Compression, detect_format_from_path};
+    let format = |path| detect_format_from_path(Path::new(path));
+    assert_eq!(format("data.yaml.zst"), Some(Format::Yaml));
+    assert_eq!(format("dir.d/data.jsonl.gz"), Some(Format::Jsonl));
+    assert_eq!(format("data.yml"), Some(Format::Yaml));
+    assert_eq!(format("data.gz"), None);
+    assert_eq!(format("data.txt.zst"), None);
+    assert_eq!(
+        Compression::from_path(Path::new("data.json.gz")),
+        Some(Compression::Gzip)
+    );
+    assert_eq!(Compression::from_path(Path::new("data.zst.json")), None);
+}
+
+#[test]
+fn index_object_keys_records_the_position_of_each_key() {
+    use stream::{Index, PathValue, PrimitiveValue};
+    let input: &[u8] = br#"{"c": 1, "a": 2, "b": 3}"#;
+    let options = Options::builder()
+        .index_object_keys(true)
+        .drop(vec![vec![Index::Map("a".into())]])
+        .build();
+    let key = |key: &str, index| Index::OrderedKey {
+        key: key.into(),
+        index,
+    };
+    let values: Vec<_> = stream::<Json, _>(input, options)
+        .map(Result::unwrap)
+        .collect();
+    assert_eq!(
+        values,
+        [
+            PathValue {
+                path: vec![key("c", 0)],
+                value: Some(PrimitiveValue::Number(1.0)),
+                count: None,
+            },
+            PathValue {
+                path: vec![key("b", 2)],
+                value: Some(PrimitiveValue::Number(3.0)),
+                count: None,
+            },
+            PathValue {
+                path: vec![key("b", 2)],
+                value: None,
+                count: None,
+            },
+        ]
+    );
+}
+
+#[test]
+fn try_detect_format_detects_binary_formats() {
+    use serde::Serialize;
+    use stream::{detect_binary_format, try_detect_format};
+    let document = serde_json::json!({"a": [1.5, "x"]});
+
+    let schema = apache_avro::Schema::parse_str(r#""string""#).unwrap();
+    let mut writer = apache_avro::Writer::new(&schema, vec![]).unwrap();
+    writer.append_value("x").unwrap();
+    let avro = writer.into_inner().unwrap();
+    assert_eq!(try_detect_format(&avro), Some(Format::Avro));
+
+    let mut serializer = serde_smile::Serializer::new(vec![]);
+    document.serialize(&mut serializer).unwrap();
+    let smile = serializer.into_inner();
+    assert_eq!(try_detect_format(&smile), Some(Format::Smile));
+
+    let mut cbor = vec![];
+    let tagged = ciborium::tag::Required::<_, 55799>(&document);
+    ciborium::into_writer(&tagged, &mut cbor).unwrap();
+    assert_eq!(try_detect_format(&cbor), Some(Format::CborSeq));
+    // Without the self-described tag, CBOR has no magic bytes.
+    let mut cbor = vec![];
+    ciborium::into_writer(&document, &mut cbor).unwrap();
+    assert_eq!(detect_binary_format(&cbor), None);
+
+    let mut msgpack = rmp_serde::to_vec(&document).unwrap();
+    assert_eq!(try_detect_format(&msgpack), Some(Format::MsgpackStream));
+    msgpack.pop();
+    assert_eq!(try_detect_format(&msgpack), None);
+}
+
+#[test]
+fn collapse_arrays_collapses_only_arrays_of_scalars() {
+    use stream::{values::into_values, PrimitiveValue};
+    let input: &[u8] = br#"{"tags": ["a", 1, null], "mixed": [1, [2, 3], {}, 4], "none": []}"#;
+    let options = Options::builder().collapse_arrays(true).build();
+    let values: Vec<_> = stream::<Json, _>(input, options.clone())
+        .map(Result::unwrap)
+        .filter_map(|v| Some((stream::path::format_path(&v.path), v.value?)))
+        .collect();
+    assert_eq!(
+        values,
+        [
+            (
+                ".tags".to_string(),
+                PrimitiveValue::Scalars(vec![
+                    PrimitiveValue::String("a".into()),
+                    PrimitiveValue::Number(1.0),
+                    PrimitiveValue::Null,
+                ])
+            ),
+            (".mixed[0]".to_string(), PrimitiveValue::Number(1.0)),
+            (
+                ".mixed[1]".to_string(),
+                PrimitiveValue::Scalars(vec![
+                    PrimitiveValue::Number(2.0),
+                    PrimitiveValue::Number(3.0)
+                ])
+            ),
+            (".mixed[2]".to_string(), PrimitiveValue::EmptyObject),
+            (".mixed[3]".to_string(), PrimitiveValue::Number(4.0)),
+            (".none".to_string(), PrimitiveValue::EmptyArray),
+        ]
+    );
+    let documents: Vec<_> = into_values(stream::<Json, _>(input, options))
+        .map(Result::unwrap)
+        .collect();
+    assert_eq!(
+        documents,
+        [serde_json::from_slice::<serde_json::Value>(input).unwrap()]
+    );
+}
+
+#[test]
+fn stream_state_reports_its_own_errors_and_stops_when_the_sink_does() {
+    use serde::de::DeserializeSeed;
+    use stream::{PathValue, StreamState};
+    let document = serde_json::json!({"a": [[1, 2], 3]});
+    let options = Options::builder().max_depth(2).build();
+    let mut path = vec![];
+    let mut state = StreamState::new(|_: PathValue| true, &mut path, options);
+    assert!(state.deserialize(&document).is_err());
+    assert!(matches!(
+        state.take_error(),
+        Some(StreamError::DepthExceeded { max_depth: 2, .. })
+    ));
+    drop(state);
+    assert!(path.is_empty());
+
+    let mut count = 0;
+    let mut state = StreamState::new(
+        |_: PathValue| {
+            count += 1;
+            false
+        },
+        &mut path,
+        Options::default(),
+    );
+    assert!(state.deserialize(&document).is_err());
+    assert!(state.take_error().is_none());
+    assert_eq!(count, 1);
+}
+
+#[test]
+fn close_with_count_counts_elements_and_entries() {
+    let input: &[u8] = br#"{"a": [1, [2, 3], []], "b": {"c": {}}}"#;
+    let options = Options::builder().close_with_count(true).build();
+    let closes: Vec<_> = stream::<Json, _>(input, options)
+        .map(Result::unwrap)
+        .filter(|v| v.value.is_none())
+        .map(|v| (stream::path::format_path(&v.path), v.count))
+        .collect();
+    assert_eq!(
+        closes,
+        [
+            (".a[1][1]".to_string(), Some(2)),
+            (".a[2]".to_string(), Some(3)),
+            (".b.c".to_string(), Some(1)),
+            (".b".to_string(), Some(2)),
+        ]
+    );
+}
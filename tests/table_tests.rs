@@ -0,0 +1,60 @@
+use parquet::{
+    file::reader::{FileReader, SerializedFileReader},
+    record::RowAccessor,
+};
+use stream::{
+    render::RenderOptions,
+    table::{ParquetWriter, ROWS_PER_GROUP},
+    walk,
+};
+
+fn read(file: std::fs::File) -> (usize, Vec<[String; 3]>) {
+    let reader = SerializedFileReader::new(file).unwrap();
+    let row_groups = reader.num_row_groups();
+    let rows = reader
+        .into_iter()
+        .map(|row| {
+            let row = row.unwrap();
+            [0, 1, 2].map(|i| row.get_string(i).unwrap().clone())
+        })
+        .collect();
+    (row_groups, rows)
+}
+
+#[test]
+fn leaves_are_rows_of_path_value_and_type() {
+    let document = serde_json::json!({"a": [1, "x y", null], "b": {}});
+    let mut table =
+        ParquetWriter::new(tempfile::tempfile().unwrap(), RenderOptions::default()).unwrap();
+    for value in walk(&document) {
+        table.write(&value).unwrap();
+    }
+    let (row_groups, rows) = read(table.finish().unwrap());
+    assert_eq!(row_groups, 1);
+    let row = |path: &str, value: &str, type_name: &str| {
+        [path.to_string(), value.to_string(), type_name.to_string()]
+    };
+    assert_eq!(
+        rows,
+        [
+            row(r#"["a",0]"#, "1", "number"),
+            row(r#"["a",1]"#, "x y", "string"),
+            row(r#"["a",2]"#, "null", "null"),
+            row(r#"["b"]"#, "{}", "object"),
+        ]
+    );
+}
+
+#[test]
+fn rows_are_written_in_groups() {
+    let document = serde_json::Value::from(vec![true; ROWS_PER_GROUP + 1]);
+    let mut table =
+        ParquetWriter::new(tempfile::tempfile().unwrap(), RenderOptions::default()).unwrap();
+    for value in walk(&document) {
+        table.write(&value).unwrap();
+    }
+    let (row_groups, rows) = read(table.finish().unwrap());
+    assert_eq!(row_groups, 2);
+    assert_eq!(rows.len(), ROWS_PER_GROUP + 1);
+    assert_eq!(rows[ROWS_PER_GROUP][0], format!("[{ROWS_PER_GROUP}]"));
+}
@@ -0,0 +1,100 @@
+use serde_json::json;
+use stream::{
+    stream,
+    values::{into_values, write_canonical},
+    Json, Options,
+};
+
+#[test]
+fn into_values_round_trips() {
+    let input: &[u8] = br#"{"a": [1, [], {}], "b": {"c": "d"}} 1.5 "s" [] [[null]]"#;
+    let values: Vec<_> = into_values(stream::<Json, _>(input, Options::default()))
+        .map(Result::unwrap)
+        .collect();
+    assert_eq!(
+        values,
+        vec![
+            json!({"a": [1, [], {}], "b": {"c": "d"}}),
+            json!(1.5),
+            json!("s"),
+            json!([]),
+            json!([[null]]),
+        ]
+    );
+}
+
+#[test]
+fn canonical_form_sorts_keys() {
+    let mut out = vec![];
+    write_canonical(&mut out, &json!({"b": {"z": 1, "y": [true]}, "a": "x"})).unwrap();
+    assert_eq!(out, br#"{"a":"x","b":{"y":[true],"z":1}}"#);
+}
+
+/// The examples of RFC 8785, sections 3.2.2.3 and 3.2.3, and appendix B.
+#[test]
+fn canonical_form_matches_jcs_test_vectors() {
+    let canonical = |value: &serde_json::Value| {
+        let mut out = vec![];
+        write_canonical(&mut out, value).unwrap();
+        String::from_utf8(out).unwrap()
+    };
+    let input = r#"{
+        "numbers": [333333333.33333329, 1E30, 4.50, 2e-3, 0.000000000000000000000000001],
+        "string": "\u20ac$\u000F\u000aA'\u0042\u0022\u005c\\\"\/",
+        "literals": [null, true, false]
+    }"#;
+    assert_eq!(
+        canonical(&serde_json::from_str(input).unwrap()),
+        r#"{"literals":[null,true,false],"numbers":[333333333.3333333,1e+30,4.5,0.002,1e-27],"string":"€$\u000f\nA'B\"\\\\\"/"}"#
+    );
+
+    let input = r#"{
+        "€": "Euro Sign",
+        "\r": "Carriage Return",
+        "דּ": "Hebrew Letter Dalet With Dagesh",
+        "1": "One",
+        "😀": "Emoji: Grinning Face",
+        "\u0080": "Control",
+        "ö": "Latin Small Letter O With Diaeresis"
+    }"#;
+    let keys: Vec<String> = serde_json::from_str::<serde_json::Value>(&canonical(
+        &serde_json::from_str(input).unwrap(),
+    ))
+    .unwrap()
+    .as_object()
+    .unwrap()
+    .keys()
+    .cloned()
+    .collect();
+    assert_eq!(keys, ["\r", "1", "\u{80}", "ö", "€", "😀", "\u{fb33}"]);
+
+    for (bits, expected) in [
+        (0x0000000000000000, "0"),
+        (0x8000000000000000, "0"),
+        (0x0000000000000001, "5e-324"),
+        (0x8000000000000001, "-5e-324"),
+        (0x7fefffffffffffff, "1.7976931348623157e+308"),
+        (0xffefffffffffffff, "-1.7976931348623157e+308"),
+        (0x4340000000000000, "9007199254740992"),
+        (0xc340000000000000, "-9007199254740992"),
+        (0x4430000000000000, "295147905179352830000"),
+        (0x44b52d02c7e14af5, "9.999999999999997e+22"),
+        (0x44b52d02c7e14af6, "1e+23"),
+        (0x44b52d02c7e14af7, "1.0000000000000001e+23"),
+        (0x444b1ae4d6e2ef4e, "999999999999999700000"),
+        (0x444b1ae4d6e2ef4f, "999999999999999900000"),
+        (0x444b1ae4d6e2ef50, "1e+21"),
+        (0x3eb0c6f7a0b5ed8c, "9.999999999999997e-7"),
+        (0x3eb0c6f7a0b5ed8d, "0.000001"),
+        (0x41b3de4355555553, "333333333.3333332"),
+        (0x41b3de4355555554, "333333333.33333325"),
+        (0x41b3de4355555555, "333333333.3333333"),
+        (0x41b3de4355555556, "333333333.3333334"),
+        (0x41b3de4355555557, "333333333.33333343"),
+        (0xbecbf647612f3696, "-0.0000033333333333333333"),
+        (0x43143ff3c1cb0959, "1424953923781206.2"),
+    ] {
+        let v = f64::from_bits(bits);
+        assert_eq!(canonical(&json!(v)), expected, "{v:e}");
+    }
+}